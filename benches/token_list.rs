@@ -0,0 +1,46 @@
+//! Compares `parse_css` + `free_tokens` with the arena backed `parse_css_list` + `token_list_free`.
+//!
+//! Run with `cargo bench --bench token_list`.
+use std::hint::black_box;
+use std::mem::MaybeUninit;
+use std::time::{Duration, Instant};
+
+use cssparser_fii::{free_tokens, parse_css, token_list::{parse_css_list, token_list_free}};
+use safer_ffi::prelude::AsOut;
+
+const ITERATIONS: u32 = 200;
+
+fn time(name: &str, mut f: impl FnMut()) -> Duration {
+    // warm up
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_iteration = start.elapsed() / ITERATIONS;
+    println!("{name:<32} {per_iteration:>12.2?} per stylesheet");
+    per_iteration
+}
+
+fn main() {
+    // a bigger stylesheet makes the per-token costs dominate
+    let css = include_str!("../test/test.css").repeat(50);
+    let c_css = std::ffi::CString::new(css.clone()).unwrap();
+    println!("{} bytes of css", css.len());
+
+    let tokens = time("parse_css + free_tokens", || {
+        let mut tokens = MaybeUninit::uninit();
+        parse_css(c_css.as_ptr() as *const _, tokens.as_out(), None);
+        free_tokens(black_box(unsafe { tokens.assume_init() }));
+    });
+
+    let list = time("parse_css_list + token_list_free", || {
+        let mut list = MaybeUninit::uninit();
+        parse_css_list(css.as_ptr(), css.len(), list.as_out(), None);
+        if let Some(list) = black_box(unsafe { list.assume_init() }) {
+            token_list_free(list);
+        }
+    });
+
+    println!("the token list is {:.2}x as fast", tokens.as_secs_f64() / list.as_secs_f64());
+}
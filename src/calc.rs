@@ -0,0 +1,603 @@
+#![allow(nonstandard_style)]
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{c_slice, repr_c, Out}};
+
+use crate::{
+    block_end,
+    cstr,
+    error::{catch_panic, finish, Error, ErrorKind, Status},
+    input_str,
+    layout::breadth_first,
+    location::SourceLocation,
+    tokenize,
+    Token,
+};
+
+/// The type of a math expression or of one of its nodes.
+///
+/// Lengths, angles and times can be combined with percentages, see [`CalcNode::has_percentage`].
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalcType {
+    Number,
+    Length,
+    Angle,
+    Time,
+    /// Only percentages (and numbers they are multiplied by).
+    Percentage,
+}
+
+impl CalcType {
+    /// The type for error messages, such as `an angle`.
+    fn describe(self) -> &'static str {
+        match self {
+            CalcType::Number => "a number",
+            CalcType::Length => "a length",
+            CalcType::Angle => "an angle",
+            CalcType::Time => "a time",
+            CalcType::Percentage => "a percentage",
+        }
+    }
+}
+
+/// What a [`CalcNode`] computes.
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalcNodeType {
+    /// A number, dimension or percentage, see `value` and `unit`.
+    Value,
+    /// The sum of the children, `a - b` is stored as `a + (-b)`.
+    Sum,
+    /// The product of the children, `a / b` is stored as `a * (1 / b)`.
+    Product,
+    /// The opposite of the only child.
+    Negate,
+    /// One divided by the only child, which is a number.
+    Invert,
+    Min,
+    Max,
+    /// `clamp(MIN, VAL, MAX)`, the children are in that order.
+    Clamp,
+}
+
+/// A node of a [`CalcExpression`].
+///
+/// The operands of a node are `expression.nodes.ptr[first_child .. first_child + child_count]`.
+/// `calc()` and parentheses only group, they do not get a node of their own.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct CalcNode {
+    pub node_type: CalcNodeType,
+    pub value_type: CalcType,
+    /// Whether percentages are part of the value, `100% - 8px` is a length with percentages.
+    pub has_percentage: bool,
+    /// The number of a `Value` as written (`50` for `50%`), the constants `e`, `pi`, `infinity` and `NaN` are numbers.
+    pub value: f32,
+    /// The unit of a dimension in lowercase, empty otherwise.
+    pub unit: cstr,
+    pub first_child: usize,
+    pub child_count: usize,
+    pub location: SourceLocation,
+}
+
+/// A parsed `calc()`, `min()`, `max()` or `clamp()`, free it with `free_calc_expression`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct CalcExpression {
+    /// The root of the expression comes first, its type is the one of the whole expression.
+    pub nodes: repr_c::Vec<CalcNode>,
+}
+
+/// What `calc_evaluate` resolves relative units and percentages against, `calc_context_default` matches
+/// the defaults of `MediaEnvironment_t`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalcContext {
+    /// What `100%` is, in the unit of the result (see `calc_evaluate`).
+    pub percentage_basis: f32,
+    /// The font size of the element and of the root element in pixels, what `1em` and `1rem` are.
+    pub font_size: f32,
+    pub root_font_size: f32,
+    /// The line height of the element and of the root element in pixels, what `1lh` and `1rlh` are.
+    pub line_height: f32,
+    pub root_line_height: f32,
+    /// The x-height and the advance of `0` of the font in pixels, what `1ex` and `1ch` are.
+    pub x_height: f32,
+    pub ch_width: f32,
+    /// The size of the viewport in pixels, for `vw`, `vh`, `vmin` and `vmax`.
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+}
+
+impl CalcContext {
+    /// A context where the element and the root element have the font size `font_size`.
+    ///
+    /// Without font metrics, the line height is `1.2em` and the x-height and the advance of `0` are `0.5em`.
+    pub fn new(font_size: f32, viewport_width: f32, viewport_height: f32) -> Self {
+        Self {
+            percentage_basis: 0.0,
+            font_size,
+            root_font_size: font_size,
+            line_height: font_size * 1.2,
+            root_line_height: font_size * 1.2,
+            x_height: font_size / 2.0,
+            ch_width: font_size / 2.0,
+            viewport_width,
+            viewport_height,
+        }
+    }
+}
+
+impl Default for CalcContext {
+    fn default() -> Self {
+        Self::new(16.0, 1024.0, 768.0)
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// How many pixels one `unit` is, relative units resolve against `context`.
+pub(crate) fn length_unit(unit: &str, context: &CalcContext) -> Option<f64> {
+    let (width, height) = (f64::from(context.viewport_width), f64::from(context.viewport_height));
+    let pixels = match unit {
+        "px" => 1.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        "q" => 96.0 / 101.6,
+        "in" => 96.0,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        "em" => f64::from(context.font_size),
+        "rem" => f64::from(context.root_font_size),
+        "lh" => f64::from(context.line_height),
+        "rlh" => f64::from(context.root_line_height),
+        "ex" => f64::from(context.x_height),
+        "ch" => f64::from(context.ch_width),
+        "vw" => width / 100.0,
+        "vh" => height / 100.0,
+        "vmin" => width.min(height) / 100.0,
+        "vmax" => width.max(height) / 100.0,
+        _ => return None,
+    };
+    Some(pixels)
+}
+
+/// How many degrees one `unit` is.
+fn angle_unit(unit: &str) -> Option<f64> {
+    match unit {
+        "deg" => Some(1.0),
+        "grad" => Some(0.9),
+        "rad" => Some(180.0 / std::f64::consts::PI),
+        "turn" => Some(360.0),
+        _ => None,
+    }
+}
+
+/// How many seconds one `unit` is.
+fn time_unit(unit: &str) -> Option<f64> {
+    match unit {
+        "s" => Some(1.0),
+        "ms" => Some(0.001),
+        _ => None,
+    }
+}
+
+/// The type of a dimension in `unit`, which is in lowercase.
+fn unit_type(unit: &str) -> Option<CalcType> {
+    match unit {
+        "px" | "cm" | "mm" | "q" | "in" | "pt" | "pc" | "em" | "rem" | "lh" | "rlh" | "ex" | "ch" | "vw" | "vh" | "vmin" | "vmax" => Some(CalcType::Length),
+        "deg" | "grad" | "rad" | "turn" => Some(CalcType::Angle),
+        "s" | "ms" => Some(CalcType::Time),
+        _ => None,
+    }
+}
+
+/// A node that owns its operands, before it is laid out in a [`CalcExpression`].
+struct CalcTree {
+    node: CalcNode,
+    children: Vec<CalcTree>,
+}
+
+impl CalcTree {
+    fn value(value_type: CalcType, value: f32, unit: &str, location: SourceLocation) -> Self {
+        let node = CalcNode {
+            node_type: CalcNodeType::Value,
+            value_type,
+            has_percentage: value_type == CalcType::Percentage,
+            value,
+            unit: unit.to_string().into(),
+            first_child: 0,
+            child_count: 0,
+            location,
+        };
+        Self { node, children: Vec::new() }
+    }
+
+    /// An operation on `children`, which fails when their types cannot be combined by it.
+    fn new(node_type: CalcNodeType, children: Vec<CalcTree>, location: SourceLocation) -> Result<Self> {
+        let mut types = children.iter().map(|child| (child.node.value_type, child.node.has_percentage));
+        let (value_type, has_percentage) = match node_type {
+            CalcNodeType::Negate => types.next().unwrap(),
+            CalcNodeType::Invert => match types.next().unwrap() {
+                (CalcType::Number, _) => (CalcType::Number, false),
+                (value_type, _) => return Err(Error::new(ErrorKind::Custom, format!("cannot divide by {}", value_type.describe())).at(at_start(location))),
+            },
+            CalcNodeType::Product => types.reduce(|left, right| match (left, right) {
+                ((CalcType::Number, _), other) | (other, (CalcType::Number, _)) => other,
+                // reported below, the type does not matter
+                _ => (CalcType::Number, false),
+            }).unwrap(),
+            _ => types.map(Some).reduce(|left, right| match (left?, right?) {
+                ((left, left_percentage), (right, right_percentage)) if left == right => Some((left, left_percentage || right_percentage)),
+                ((CalcType::Percentage, _), (other @ (CalcType::Length | CalcType::Angle | CalcType::Time), _))
+                | ((other @ (CalcType::Length | CalcType::Angle | CalcType::Time), _), (CalcType::Percentage, _)) => Some((other, true)),
+                _ => None,
+            }).flatten().ok_or_else(|| Error::new(ErrorKind::Custom, format!("cannot combine {}", describe_types(&children))).at(at_start(location)))?,
+        };
+        if node_type == CalcNodeType::Product && children.iter().filter(|child| child.node.value_type != CalcType::Number).count() > 1 {
+            return Err(Error::new(ErrorKind::Custom, format!("cannot multiply {}", describe_types(&children))).at(at_start(location)));
+        }
+        let node = CalcNode {
+            node_type,
+            value_type,
+            has_percentage,
+            value: 0.0,
+            unit: String::new().into(),
+            first_child: 0,
+            child_count: children.len(),
+            location,
+        };
+        Ok(Self { node, children })
+    }
+}
+
+/// The types of `children`, such as `a length and an angle`.
+fn describe_types(children: &[CalcTree]) -> String {
+    let names: Vec<_> = children.iter().map(|child| child.node.value_type.describe()).collect();
+    match names.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} and {last}", rest.join(", ")),
+        None => String::new(),
+    }
+}
+
+/// The range from the start of `first` to the end of `last`.
+fn span(first: SourceLocation, last: SourceLocation) -> SourceLocation {
+    SourceLocation { end: last.end, ..first }
+}
+
+/// The empty location at the start of `location`, errors point at a position.
+fn at_start(location: SourceLocation) -> SourceLocation {
+    SourceLocation { end: location.start, ..location }
+}
+
+fn unexpected(token: &Token) -> Error {
+    let value = cssparser::Token::from(&*token.value);
+    Error::new(ErrorKind::UnexpectedToken, format!("unexpected token: {value:?}")).at(at_start(token.location))
+}
+
+/// Reads the tokens of a block, skipping whitespace and comments.
+struct Cursor<'a> {
+    tokens: &'a [Token],
+    index: usize,
+    /// Where the block ends, for errors at the end of it.
+    end: SourceLocation,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(tokens: &'a [Token], end: SourceLocation) -> Self {
+        Self { tokens, index: 0, end }
+    }
+
+    fn peek(&mut self) -> Option<(&'a Token, cssparser::Token<'a>)> {
+        while let Some(token) = self.tokens.get(self.index) {
+            match cssparser::Token::from(&*token.value) {
+                cssparser::Token::WhiteSpace(_) | cssparser::Token::Comment(_) => self.index += 1,
+                value => return Some((token, value)),
+            }
+        }
+        None
+    }
+
+    fn next(&mut self) -> Result<(&'a Token, cssparser::Token<'a>)> {
+        let next = self.peek().ok_or_else(|| Error::new(ErrorKind::EndOfInput, "unexpected end of input").at(at_start(self.end)))?;
+        self.index += 1;
+        Ok(next)
+    }
+
+    fn expect_exhausted(&mut self) -> Result<()> {
+        match self.peek() {
+            Some((token, _)) => Err(unexpected(token)),
+            None => Ok(()),
+        }
+    }
+
+    /// The block or function opened by the token just read, which is skipped, and where it ends.
+    fn block(&mut self) -> (&'a [Token], SourceLocation) {
+        let open = self.index - 1;
+        match block_end(self.tokens, open) {
+            Some(close) => {
+                self.index = close + 1;
+                (&self.tokens[open + 1..close], self.tokens[close].location)
+            },
+            // left open at the end of the input
+            None => {
+                self.index = self.tokens.len();
+                (&self.tokens[open + 1..], self.end)
+            },
+        }
+    }
+
+    /// Whether the token at `index` touches the token before or after it in the input, whitespace tokens never do.
+    fn touches_neighbours(&self, index: usize) -> bool {
+        let location = self.tokens[index].location;
+        let touches = |neighbour: Option<&Token>, adjacent: &dyn Fn(SourceLocation) -> bool| match neighbour {
+            Some(neighbour) => !matches!(cssparser::Token::from(&*neighbour.value), cssparser::Token::WhiteSpace(_)) && adjacent(neighbour.location),
+            None => false,
+        };
+        touches(index.checked_sub(1).map(|before| &self.tokens[before]), &|before| before.end == location.start)
+            || touches(self.tokens.get(index + 1), &|after| after.start == location.end)
+    }
+}
+
+/// Parses the `+` and `-` separated products of `cursor`.
+fn parse_sum(cursor: &mut Cursor<'_>) -> Result<CalcTree> {
+    let mut terms = vec![parse_product(cursor)?];
+    while let Some((token, cssparser::Token::Delim(operator @ ('+' | '-')))) = cursor.peek() {
+        // `1px -2px` is two values, so `+` and `-` need whitespace on both sides
+        if cursor.touches_neighbours(cursor.index) {
+            return Err(Error::new(ErrorKind::Custom, format!("`{operator}` must be surrounded by whitespace")).at(at_start(token.location)));
+        }
+        cursor.index += 1;
+        let term = parse_product(cursor)?;
+        terms.push(match operator {
+            '-' => {
+                let location = span(token.location, term.node.location);
+                CalcTree::new(CalcNodeType::Negate, vec![term], location)?
+            },
+            _ => term,
+        });
+    }
+    operation(CalcNodeType::Sum, terms)
+}
+
+/// Parses the `*` and `/` separated values of `cursor`.
+fn parse_product(cursor: &mut Cursor<'_>) -> Result<CalcTree> {
+    let mut factors = vec![parse_value(cursor)?];
+    while let Some((token, cssparser::Token::Delim(operator @ ('*' | '/')))) = cursor.peek() {
+        cursor.index += 1;
+        let factor = parse_value(cursor)?;
+        factors.push(match operator {
+            '/' => {
+                let location = span(token.location, factor.node.location);
+                CalcTree::new(CalcNodeType::Invert, vec![factor], location)?
+            },
+            _ => factor,
+        });
+    }
+    operation(CalcNodeType::Product, factors)
+}
+
+/// A `Sum` or `Product` of `operands`, or the operand itself when there is only one.
+fn operation(node_type: CalcNodeType, mut operands: Vec<CalcTree>) -> Result<CalcTree> {
+    if operands.len() == 1 {
+        return Ok(operands.pop().unwrap());
+    }
+    let location = span(operands[0].node.location, operands[operands.len() - 1].node.location);
+    CalcTree::new(node_type, operands, location)
+}
+
+fn parse_value(cursor: &mut Cursor<'_>) -> Result<CalcTree> {
+    let (token, value) = cursor.next()?;
+    match value {
+        cssparser::Token::Number { value, .. } => Ok(CalcTree::value(CalcType::Number, value, "", token.location)),
+        cssparser::Token::Percentage { unit_value, .. } => Ok(CalcTree::value(CalcType::Percentage, unit_value * 100.0, "", token.location)),
+        cssparser::Token::Dimension { value, unit, .. } => {
+            let unit = unit.to_ascii_lowercase();
+            let value_type = unit_type(&unit)
+                .ok_or_else(|| Error::new(ErrorKind::Custom, format!("unsupported unit `{unit}`")).at(at_start(token.location)))?;
+            Ok(CalcTree::value(value_type, value, &unit, token.location))
+        },
+        cssparser::Token::Ident(name) => {
+            let value = cssparser::match_ignore_ascii_case! { &name,
+                "e" => std::f32::consts::E,
+                "pi" => std::f32::consts::PI,
+                "infinity" => f32::INFINITY,
+                "-infinity" => f32::NEG_INFINITY,
+                "nan" => f32::NAN,
+                _ => return Err(unexpected(token)),
+            };
+            Ok(CalcTree::value(CalcType::Number, value, "", token.location))
+        },
+        cssparser::Token::ParenthesisBlock => {
+            let (tokens, end) = cursor.block();
+            parse_entire_sum(tokens, end)
+        },
+        cssparser::Token::Function(_) => parse_function(cursor, token),
+        _ => Err(unexpected(token)),
+    }
+}
+
+/// Parses a sum that must be all of `tokens`.
+fn parse_entire_sum(tokens: &[Token], end: SourceLocation) -> Result<CalcTree> {
+    let mut cursor = Cursor::new(tokens, end);
+    let sum = parse_sum(&mut cursor)?;
+    cursor.expect_exhausted()?;
+    Ok(sum)
+}
+
+/// Parses the math function `function`, which was just read from `cursor`.
+fn parse_function(cursor: &mut Cursor<'_>, function: &Token) -> Result<CalcTree> {
+    let cssparser::Token::Function(name) = cssparser::Token::from(&*function.value) else {
+        return Err(unexpected(function));
+    };
+    let (node_type, arguments) = cssparser::match_ignore_ascii_case! { &name,
+        "calc" => (None, 1..=1),
+        "min" => (Some(CalcNodeType::Min), 1..=usize::MAX),
+        "max" => (Some(CalcNodeType::Max), 1..=usize::MAX),
+        "clamp" => (Some(CalcNodeType::Clamp), 3..=3),
+        _ => return Err(unexpected(function)),
+    };
+    let (tokens, end) = cursor.block();
+    let location = span(function.location, end);
+
+    // the arguments are separated by the commas that are not nested in a block
+    let mut operands = Vec::new();
+    let mut start = 0;
+    let mut index = 0;
+    while index <= tokens.len() {
+        let comma = match tokens.get(index).map(|token| cssparser::Token::from(&*token.value)) {
+            Some(cssparser::Token::Comma) => tokens[index].location,
+            None => end,
+            Some(cssparser::Token::Function(_) | cssparser::Token::ParenthesisBlock | cssparser::Token::SquareBracketBlock | cssparser::Token::CurlyBracketBlock) => {
+                index = block_end(tokens, index).unwrap_or(tokens.len());
+                index += 1;
+                continue;
+            },
+            Some(_) => {
+                index += 1;
+                continue;
+            },
+        };
+        if operands.len() == *arguments.end() {
+            return Err(unexpected(&tokens[start - 1]));
+        }
+        operands.push(parse_entire_sum(&tokens[start..index], comma)?);
+        index += 1;
+        start = index;
+    }
+    if operands.len() < *arguments.start() {
+        let expected = Error::new(ErrorKind::Custom, format!("`{name}()` takes {} arguments", arguments.start()));
+        return Err(expected.at(at_start(end)));
+    }
+    match node_type {
+        Some(node_type) => CalcTree::new(node_type, operands, location),
+        None => Ok(operands.pop().unwrap()),
+    }
+}
+
+/// Parses `tokens` (as returned by `parse_css`, or the value of a declaration) as a single math function.
+///
+/// Tokens from `parse_css` have no whitespace, whether a `+` or `-` is surrounded by it is read from their locations.
+/// Their functions are not closed either, one without a closing token runs to the end of `tokens`.
+pub fn calc_expression_tokens(tokens: &[Token]) -> Result<CalcExpression> {
+    let end = tokens.last().map(|token| SourceLocation { start: token.location.end, ..token.location }).unwrap_or_default();
+    let mut cursor = Cursor::new(tokens, end);
+    let (function, value) = cursor.next()?;
+    let root = match value {
+        cssparser::Token::Function(_) => parse_function(&mut cursor, function)?,
+        _ => return Err(unexpected(function)),
+    };
+    cursor.expect_exhausted()?;
+
+    let nodes = breadth_first(vec![root], |tree| std::mem::take(&mut tree.children))
+        .into_iter()
+        .map(|(tree, first_child, child_count)| CalcNode { first_child, child_count, ..tree.node })
+        .collect::<Vec<_>>();
+    Ok(CalcExpression { nodes: nodes.into() })
+}
+
+/// Parses `input` as a single math function, such as `calc(100% - 2 * 8px)`.
+pub fn calc_expression(input: &str) -> Result<CalcExpression> {
+    calc_expression_tokens(&tokenize(input, true)?)
+}
+
+impl CalcExpression {
+    /// The value of the expression, see `calc_evaluate`.
+    pub fn evaluate(&self, context: &CalcContext) -> f32 {
+        match self.nodes.is_empty() {
+            true => f32::NAN,
+            false => self.evaluate_node(0, context) as f32,
+        }
+    }
+
+    fn evaluate_node(&self, index: usize, context: &CalcContext) -> f64 {
+        let node = &self.nodes[index];
+        let mut children = (node.first_child..node.first_child + node.child_count).map(|child| self.evaluate_node(child, context));
+        let value = f64::from(node.value);
+        match node.node_type {
+            CalcNodeType::Value => match node.value_type {
+                CalcType::Number => value,
+                CalcType::Percentage => value / 100.0 * f64::from(context.percentage_basis),
+                CalcType::Length => value * length_unit(&node.unit, context).unwrap_or(f64::NAN),
+                CalcType::Angle => value * angle_unit(&node.unit).unwrap_or(f64::NAN),
+                CalcType::Time => value * time_unit(&node.unit).unwrap_or(f64::NAN),
+            },
+            CalcNodeType::Sum => children.sum(),
+            CalcNodeType::Product => children.product(),
+            CalcNodeType::Negate => -children.next().unwrap_or(f64::NAN),
+            CalcNodeType::Invert => 1.0 / children.next().unwrap_or(f64::NAN),
+            CalcNodeType::Min => children.reduce(min).unwrap_or(f64::NAN),
+            CalcNodeType::Max => children.reduce(max).unwrap_or(f64::NAN),
+            CalcNodeType::Clamp => {
+                let (lower, value, upper) = (children.next(), children.next(), children.next());
+                match (lower, value, upper) {
+                    (Some(lower), Some(value), Some(upper)) => max(lower, min(value, upper)),
+                    _ => f64::NAN,
+                }
+            },
+        }
+    }
+}
+
+/// Like `f64::min`, but NaN when either is, as `min()` is.
+fn min(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() { f64::NAN } else { a.min(b) }
+}
+
+fn max(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() { f64::NAN } else { a.max(b) }
+}
+
+fn empty_expression() -> CalcExpression {
+    CalcExpression { nodes: Vec::new().into() }
+}
+
+/// Parses the NUL-terminated string `input`, a single `calc()`, `min()`, `max()` or `clamp()`, into the tree written to `expression`.
+///
+/// Operands whose types cannot be combined (`calc(1px + 2deg)`, `calc(1px * 2px)`, `calc(1px / 1px)`)
+/// and units other than lengths, angles and times fail with `ERROR_KIND_CUSTOM`.
+/// Out-params otherwise behave like they do for `parse_css`, `expression` must be released with `free_calc_expression`.
+#[ffi_export]
+pub fn parse_calc(input: *const safer_ffi::c_char, expression: Out<'_, CalcExpression>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        calc_expression(input_str(input)?)
+    });
+
+    finish(result, expression, empty_expression, error)
+}
+
+/// Like `parse_calc`, for tokens returned by `parse_css` (such as the value of a `Declaration_t`).
+#[ffi_export]
+pub fn parse_calc_tokens(tokens: c_slice::Ref<'_, Token>, expression: Out<'_, CalcExpression>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        calc_expression_tokens(tokens.as_slice())
+    });
+
+    finish(result, expression, empty_expression, error)
+}
+
+#[ffi_export]
+pub fn calc_context_default() -> CalcContext {
+    CalcContext::default()
+}
+
+/// Evaluates `expression` in `context`.
+///
+/// The result is in pixels for a length, degrees for an angle and seconds for a time.
+/// Percentages are `percentage_basis` times their fraction, so when the expression is a `CALC_TYPE_PERCENTAGE`
+/// the result is in the unit of the basis (set it to 100 to keep percentages).
+/// Like in CSS, dividing by zero gives an infinity and the result may be NaN, which the caller has to clamp.
+#[ffi_export]
+pub fn calc_evaluate(expression: &CalcExpression, context: &CalcContext) -> f32 {
+    catch_panic(|| Ok(expression.evaluate(context))).unwrap_or(f32::NAN)
+}
+
+#[ffi_export]
+pub fn free_calc_expression(expression: CalcExpression) {
+    drop(expression);
+}
@@ -0,0 +1,458 @@
+#![allow(nonstandard_style)]
+use std::convert::Infallible;
+
+use cssparser::{color::PredefinedColorSpace, match_ignore_ascii_case, ParseError, Parser};
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{c_slice, Out}};
+
+use crate::{error::{catch_panic, finish, Error, Status}, input_str, serialize::parse_tokens, Token};
+
+/// The space the components of a [`Color`] are in, and how to read them.
+///
+/// Components that accept percentages store them scaled to the range given here.
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// `currentcolor`, which depends on the element it is used on, the components are unused.
+    CurrentColor,
+    /// Hex and named colors, `rgb()` and `color(srgb ...)`: red, green and blue from 0 to 1.
+    Srgb,
+    /// `hsl()`: hue in degrees, saturation and lightness from 0 to 100.
+    Hsl,
+    /// `hwb()`: hue in degrees, whiteness and blackness from 0 to 100.
+    Hwb,
+    /// `lab()`: lightness from 0 to 100, `a` and `b` around 0 (100% is 125).
+    Lab,
+    /// `lch()`: lightness from 0 to 100, chroma from 0 (100% is 150) and hue in degrees.
+    Lch,
+    /// `oklab()`: lightness from 0 to 1, `a` and `b` around 0 (100% is 0.4).
+    Oklab,
+    /// `oklch()`: lightness from 0 to 1, chroma from 0 (100% is 0.4) and hue in degrees.
+    Oklch,
+    /// `color(srgb-linear ...)`, this and the following spaces only come from `color()`
+    /// and have components from 0 to 1, although values outside of that range are kept.
+    SrgbLinear,
+    /// `color(display-p3 ...)`
+    DisplayP3,
+    /// `color(a98-rgb ...)`
+    A98Rgb,
+    /// `color(prophoto-rgb ...)`
+    ProphotoRgb,
+    /// `color(rec2020 ...)`
+    Rec2020,
+    /// `color(xyz-d50 ...)`
+    XyzD50,
+    /// `color(xyz-d65 ...)` and `color(xyz ...)`
+    XyzD65,
+}
+
+/// A parsed `<color>`, see [`ColorSpace`] for the meaning of its components.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub space: ColorSpace,
+    /// In the order the color function takes them, such as red, green and blue.
+    pub components: [f32; 3],
+    /// From 0 (transparent) to 1 (opaque).
+    pub alpha: f32,
+    /// The components that were written as `none`, bit `i` for `components[i]` and bit 3 for `alpha`.
+    /// Their value is 0.
+    pub missing: u8,
+}
+
+/// The bit of [`Color::missing`] for the alpha.
+const ALPHA_MISSING: u8 = 1 << 3;
+
+impl Color {
+    const TRANSPARENT: Color = Color::srgb([0.0; 3], 0.0);
+
+    const fn srgb(components: [f32; 3], alpha: f32) -> Self {
+        Self { space: ColorSpace::Srgb, components, alpha, missing: 0 }
+    }
+
+    fn rgba(r: u8, g: u8, b: u8, alpha: f32) -> Self {
+        Self::srgb([r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0], alpha)
+    }
+
+    /// Converts the color to [`ColorSpace::Srgb`], `currentcolor` is returned as is.
+    ///
+    /// Missing components count as 0. Colors outside of the sRGB gamut have components below 0 or above 1,
+    /// they are not gamut mapped.
+    pub fn to_srgb(&self) -> Color {
+        let [c0, c1, c2] = self.components.map(f64::from);
+        let rgb = match self.space {
+            ColorSpace::CurrentColor => return *self,
+            ColorSpace::Srgb => [c0, c1, c2],
+            ColorSpace::Hsl => hsl_to_srgb(c0, c1 / 100.0, c2 / 100.0),
+            ColorSpace::Hwb => hwb_to_srgb(c0, c1 / 100.0, c2 / 100.0),
+            ColorSpace::Lab => xyz_d50_to_srgb(lab_to_xyz_d50([c0, c1, c2])),
+            ColorSpace::Lch => xyz_d50_to_srgb(lab_to_xyz_d50(polar_to_rectangular([c0, c1, c2]))),
+            ColorSpace::Oklab => xyz_d65_to_srgb(oklab_to_xyz_d65([c0, c1, c2])),
+            ColorSpace::Oklch => xyz_d65_to_srgb(oklab_to_xyz_d65(polar_to_rectangular([c0, c1, c2]))),
+            ColorSpace::SrgbLinear => [c0, c1, c2].map(srgb_encode),
+            ColorSpace::DisplayP3 => xyz_d65_to_srgb(multiply(&DISPLAY_P3_TO_XYZ_D65, [c0, c1, c2].map(srgb_decode))),
+            ColorSpace::A98Rgb => xyz_d65_to_srgb(multiply(&A98_RGB_TO_XYZ_D65, [c0, c1, c2].map(|c| c.signum() * c.abs().powf(563.0 / 256.0)))),
+            ColorSpace::ProphotoRgb => xyz_d50_to_srgb(multiply(&PROPHOTO_RGB_TO_XYZ_D50, [c0, c1, c2].map(prophoto_decode))),
+            ColorSpace::Rec2020 => xyz_d65_to_srgb(multiply(&REC2020_TO_XYZ_D65, [c0, c1, c2].map(rec2020_decode))),
+            ColorSpace::XyzD50 => xyz_d50_to_srgb([c0, c1, c2]),
+            ColorSpace::XyzD65 => xyz_d65_to_srgb([c0, c1, c2]),
+        };
+        Self::srgb(rgb.map(|c| c as f32), self.alpha)
+    }
+}
+
+type Result<'i, T> = std::result::Result<T, ParseError<'i, Infallible>>;
+
+/// How a component was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Number,
+    /// The value is the fraction, from 0 to 1.
+    Percentage,
+    /// The value is in degrees.
+    Angle,
+    /// `none`, the value is 0.
+    None,
+}
+
+/// Parses a `<color>` from `input`.
+///
+/// Components given with `calc()` and relative colors (`rgb(from ...)`) are not supported.
+pub fn parse_color_value<'i>(input: &mut Parser<'i, '_>) -> Result<'i, Color> {
+    input.skip_whitespace();
+    let location = input.current_source_location();
+    let token = input.next()?.clone();
+    match &token {
+        cssparser::Token::Hash(value) | cssparser::Token::IDHash(value) => cssparser::color::parse_hash_color(value.as_bytes())
+            .map(|(r, g, b, alpha)| Color::rgba(r, g, b, alpha))
+            .map_err(|()| location.new_unexpected_token_error(token.clone())),
+        cssparser::Token::Ident(name) if name.eq_ignore_ascii_case("currentcolor") => {
+            Ok(Color { space: ColorSpace::CurrentColor, ..Color::TRANSPARENT })
+        },
+        cssparser::Token::Ident(name) if name.eq_ignore_ascii_case("transparent") => Ok(Color::TRANSPARENT),
+        cssparser::Token::Ident(name) => cssparser::color::parse_named_color(name)
+            .map(|(r, g, b)| Color::rgba(r, g, b, 1.0))
+            .map_err(|()| location.new_unexpected_token_error(token.clone())),
+        cssparser::Token::Function(name) => {
+            let (space, legacy) = match_ignore_ascii_case! { name,
+                "rgb" | "rgba" => (Some(ColorSpace::Srgb), true),
+                "hsl" | "hsla" => (Some(ColorSpace::Hsl), true),
+                "hwb" => (Some(ColorSpace::Hwb), false),
+                "lab" => (Some(ColorSpace::Lab), false),
+                "lch" => (Some(ColorSpace::Lch), false),
+                "oklab" => (Some(ColorSpace::Oklab), false),
+                "oklch" => (Some(ColorSpace::Oklch), false),
+                "color" => (None, false),
+                _ => return Err(location.new_unexpected_token_error(token.clone())),
+            };
+            input.parse_nested_block(|input| parse_function(input, space, legacy))
+        },
+        _ => Err(location.new_unexpected_token_error(token.clone())),
+    }
+}
+
+/// Parses the arguments of a color function, `space` is `None` for `color()`
+/// and `legacy` is set for the functions that also accept comma-separated arguments.
+fn parse_function<'i>(input: &mut Parser<'i, '_>, space: Option<ColorSpace>, legacy: bool) -> Result<'i, Color> {
+    let color_function = space.is_none();
+    let space = match space {
+        Some(space) => space,
+        None => predefined(PredefinedColorSpace::parse(input)?),
+    };
+    let hue = match space {
+        ColorSpace::Hsl | ColorSpace::Hwb => Some(0),
+        ColorSpace::Lch | ColorSpace::Oklch => Some(2),
+        _ => None,
+    };
+    let accepts = |i: usize| if hue == Some(i) {
+        &[Kind::Number, Kind::Angle, Kind::None][..]
+    } else {
+        &[Kind::Number, Kind::Percentage, Kind::None][..]
+    };
+
+    let mut components = [(Kind::None, 0.0); 3];
+    components[0] = component(input, accepts(0))?;
+    // `rgb(1, 2, 3)` and `hsl(1, 2%, 3%)`, which do not allow `none` and only take one kind of value per component
+    let legacy = legacy && components[0].0 != Kind::None && input.try_parse(|input| input.expect_comma()).is_ok();
+    for i in 1..3 {
+        components[i] = match legacy {
+            true if i == 1 => component(input, match space {
+                ColorSpace::Hsl => &[Kind::Percentage],
+                _ => std::slice::from_ref(&components[0].0),
+            })?,
+            true => {
+                input.expect_comma()?;
+                component(input, std::slice::from_ref(&components[1].0))?
+            },
+            false => component(input, accepts(i))?,
+        };
+    }
+    let alpha = match legacy {
+        true if input.try_parse(|input| input.expect_comma()).is_ok() => Some(component(input, &[Kind::Number, Kind::Percentage])?),
+        false if input.try_parse(|input| input.expect_delim('/')).is_ok() => Some(component(input, &[Kind::Number, Kind::Percentage, Kind::None])?),
+        _ => None,
+    };
+
+    let mut color = Color::TRANSPARENT;
+    color.space = space;
+    for (i, &(kind, value)) in components.iter().enumerate() {
+        if kind == Kind::None {
+            color.missing |= 1 << i;
+        }
+        color.components[i] = match (space, kind, i) {
+            (_, Kind::None, _) => 0.0,
+            (_, Kind::Number | Kind::Angle, _) if hue == Some(i) => value,
+            (ColorSpace::Srgb, Kind::Number, _) if !color_function => (value / 255.0).clamp(0.0, 1.0),
+            (ColorSpace::Srgb, Kind::Percentage, _) if !color_function => value.clamp(0.0, 1.0),
+            (ColorSpace::Hsl, _, 1) => scale(kind, value, 100.0).max(0.0),
+            (ColorSpace::Hsl | ColorSpace::Hwb, _, _) => scale(kind, value, 100.0),
+            (ColorSpace::Lab | ColorSpace::Lch, _, 0) => scale(kind, value, 100.0).clamp(0.0, 100.0),
+            (ColorSpace::Lab, _, _) => scale(kind, value, 125.0),
+            (ColorSpace::Lch, _, _) => scale(kind, value, 150.0).max(0.0),
+            (ColorSpace::Oklab | ColorSpace::Oklch, _, 0) => scale(kind, value, 1.0).clamp(0.0, 1.0),
+            (ColorSpace::Oklab, _, _) => scale(kind, value, 0.4),
+            (ColorSpace::Oklch, _, _) => scale(kind, value, 0.4).max(0.0),
+            _ => value,
+        };
+    }
+    color.alpha = match alpha {
+        None => 1.0,
+        Some((Kind::None, _)) => {
+            color.missing |= ALPHA_MISSING;
+            0.0
+        },
+        Some((_, value)) => value.clamp(0.0, 1.0),
+    };
+    Ok(color)
+}
+
+/// Parses a number, percentage, angle or `none`, failing unless it is one of `accepts`.
+fn component<'i>(input: &mut Parser<'i, '_>, accepts: &[Kind]) -> Result<'i, (Kind, f32)> {
+    input.skip_whitespace();
+    let location = input.current_source_location();
+    let token = input.next()?.clone();
+    let component = match &token {
+        cssparser::Token::Number { value, .. } => (Kind::Number, *value),
+        cssparser::Token::Percentage { unit_value, .. } => (Kind::Percentage, *unit_value),
+        cssparser::Token::Dimension { value, unit, .. } => {
+            let degrees = match_ignore_ascii_case! { unit,
+                "deg" => *value,
+                "grad" => *value * 360.0 / 400.0,
+                "rad" => value.to_degrees(),
+                "turn" => *value * 360.0,
+                _ => return Err(location.new_unexpected_token_error(token.clone())),
+            };
+            (Kind::Angle, degrees)
+        },
+        cssparser::Token::Ident(name) if name.eq_ignore_ascii_case("none") => (Kind::None, 0.0),
+        _ => return Err(location.new_unexpected_token_error(token.clone())),
+    };
+    if !accepts.contains(&component.0) {
+        return Err(location.new_unexpected_token_error(token));
+    }
+    Ok(component)
+}
+
+/// The value of a number or percentage component, `percent` being the value of `100%`.
+fn scale(kind: Kind, value: f32, percent: f32) -> f32 {
+    match kind {
+        Kind::Percentage => value * percent,
+        _ => value,
+    }
+}
+
+fn predefined(space: PredefinedColorSpace) -> ColorSpace {
+    match space {
+        PredefinedColorSpace::Srgb => ColorSpace::Srgb,
+        PredefinedColorSpace::SrgbLinear => ColorSpace::SrgbLinear,
+        PredefinedColorSpace::DisplayP3 => ColorSpace::DisplayP3,
+        PredefinedColorSpace::A98Rgb => ColorSpace::A98Rgb,
+        PredefinedColorSpace::ProphotoRgb => ColorSpace::ProphotoRgb,
+        PredefinedColorSpace::Rec2020 => ColorSpace::Rec2020,
+        PredefinedColorSpace::XyzD50 => ColorSpace::XyzD50,
+        PredefinedColorSpace::XyzD65 => ColorSpace::XyzD65,
+    }
+}
+
+// The conversions and matrices below are the ones of https://www.w3.org/TR/css-color-4/#color-conversion-code
+
+type Matrix = [[f64; 3]; 3];
+
+const XYZ_D65_TO_LINEAR_SRGB: Matrix = [
+    [12831.0 / 3959.0, -329.0 / 214.0, -1974.0 / 3959.0],
+    [-851781.0 / 878810.0, 1648619.0 / 878810.0, 36519.0 / 878810.0],
+    [705.0 / 12673.0, -2585.0 / 12673.0, 705.0 / 667.0],
+];
+
+const D50_TO_D65: Matrix = [
+    [0.955473421488075, -0.02309845494876471, 0.06325924320057072],
+    [-0.0283697093338637, 1.0099953980813041, 0.021041441191917323],
+    [0.012314014864481998, -0.020507649298898964, 1.330365926242124],
+];
+
+const DISPLAY_P3_TO_XYZ_D65: Matrix = [
+    [608311.0 / 1250200.0, 189793.0 / 714400.0, 198249.0 / 1000160.0],
+    [35783.0 / 156275.0, 247089.0 / 357200.0, 198249.0 / 2500400.0],
+    [0.0, 32229.0 / 714400.0, 5220557.0 / 5000800.0],
+];
+
+const A98_RGB_TO_XYZ_D65: Matrix = [
+    [573536.0 / 994567.0, 263643.0 / 1420810.0, 187206.0 / 994567.0],
+    [591459.0 / 1989134.0, 6239551.0 / 9945670.0, 374412.0 / 4972835.0],
+    [53769.0 / 1989134.0, 351524.0 / 4972835.0, 4929758.0 / 4972835.0],
+];
+
+const PROPHOTO_RGB_TO_XYZ_D50: Matrix = [
+    [0.7977666449006423, 0.13518129740053308, 0.031347734128392285],
+    [0.2880748288194013, 0.711835234241873, 0.00008993693872564],
+    [0.0, 0.0, 0.8251046025104602],
+];
+
+const REC2020_TO_XYZ_D65: Matrix = [
+    [63426534.0 / 99577255.0, 20160776.0 / 139408157.0, 47086771.0 / 278816314.0],
+    [26158966.0 / 99577255.0, 472592308.0 / 697040785.0, 8267143.0 / 139408157.0],
+    [0.0, 19567812.0 / 697040785.0, 295819943.0 / 278816314.0],
+];
+
+const OKLAB_TO_LMS: Matrix = [
+    [1.0, 0.3963377773761749, 0.2158037573099136],
+    [1.0, -0.1055613458156586, -0.0638541728258133],
+    [1.0, -0.0894841775298119, -1.2914855480194092],
+];
+
+const LMS_TO_XYZ_D65: Matrix = [
+    [1.2268798758459243, -0.5578149944602171, 0.2813910456659647],
+    [-0.0405757452148008, 1.112286803280317, -0.0717110580655164],
+    [-0.0763729366746601, -0.4214933324022432, 1.5869240198367816],
+];
+
+fn multiply(matrix: &Matrix, [x, y, z]: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row[0] * x + row[1] * y + row[2] * z)
+}
+
+fn srgb_decode(c: f64) -> f64 {
+    if c.abs() <= 0.04045 {
+        c / 12.92
+    } else {
+        c.signum() * ((c.abs() + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn srgb_encode(c: f64) -> f64 {
+    if c.abs() > 0.0031308 {
+        c.signum() * (1.055 * c.abs().powf(1.0 / 2.4) - 0.055)
+    } else {
+        12.92 * c
+    }
+}
+
+fn prophoto_decode(c: f64) -> f64 {
+    if c.abs() <= 16.0 / 512.0 {
+        c / 16.0
+    } else {
+        c.signum() * c.abs().powf(1.8)
+    }
+}
+
+fn rec2020_decode(c: f64) -> f64 {
+    const ALPHA: f64 = 1.09929682680944;
+    const BETA: f64 = 0.018053968510807;
+    if c.abs() < BETA * 4.5 {
+        c / 4.5
+    } else {
+        c.signum() * ((c.abs() + ALPHA - 1.0) / ALPHA).powf(1.0 / 0.45)
+    }
+}
+
+fn xyz_d65_to_srgb(xyz: [f64; 3]) -> [f64; 3] {
+    multiply(&XYZ_D65_TO_LINEAR_SRGB, xyz).map(srgb_encode)
+}
+
+fn xyz_d50_to_srgb(xyz: [f64; 3]) -> [f64; 3] {
+    xyz_d65_to_srgb(multiply(&D50_TO_D65, xyz))
+}
+
+fn lab_to_xyz_d50([l, a, b]: [f64; 3]) -> [f64; 3] {
+    const KAPPA: f64 = 24389.0 / 27.0;
+    const EPSILON: f64 = 216.0 / 24389.0;
+    const WHITE: [f64; 3] = [0.3457 / 0.3585, 1.0, (1.0 - 0.3457 - 0.3585) / 0.3585];
+
+    let f1 = (l + 16.0) / 116.0;
+    let f0 = a / 500.0 + f1;
+    let f2 = f1 - b / 200.0;
+    let inverse = |f: f64| if f.powi(3) > EPSILON { f.powi(3) } else { (116.0 * f - 16.0) / KAPPA };
+    let y = if l > KAPPA * EPSILON { f1.powi(3) } else { l / KAPPA };
+    [inverse(f0) * WHITE[0], y * WHITE[1], inverse(f2) * WHITE[2]]
+}
+
+fn oklab_to_xyz_d65(oklab: [f64; 3]) -> [f64; 3] {
+    multiply(&LMS_TO_XYZ_D65, multiply(&OKLAB_TO_LMS, oklab).map(|c| c.powi(3)))
+}
+
+/// `lch` → `lab`, and `oklch` → `oklab`.
+fn polar_to_rectangular([l, c, h]: [f64; 3]) -> [f64; 3] {
+    let h = h.to_radians();
+    [l, c * h.cos(), c * h.sin()]
+}
+
+fn hsl_to_srgb(hue: f64, saturation: f64, lightness: f64) -> [f64; 3] {
+    let hue = hue.rem_euclid(360.0);
+    let f = |n: f64| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+        lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    [f(0.0), f(8.0), f(4.0)]
+}
+
+fn hwb_to_srgb(hue: f64, whiteness: f64, blackness: f64) -> [f64; 3] {
+    if whiteness + blackness >= 1.0 {
+        let gray = whiteness / (whiteness + blackness);
+        return [gray; 3];
+    }
+    hsl_to_srgb(hue, 1.0, 0.5).map(|c| c * (1.0 - whiteness - blackness) + whiteness)
+}
+
+/// Parses `input` as a single color, surrounded by optional whitespace.
+pub fn color(input: &str) -> std::result::Result<Color, Error> {
+    let mut parser_input = cssparser::ParserInput::new(input);
+    let mut parser = Parser::new(&mut parser_input);
+    parser.parse_entirely(parse_color_value)
+        .map_err(|e| Error::from_parse_error(&e, parser.position()))
+}
+
+/// Parses the NUL-terminated string `input` (such as `#ff0000`, `rebeccapurple` or `oklch(70% 0.1 120 / 50%)`)
+/// into `color`.
+///
+/// Out-params behave like they do for `parse_css`, on failure `color` is transparent black.
+#[ffi_export]
+pub fn parse_color(input: *const safer_ffi::c_char, color: Out<'_, Color>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        self::color(input_str(input)?)
+    });
+
+    finish(result, color, || Color::TRANSPARENT, error)
+}
+
+/// Like `parse_color`, for tokens returned by `parse_css` (such as the value of a `Declaration_t`).
+///
+/// The error location is the one of the token the error occurred at.
+#[ffi_export]
+pub fn parse_color_tokens(tokens: c_slice::Ref<'_, Token>, color: Out<'_, Color>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        parse_tokens(tokens.as_slice(), parse_color_value)
+    });
+
+    finish(result, color, || Color::TRANSPARENT, error)
+}
+
+/// Converts `color` to sRGB, see `ColorSpace_t` for the components.
+///
+/// Missing components count as 0 and the result has none.
+/// Components of colors outside of the sRGB gamut end up below 0 or above 1, clamp them if needed.
+/// `currentcolor` is returned unchanged.
+#[ffi_export]
+pub fn color_to_srgb(color: Color) -> Color {
+    color.to_srgb()
+}
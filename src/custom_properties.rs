@@ -0,0 +1,331 @@
+#![allow(nonstandard_style)]
+use std::collections::{HashMap, HashSet};
+
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{c_slice, repr_c, Out}};
+
+use crate::{
+    block_end,
+    error::{catch_panic, finish, Error, ErrorKind, Status},
+    input_str,
+    location::SourceLocation,
+    skip_whitespace,
+    stylesheet::Declaration,
+    Token,
+};
+
+/// The custom properties (`--name: value`) of an element, used to substitute `var()`.
+///
+/// Declarations are added with `custom_properties_add` in cascade order, the values are computed
+/// the first time they are needed: every `var()` in them is replaced, and the properties that
+/// cannot be resolved (because they reference each other or use an unset property without fallback)
+/// are guaranteed-invalid, as if they were never declared.
+#[derive_ReprC]
+#[repr(opaque)]
+#[derive(Default)]
+pub struct CustomProperties {
+    /// The computed values of the parent element.
+    inherited: HashMap<String, Vec<Token>>,
+    /// The value of each declared property and whether it is `!important`.
+    declared: HashMap<String, (Vec<Token>, bool)>,
+    /// The computed values, `None` when a declaration was added since they were last computed.
+    computed: Option<HashMap<String, Vec<Token>>>,
+}
+
+impl CustomProperties {
+    /// The custom properties of an element, `parent` being the ones of its parent element (`None` for the root).
+    pub fn new(parent: Option<&mut CustomProperties>) -> Self {
+        let inherited = parent.map(|parent| parent.computed().clone()).unwrap_or_default();
+        Self { inherited, ..Default::default() }
+    }
+
+    /// Adds the custom properties of `declarations`, other properties are ignored.
+    pub fn add(&mut self, declarations: &[Declaration]) {
+        for declaration in declarations {
+            if declaration.name.starts_with("--") {
+                self.declare(&declaration.name, &declaration.value, declaration.important);
+            }
+        }
+    }
+
+    /// Declares `name` (with its `--`), replacing the previous declaration unless it was `!important` and this one is not.
+    pub fn declare(&mut self, name: &str, value: &[Token], important: bool) {
+        if let Some((_, true)) = self.declared.get(name) {
+            if !important {
+                return;
+            }
+        }
+        self.declared.insert(name.to_string(), (value.to_vec(), important));
+        self.computed = None;
+    }
+
+    fn computed(&mut self) -> &HashMap<String, Vec<Token>> {
+        if self.computed.is_none() {
+            let mut resolver = Resolver { inherited: &self.inherited, declared: &self.declared, values: HashMap::new(), cyclic: cyclic(&self.declared) };
+            let mut computed = self.inherited.clone();
+            for name in self.declared.keys() {
+                match resolver.value(name) {
+                    Some(value) => computed.insert(name.clone(), value),
+                    None => computed.remove(name),
+                };
+            }
+            self.computed = Some(computed);
+        }
+        self.computed.as_ref().unwrap()
+    }
+
+    /// The computed value of `name`, `None` when it is guaranteed-invalid.
+    pub fn get(&mut self, name: &str) -> Option<&[Token]> {
+        self.computed().get(name).map(Vec::as_slice)
+    }
+
+    /// Replaces every `var()` in `tokens` (the value of a declaration) by the value of the custom property
+    /// or by its fallback.
+    ///
+    /// Fails with `ErrorKind::GuaranteedInvalid` when a `var()` is malformed or has neither,
+    /// the declaration is then invalid at computed-value time.
+    pub fn substitute(&mut self, tokens: &[Token]) -> Result<Vec<Token>, Error> {
+        let computed = self.computed();
+        substitute(tokens, &mut |name| computed.get(name).cloned())
+    }
+}
+
+/// The CSS-wide keywords a custom property can be set to.
+enum Keyword {
+    Initial,
+    /// `inherit`, and `unset`, `revert` and `revert-layer` which behave like it for inherited properties.
+    Inherit,
+}
+
+fn keyword(value: &[Token]) -> Option<Keyword> {
+    let mut tokens = value.iter().map(|token| cssparser::Token::from(&*token.value)).filter(|token| !matches!(token, cssparser::Token::WhiteSpace(_)));
+    let keyword = match (tokens.next(), tokens.next()) {
+        (Some(cssparser::Token::Ident(keyword)), None) => keyword,
+        _ => return None,
+    };
+    cssparser::match_ignore_ascii_case! { &keyword,
+        "initial" => Some(Keyword::Initial),
+        "inherit" | "unset" | "revert" | "revert-layer" => Some(Keyword::Inherit),
+        _ => None,
+    }
+}
+
+/// The custom properties named by the `var()`s of `value`, including the ones in fallbacks.
+fn references(value: &[Token]) -> Vec<String> {
+    let mut names = Vec::new();
+    for (index, token) in value.iter().enumerate() {
+        if let cssparser::Token::Function(function) = cssparser::Token::from(&*token.value) {
+            if !function.eq_ignore_ascii_case("var") {
+                continue;
+            }
+            if let Some(cssparser::Token::Ident(name)) = value.get(skip_whitespace(value, index + 1)).map(|token| cssparser::Token::from(&*token.value)) {
+                if name.starts_with("--") {
+                    names.push(name.to_string());
+                }
+            }
+        }
+    }
+    names
+}
+
+/// The declared properties that are part of a dependency cycle: the members of the strongly connected components
+/// of the graph where every property points at the declared properties it references, found with Tarjan's algorithm.
+///
+/// A reference in a fallback counts even when the fallback is not used, like in the specification.
+fn cyclic(declared: &HashMap<String, (Vec<Token>, bool)>) -> HashSet<String> {
+    let edges: HashMap<&str, Vec<String>> = declared.iter().map(|(name, (value, _))| {
+        let references = match keyword(value) {
+            Some(_) => Vec::new(),
+            None => references(value).into_iter().filter(|reference| declared.contains_key(reference)).collect(),
+        };
+        (name.as_str(), references)
+    }).collect();
+    let mut tarjan = Tarjan { edges: &edges, order: HashMap::new(), low: HashMap::new(), stack: Vec::new(), cyclic: HashSet::new() };
+    for name in declared.keys() {
+        if !tarjan.order.contains_key(name.as_str()) {
+            tarjan.visit(name);
+        }
+    }
+    tarjan.cyclic
+}
+
+struct Tarjan<'a> {
+    edges: &'a HashMap<&'a str, Vec<String>>,
+    /// The order in which each property was visited.
+    order: HashMap<&'a str, usize>,
+    /// The smallest order reachable from each property through the properties on `stack`.
+    low: HashMap<&'a str, usize>,
+    /// The visited properties whose component is not complete yet.
+    stack: Vec<&'a str>,
+    cyclic: HashSet<String>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, name: &'a str) {
+        let order = self.order.len();
+        self.order.insert(name, order);
+        self.low.insert(name, order);
+        self.stack.push(name);
+
+        let edges = self.edges;
+        for reference in &edges[name] {
+            let (reference, _) = edges.get_key_value(reference.as_str()).unwrap();
+            let low = match self.order.get(reference) {
+                None => {
+                    self.visit(reference);
+                    self.low[reference]
+                },
+                Some(&order) if self.stack.contains(reference) => order,
+                // part of a component that is already complete
+                Some(_) => continue,
+            };
+            let current = self.low.get_mut(name).unwrap();
+            *current = (*current).min(low);
+        }
+
+        if self.low[name] == order {
+            let position = self.stack.iter().rposition(|other| *other == name).unwrap();
+            let component = self.stack.split_off(position);
+            if component.len() > 1 || edges[name].iter().any(|reference| reference == name) {
+                self.cyclic.extend(component.into_iter().map(str::to_string));
+            }
+        }
+    }
+}
+
+/// Computes the declared custom properties of an element, each one at most once.
+struct Resolver<'a> {
+    inherited: &'a HashMap<String, Vec<Token>>,
+    declared: &'a HashMap<String, (Vec<Token>, bool)>,
+    /// The properties computed so far, `None` when guaranteed-invalid.
+    values: HashMap<String, Option<Vec<Token>>>,
+    /// The properties that are part of a cycle, the others only reference each other without one.
+    cyclic: HashSet<String>,
+}
+
+impl Resolver<'_> {
+    fn value(&mut self, name: &str) -> Option<Vec<Token>> {
+        if let Some(value) = self.values.get(name) {
+            return value.clone();
+        }
+        let Some((declared, _)) = self.declared.get(name) else {
+            return self.inherited.get(name).cloned();
+        };
+
+        let value = match keyword(declared) {
+            // a property of a cycle is invalid even if a fallback would break the cycle
+            _ if self.cyclic.contains(name) => None,
+            Some(Keyword::Initial) => None,
+            Some(Keyword::Inherit) => self.inherited.get(name).cloned(),
+            None => substitute(declared, &mut |name| self.value(name)).ok(),
+        };
+        self.values.insert(name.to_string(), value.clone());
+        value
+    }
+}
+
+/// Splits the arguments of a `var()` into the property name and the fallback (`None` without a comma).
+fn parse_var(arguments: &[Token]) -> Option<(String, Option<&[Token]>)> {
+    let index = skip_whitespace(arguments, 0);
+    let name = match cssparser::Token::from(&*arguments.get(index)?.value) {
+        cssparser::Token::Ident(name) if name.starts_with("--") => name.to_string(),
+        _ => return None,
+    };
+    let index = skip_whitespace(arguments, index + 1);
+    let Some(comma) = arguments.get(index) else {
+        return Some((name, None));
+    };
+    match cssparser::Token::from(&*comma.value) {
+        cssparser::Token::Comma => {
+            let mut fallback = &arguments[skip_whitespace(arguments, index + 1)..];
+            while let Some(cssparser::Token::WhiteSpace(_)) = fallback.last().map(|token| cssparser::Token::from(&*token.value)) {
+                fallback = &fallback[..fallback.len() - 1];
+            }
+            Some((name, Some(fallback)))
+        },
+        _ => None,
+    }
+}
+
+/// Replaces every `var()` of `tokens`, `value` returning the value of a custom property.
+fn substitute(tokens: &[Token], value: &mut dyn FnMut(&str) -> Option<Vec<Token>>) -> Result<Vec<Token>, Error> {
+    let mut output = Vec::with_capacity(tokens.len());
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = &tokens[index];
+        match cssparser::Token::from(&*token.value) {
+            cssparser::Token::Function(function) if function.eq_ignore_ascii_case("var") => {
+                // an unclosed `var(` runs to the end of the value
+                let end = block_end(tokens, index).unwrap_or(tokens.len());
+                let location = SourceLocation { end: tokens[end.min(tokens.len() - 1)].location.end, ..token.location };
+                let (name, fallback) = parse_var(&tokens[index + 1..end])
+                    .ok_or_else(|| Error::new(ErrorKind::GuaranteedInvalid, "invalid `var()`").at(location))?;
+                match (value(&name), fallback) {
+                    (Some(value), _) => output.extend(value),
+                    (None, Some(fallback)) => output.extend(substitute(fallback, value)?),
+                    (None, None) => return Err(Error::new(ErrorKind::GuaranteedInvalid, format!("`{name}` has no value")).at(location)),
+                }
+                index = end + 1;
+            },
+            _ => {
+                output.push(token.clone());
+                index += 1;
+            },
+        }
+    }
+    Ok(output)
+}
+
+/// Creates the custom properties of an element, `parent` being the ones of its parent element (null for the root).
+///
+/// The parent's values are copied, so it can be freed before its children. Release the result with `custom_properties_free`.
+#[ffi_export]
+pub fn custom_properties_new(parent: Option<&mut CustomProperties>) -> repr_c::Box<CustomProperties> {
+    Box::new(CustomProperties::new(parent)).into()
+}
+
+/// Adds the custom property declarations of `declarations` (such as the ones of a matching `Rule_t`),
+/// other properties are ignored.
+///
+/// Declarations must be added in cascade order: a later declaration replaces an earlier one,
+/// unless the earlier one is `!important` and the later one is not.
+#[ffi_export]
+pub fn custom_properties_add(properties: &mut CustomProperties, declarations: c_slice::Ref<'_, Declaration>) {
+    let _ = catch_panic(|| {
+        properties.add(declarations.as_slice());
+        Ok(())
+    });
+}
+
+/// Writes the computed value of the custom property `name` (with its `--`) to `value`, free it with `free_tokens`.
+///
+/// Fails with `ERROR_KIND_GUARANTEED_INVALID` when the property is not set or cannot be computed.
+#[ffi_export]
+pub fn custom_properties_get(properties: &mut CustomProperties, name: *const safer_ffi::c_char, value: Out<'_, repr_c::Vec<Token>>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        let name = input_str(name)?;
+        match properties.get(name) {
+            Some(value) => Ok(value.to_vec().into()),
+            None => Err(Error::new(ErrorKind::GuaranteedInvalid, format!("`{name}` has no value"))),
+        }
+    });
+
+    finish(result, value, || Vec::new().into(), error)
+}
+
+/// Writes `value` (the tokens of a declaration) to `tokens` with every `var()` replaced, free them with `free_tokens`.
+///
+/// Fails with `ERROR_KIND_GUARANTEED_INVALID`, located at the `var()`, when a `var()` is malformed
+/// or references a property without value and has no fallback: the declaration is invalid at computed-value time.
+#[ffi_export]
+pub fn custom_properties_substitute(properties: &mut CustomProperties, value: c_slice::Ref<'_, Token>, tokens: Out<'_, repr_c::Vec<Token>>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        properties.substitute(value.as_slice()).map(Into::into)
+    });
+
+    finish(result, tokens, || Vec::new().into(), error)
+}
+
+#[ffi_export]
+pub fn custom_properties_free(properties: repr_c::Box<CustomProperties>) {
+    drop(properties);
+}
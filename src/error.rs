@@ -0,0 +1,168 @@
+#![allow(nonstandard_style)]
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+
+use safer_ffi::{derive_ReprC, ffi_export, prelude::Out};
+use crate::{cstr, location::SourceLocation};
+
+/// The outcome of a call into the library.
+///
+/// Every fallible function returns one of these and writes its result to an out-param.
+/// When the status is not `Ok` the result is still written (as an empty value)
+/// so it is always safe to free it.
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The call succeeded.
+    Ok,
+    /// The input could not be used at all (a null pointer, invalid UTF-8, ...).
+    InvalidInput,
+    /// The input was read but could not be parsed.
+    ParseError,
+    /// The library panicked, the panic was caught before reaching the caller.
+    Panic,
+    /// A callback asked to stop, see `parse_css_events`.
+    Aborted,
+}
+
+/// What went wrong, see [`Error`].
+///
+/// The parse error kinds mirror `cssparser::BasicParseErrorKind`.
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// No error occurred.
+    None,
+    /// A required pointer argument was null.
+    NullPointer,
+    /// The input was not valid UTF-8.
+    InvalidUtf8,
+    /// An unexpected token was encountered.
+    UnexpectedToken,
+    /// The end of the input was encountered unexpectedly.
+    EndOfInput,
+    /// An `@` rule was encountered that was invalid.
+    AtRuleInvalid,
+    /// The body of an `@` rule was invalid.
+    AtRuleBodyInvalid,
+    /// A qualified rule was encountered that was invalid.
+    QualifiedRuleInvalid,
+    /// An error specific to what was being parsed, see the message.
+    Custom,
+    /// The library panicked.
+    Panic,
+    /// The loader given to `load_stylesheet` could not provide a file.
+    LoadFailed,
+    /// An `@import` was skipped because the file it imports is already being imported.
+    ImportCycle,
+    /// A `var()` is malformed, or references a custom property without value and has no fallback.
+    GuaranteedInvalid,
+}
+
+impl From<&cssparser::BasicParseErrorKind<'_>> for ErrorKind {
+    fn from(value: &cssparser::BasicParseErrorKind<'_>) -> Self {
+        match value {
+            cssparser::BasicParseErrorKind::UnexpectedToken(_) => ErrorKind::UnexpectedToken,
+            cssparser::BasicParseErrorKind::EndOfInput => ErrorKind::EndOfInput,
+            cssparser::BasicParseErrorKind::AtRuleInvalid(_) => ErrorKind::AtRuleInvalid,
+            cssparser::BasicParseErrorKind::AtRuleBodyInvalid => ErrorKind::AtRuleBodyInvalid,
+            cssparser::BasicParseErrorKind::QualifiedRuleInvalid => ErrorKind::QualifiedRuleInvalid,
+        }
+    }
+}
+
+/// Details about a failed call, free it with `free_error`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    /// A human readable description of the error.
+    pub message: cstr,
+    /// Where in the input the error occurred.
+    /// `start` and `end` are equal, as errors point at a position rather than a range,
+    /// except for the errors of a `StyleSheet_t` which span the rule or declaration that was skipped.
+    pub location: SourceLocation,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into().into(), location: SourceLocation::default() }
+    }
+
+    pub fn none() -> Self {
+        Self::new(ErrorKind::None, String::new())
+    }
+
+    pub fn at(self, location: SourceLocation) -> Self {
+        Self { location, ..self }
+    }
+
+    /// Converts an error reported by `cssparser`, `position` is where the parser was when it failed.
+    pub fn from_parse_error<E: std::fmt::Display>(error: &cssparser::ParseError<'_, E>, position: cssparser::SourcePosition) -> Self {
+        match &error.kind {
+            cssparser::ParseErrorKind::Basic(kind) => Self::from_basic(kind, error.location, position),
+            cssparser::ParseErrorKind::Custom(custom) => Self::new(ErrorKind::Custom, custom.to_string())
+                .at(SourceLocation::new(position, position, error.location)),
+        }
+    }
+
+    pub fn from_basic(kind: &cssparser::BasicParseErrorKind<'_>, location: cssparser::SourceLocation, position: cssparser::SourcePosition) -> Self {
+        Self::new(kind.into(), kind.to_string()).at(SourceLocation::new(position, position, location))
+    }
+
+    /// An error for input that is not valid UTF-8, located at the first invalid byte.
+    pub fn invalid_utf8(error: std::str::Utf8Error) -> Self {
+        let offset = error.valid_up_to();
+        Self::new(ErrorKind::InvalidUtf8, error.to_string())
+            .at(SourceLocation { start: offset, end: offset, ..Default::default() })
+    }
+
+    pub fn panic(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "the library panicked".to_string(),
+            },
+        };
+        Self::new(ErrorKind::Panic, message)
+    }
+
+    pub fn status(&self) -> Status {
+        match self.kind {
+            ErrorKind::None => Status::Ok,
+            ErrorKind::NullPointer | ErrorKind::InvalidUtf8 | ErrorKind::LoadFailed => Status::InvalidInput,
+            ErrorKind::Panic => Status::Panic,
+            _ => Status::ParseError,
+        }
+    }
+}
+
+#[ffi_export]
+pub fn free_error(error: Error) {
+    drop(error);
+}
+
+/// Runs `f`, turning a panic into an [`Error`] so it never unwinds into C.
+pub fn catch_panic<T>(f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+    std::panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| Err(Error::panic(payload)))
+}
+
+/// Writes the outcome of an exported function to its out-params and returns the matching status.
+///
+/// On failure `out` receives `empty()` so the caller can free it unconditionally.
+pub fn finish<T>(result: Result<T, Error>, out: Out<'_, T>, empty: impl FnOnce() -> T, error: Option<Out<'_, Error>>) -> Status {
+    let (value, err) = match result {
+        Ok(value) => (value, Error::none()),
+        Err(err) => (empty(), err),
+    };
+    out.write(value);
+    let status = err.status();
+    if let Some(error) = error {
+        error.write(err);
+    }
+    status
+}
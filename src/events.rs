@@ -0,0 +1,325 @@
+#![allow(nonstandard_style)]
+use std::{convert::Infallible, ffi::c_void};
+
+use cssparser::{AtRuleParser, CowRcStr, DeclarationParser, Delimiter, ParseError, Parser, ParserState, QualifiedRuleParser, RuleBodyItemParser, RuleBodyParser, StyleSheetParser};
+use safer_ffi::{derive_ReprC, ffi_export};
+
+use crate::{
+    error::{catch_panic, Error, Status},
+    input_str,
+    location::SourceLocation,
+    stylesheet::{block_contents, component_tokens_and_comments, skipped_item_error, strip_important, RuleType},
+    view::StrView,
+};
+
+/// What a callback of `EventCallbacks` wants the parser to do next.
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventFlow {
+    Continue,
+    /// Stop parsing, `parse_css_events` returns `Status::Aborted` without calling anything else.
+    Abort,
+}
+
+/// The functions `parse_css_events` calls as it reads a stylesheet, in source order.
+///
+/// Every callback is optional and receives the `user` pointer given to `parse_css_events`.
+/// The text of a `StrView_t` is only valid during the call, it points into the input unless its `owned` flag is set
+/// (a name with escapes, such as `col\6f r`, is passed unescaped).
+/// A rule with a block is reported as `on_rule_start`, then `on_selector` for each selector of a style rule
+/// or `on_at_rule` for an at-rule, then the contents of the block, then `on_rule_end`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct EventCallbacks {
+    /// A rule with a block starts, `location` goes from its first byte to its `{`.
+    pub on_rule_start: Option<unsafe extern "C" fn(rule_type: RuleType, location: SourceLocation, user: *mut c_void) -> EventFlow>,
+    /// One of the comma-separated selectors of a style rule, as written, without whitespace at either end.
+    pub on_selector: Option<unsafe extern "C" fn(selector: StrView, location: SourceLocation, user: *mut c_void) -> EventFlow>,
+    /// A `name: value` declaration, the value does not include `!important`.
+    /// `location` goes from the first byte of the name to the end of the value (or `!important`).
+    pub on_declaration: Option<unsafe extern "C" fn(name: StrView, value: StrView, important: bool, location: SourceLocation, user: *mut c_void) -> EventFlow>,
+    /// An at-rule, with its name (without the `@`) and its prelude as written.
+    /// Without a block (`@import "a.css";`) this is the only event for the rule and `location` spans all of it,
+    /// otherwise it follows `on_rule_start` and `location` is the same.
+    pub on_at_rule: Option<unsafe extern "C" fn(name: StrView, prelude: StrView, has_block: bool, location: SourceLocation, user: *mut c_void) -> EventFlow>,
+    /// The block of the last rule that started ends, `location` spans the whole rule.
+    pub on_rule_end: Option<unsafe extern "C" fn(location: SourceLocation, user: *mut c_void) -> EventFlow>,
+    /// A comment, without its opening and closing delimiters.
+    /// Comments inside of a prelude or a value are reported right after the rule or declaration they are in.
+    pub on_comment: Option<unsafe extern "C" fn(text: StrView, location: SourceLocation, user: *mut c_void) -> EventFlow>,
+    /// A rule or declaration that could not be parsed and was skipped, like those listed in `StyleSheet_t.errors`.
+    /// The error is only valid during the call and must not be freed.
+    pub on_error: Option<unsafe extern "C" fn(error: *const Error, user: *mut c_void) -> EventFlow>,
+}
+
+/// Calls the callbacks, and stops calling them once one has aborted.
+struct Emitter<'i> {
+    callbacks: EventCallbacks,
+    user: *mut c_void,
+    css: &'i str,
+    aborted: bool,
+    /// The comments found while parsing the current prelude or value, reported after it.
+    comments: Vec<(&'i str, SourceLocation)>,
+}
+
+impl<'i> Emitter<'i> {
+    fn emit(&mut self, event: impl FnOnce(&EventCallbacks, *mut c_void) -> Option<EventFlow>) {
+        if self.aborted {
+            return;
+        }
+        if let Some(EventFlow::Abort) = event(&self.callbacks, self.user) {
+            self.aborted = true;
+        }
+    }
+
+    /// A view of `text`, owned unless it points into the input.
+    fn view(&self, text: &str) -> StrView {
+        let input = self.css.as_bytes().as_ptr_range();
+        StrView { owned: !input.contains(&text.as_ptr()) && !text.is_empty(), ..StrView::borrowed(text) }
+    }
+
+    fn flush_comments(&mut self) {
+        for (text, location) in std::mem::take(&mut self.comments) {
+            self.emit(|callbacks, user| callbacks.on_comment.map(|on_comment| unsafe { on_comment(StrView::borrowed(text), location, user) }));
+        }
+    }
+
+    /// The rest of `input` as written, with the comments in it saved for later.
+    ///
+    /// Whitespace and comments at either end are left out, the location of an empty span is where the input ends.
+    fn span(&mut self, input: &mut Parser<'i, '_>) -> Result<(&'i str, SourceLocation), ParseError<'i, Infallible>> {
+        let empty = SourceLocation::new(input.position(), input.position(), input.current_source_location());
+        let tokens = component_tokens_and_comments(input, &mut self.comments)?;
+        Ok(self.text(&tokens).unwrap_or(("", empty)))
+    }
+
+    /// The text from the first to the last of `tokens`.
+    fn text(&self, tokens: &[(cssparser::Token, SourceLocation)]) -> Option<(&'i str, SourceLocation)> {
+        let ((_, first), (_, last)) = (tokens.first()?, tokens.last()?);
+        let location = SourceLocation { end: last.end, ..*first };
+        Some((&self.css[location.start..location.end], location))
+    }
+}
+
+/// What the rule and declaration parsers return, rules with a block have been reported by then.
+enum Parsed<'i> {
+    Declaration { name: CowRcStr<'i>, value: &'i str, important: bool, end: usize },
+    /// An at-rule without a block.
+    Statement { name: CowRcStr<'i>, prelude: &'i str, start: SourceLocation },
+    Block { start: SourceLocation },
+}
+
+/// Parses rules and declarations like `RulesParser` does, reporting them instead of collecting them.
+struct EventParser<'a, 'i> {
+    emitter: &'a mut Emitter<'i>,
+    declarations: bool,
+    qualified: bool,
+    nested: bool,
+}
+
+impl<'i> EventParser<'_, 'i> {
+    /// Reports the contents of a block, then its end. `nested` is set when the block is inside of a style rule.
+    fn parse_body(&mut self, at_rule: Option<&str>, nested: bool, input: &mut Parser<'i, '_>, start: &ParserState) -> Parsed<'i> {
+        let (declarations, qualified) = block_contents(at_rule, self.nested);
+        let mut parser = EventParser { emitter: self.emitter, declarations, qualified, nested };
+        report_items(RuleBodyParser::new(input, &mut parser));
+        Parsed::Block { start: SourceLocation::new(start.position(), start.position(), start.source_location()) }
+    }
+
+    fn rule_start(&mut self, rule_type: RuleType, start: &ParserState, input: &Parser) -> SourceLocation {
+        let location = SourceLocation::new(start.position(), input.position(), start.source_location());
+        self.emitter.emit(|callbacks, user| callbacks.on_rule_start.map(|on_rule_start| unsafe { on_rule_start(rule_type, location, user) }));
+        location
+    }
+}
+
+impl<'i> QualifiedRuleParser<'i> for EventParser<'_, 'i> {
+    type Prelude = Vec<(&'i str, SourceLocation)>;
+    type QualifiedRule = Parsed<'i>;
+    type Error = Infallible;
+
+    fn parse_prelude<'t>(&mut self, input: &mut Parser<'i, 't>) -> Result<Self::Prelude, ParseError<'i, Self::Error>> {
+        let mut selectors = Vec::new();
+        loop {
+            selectors.push(input.parse_until_before(Delimiter::Comma, |input| self.emitter.span(input))?);
+            // the comma, if any
+            if input.next().is_err() {
+                return Ok(selectors);
+            }
+        }
+    }
+
+    fn parse_block<'t>(&mut self, selectors: Self::Prelude, start: &ParserState, input: &mut Parser<'i, 't>) -> Result<Self::QualifiedRule, ParseError<'i, Self::Error>> {
+        self.rule_start(RuleType::Qualified, start, input);
+        for (selector, location) in selectors {
+            self.emitter.emit(|callbacks, user| callbacks.on_selector.map(|on_selector| unsafe { on_selector(StrView::borrowed(selector), location, user) }));
+        }
+        self.emitter.flush_comments();
+        Ok(self.parse_body(None, true, input, start))
+    }
+}
+
+impl<'i> AtRuleParser<'i> for EventParser<'_, 'i> {
+    type Prelude = (CowRcStr<'i>, &'i str);
+    type AtRule = Parsed<'i>;
+    type Error = Infallible;
+
+    fn parse_prelude<'t>(&mut self, name: CowRcStr<'i>, input: &mut Parser<'i, 't>) -> Result<Self::Prelude, ParseError<'i, Self::Error>> {
+        Ok((name, self.emitter.span(input)?.0))
+    }
+
+    fn rule_without_block(&mut self, (name, prelude): Self::Prelude, start: &ParserState) -> Result<Self::AtRule, ()> {
+        let start = SourceLocation::new(start.position(), start.position(), start.source_location());
+        Ok(Parsed::Statement { name, prelude, start })
+    }
+
+    fn parse_block<'t>(&mut self, (name, prelude): Self::Prelude, start: &ParserState, input: &mut Parser<'i, 't>) -> Result<Self::AtRule, ParseError<'i, Self::Error>> {
+        let location = self.rule_start(RuleType::At, start, input);
+        let (name_view, prelude_view) = (self.emitter.view(&name), StrView::borrowed(prelude));
+        self.emitter.emit(|callbacks, user| callbacks.on_at_rule.map(|on_at_rule| unsafe { on_at_rule(name_view, prelude_view, true, location, user) }));
+        self.emitter.flush_comments();
+        let nested = self.nested;
+        Ok(self.parse_body(Some(&name), nested, input, start))
+    }
+}
+
+impl<'i> DeclarationParser<'i> for EventParser<'_, 'i> {
+    type Declaration = Parsed<'i>;
+    type Error = Infallible;
+
+    fn parse_value<'t>(&mut self, name: CowRcStr<'i>, input: &mut Parser<'i, 't>) -> Result<Self::Declaration, ParseError<'i, Self::Error>> {
+        let empty = input.position();
+        let mut value = component_tokens_and_comments(input, &mut self.emitter.comments)?;
+        let end = value.last().map_or(empty.byte_index(), |(_, location)| location.end);
+        let important = strip_important(&mut value);
+        let value = self.emitter.text(&value).map_or("", |(text, _)| text);
+        Ok(Parsed::Declaration { name, value, important, end })
+    }
+}
+
+impl<'i> RuleBodyItemParser<'i, Parsed<'i>, Infallible> for EventParser<'_, 'i> {
+    fn parse_declarations(&self) -> bool {
+        self.declarations
+    }
+
+    fn parse_qualified(&self) -> bool {
+        self.qualified
+    }
+}
+
+/// `StyleSheetParser` and `RuleBodyParser` both keep the parser they were given in public fields, like for `ItemList`.
+trait EventList<'i, 't, 'a>: Iterator<Item = Result<Parsed<'i>, (ParseError<'i, Infallible>, &'i str)>> {
+    fn input(&mut self) -> &mut Parser<'i, 't>;
+    fn emitter(&mut self) -> &mut Emitter<'i>;
+}
+
+impl<'i, 't, 'a> EventList<'i, 't, 'a> for StyleSheetParser<'i, 't, '_, EventParser<'a, 'i>> {
+    fn input(&mut self) -> &mut Parser<'i, 't> {
+        self.input
+    }
+
+    fn emitter(&mut self) -> &mut Emitter<'i> {
+        self.parser.emitter
+    }
+}
+
+impl<'i, 't, 'a> EventList<'i, 't, 'a> for RuleBodyParser<'i, 't, '_, EventParser<'a, 'i>, Parsed<'i>, Infallible> {
+    fn input(&mut self) -> &mut Parser<'i, 't> {
+        self.input
+    }
+
+    fn emitter(&mut self) -> &mut Emitter<'i> {
+        self.parser.emitter
+    }
+}
+
+/// Runs `list` until it ends or a callback aborts, reporting what it returns and what it skips.
+fn report_items<'i: 't, 't, 'a>(mut list: impl EventList<'i, 't, 'a>) {
+    while !list.emitter().aborted {
+        // like `collect_items`, skip ahead to know where the next item starts, reporting the comments on the way
+        loop {
+            let input = list.input();
+            let state = input.state();
+            let location = input.current_source_location();
+            match input.next_including_whitespace_and_comments() {
+                Ok(cssparser::Token::WhiteSpace(_) | cssparser::Token::Semicolon) => {},
+                Ok(&cssparser::Token::Comment(text)) => {
+                    let location = SourceLocation::new(state.position(), input.position(), location);
+                    list.emitter().comments.push((text, location));
+                    list.emitter().flush_comments();
+                },
+                _ => {
+                    input.reset(&state);
+                    break;
+                },
+            }
+        }
+        let input = list.input();
+        let start = input.position();
+        let start_location = input.current_source_location();
+
+        let Some(item) = list.next() else { break };
+        let end = list.input().position();
+        match item {
+            Ok(Parsed::Declaration { name, value, important, end }) => {
+                let location = SourceLocation { end, ..SourceLocation::new(start, start, start_location) };
+                let emitter = list.emitter();
+                let (name, value) = (emitter.view(&name), StrView::borrowed(value));
+                emitter.emit(|callbacks, user| callbacks.on_declaration.map(|on_declaration| unsafe { on_declaration(name, value, important, location, user) }));
+            },
+            Ok(Parsed::Statement { name, prelude, start }) => {
+                let location = SourceLocation { end: end.byte_index(), ..start };
+                let emitter = list.emitter();
+                let (name, prelude) = (emitter.view(&name), StrView::borrowed(prelude));
+                emitter.emit(|callbacks, user| callbacks.on_at_rule.map(|on_at_rule| unsafe { on_at_rule(name, prelude, false, location, user) }));
+            },
+            Ok(Parsed::Block { start }) => {
+                let location = SourceLocation { end: end.byte_index(), ..start };
+                list.emitter().emit(|callbacks, user| callbacks.on_rule_end.map(|on_rule_end| unsafe { on_rule_end(location, user) }));
+            },
+            Err((error, _)) => {
+                let error = skipped_item_error(list.input(), &error, start, start_location, end);
+                list.emitter().emit(|callbacks, user| callbacks.on_error.map(|on_error| unsafe { on_error(&error, user) }));
+            },
+        }
+        list.emitter().flush_comments();
+    }
+}
+
+/// Parses `input` as a stylesheet, reporting what it finds to `callbacks`, see `parse_css_events`.
+pub fn css_events(input: &str, callbacks: EventCallbacks, user: *mut c_void) -> Status {
+    let mut emitter = Emitter { callbacks, user, css: input, aborted: false, comments: Vec::new() };
+    let mut parser_input = cssparser::ParserInput::new(input);
+    let mut parser = Parser::new(&mut parser_input);
+    let mut event_parser = EventParser { emitter: &mut emitter, declarations: false, qualified: true, nested: false };
+    report_items(StyleSheetParser::new(&mut parser, &mut event_parser));
+
+    if emitter.aborted {
+        Status::Aborted
+    } else {
+        Status::Ok
+    }
+}
+
+/// Parses the NUL-terminated string `input` as a stylesheet without building anything,
+/// calling the functions of `callbacks` for each rule, selector, declaration and comment instead.
+///
+/// Invalid rules and declarations are reported to `on_error` and skipped, they do not make the call fail.
+/// Returns `Status::Aborted` when a callback returned `EventFlow::Abort`.
+/// When `input` cannot be read `on_error` receives the reason and the status says what it was.
+#[ffi_export]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn parse_css_events(input: *const safer_ffi::c_char, callbacks: &EventCallbacks, user: *mut c_void) -> Status {
+    let result = catch_panic(|| {
+        Ok(css_events(input_str(input)?, *callbacks, user))
+    });
+
+    result.unwrap_or_else(|error| {
+        if let Some(on_error) = callbacks.on_error {
+            unsafe { on_error(&error, user) };
+        }
+        error.status()
+    })
+}
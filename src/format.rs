@@ -0,0 +1,317 @@
+#![allow(nonstandard_style)]
+use std::convert::Infallible;
+use std::fmt::Write;
+
+use cssparser::ToCss;
+use safer_ffi::{derive_ReprC, ffi_export, prelude::Out};
+
+use crate::{cstr, error::{catch_panic, finish, Error, Status}, input_str, walk_including_whitespace};
+
+/// How `format_css` writes quoted strings.
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// `"string"`
+    Double,
+    /// `'string'`
+    Single,
+}
+
+/// The layout `format_css` produces, `format_options_default` returns the usual one.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// How many spaces make up one level of indentation, unused when `use_tabs` is set.
+    pub indent_width: u32,
+    /// Indent with one tab per level instead of spaces.
+    pub use_tabs: bool,
+    /// Write every declaration on its own line.
+    ///
+    /// When unset, declarations follow each other on the same line
+    /// and a block holding only declarations is written on the line of its rule (`a { b: c; d: e; }`).
+    pub one_declaration_per_line: bool,
+    /// Separate rules with an empty line.
+    ///
+    /// A comment right before a rule stays attached to it.
+    pub blank_line_between_rules: bool,
+    pub quote_style: QuoteStyle,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            use_tabs: false,
+            one_declaration_per_line: true,
+            blank_line_between_rules: true,
+            quote_style: QuoteStyle::Double,
+        }
+    }
+}
+
+/// One statement of a stylesheet or of the block of a rule.
+enum Item<'i> {
+    /// A comment between statements, without its opening and closing delimiters.
+    Comment(&'i str),
+    /// A declaration, or anything else that ends with a `;` (such as an `@import`).
+    /// The `;` itself is not included.
+    Statement(Vec<cssparser::Token<'i>>),
+    /// A qualified or at-rule with a `{}` block.
+    Rule { prelude: Vec<cssparser::Token<'i>>, body: Vec<Item<'i>> },
+}
+
+/// Groups the token stream into [`Item`]s.
+struct Builder<'i> {
+    /// The prelude and statements of every rule we are in, the first one is the stylesheet itself.
+    blocks: Vec<(Vec<cssparser::Token<'i>>, Vec<Item<'i>>)>,
+    /// The tokens of the statement being read, without leading whitespace.
+    current: Vec<cssparser::Token<'i>>,
+    /// The closing tokens of the functions and blocks the current statement is inside of.
+    closing: Vec<cssparser::Token<'i>>,
+}
+
+impl<'i> Builder<'i> {
+    fn push(&mut self, token: &cssparser::Token<'i>) {
+        if let Some(closing) = self.closing.last() {
+            if token == closing {
+                self.closing.pop();
+            } else if let Some(closing) = closing_token(token) {
+                self.closing.push(closing);
+            }
+            self.current.push(token.clone());
+            return;
+        }
+
+        match token {
+            cssparser::Token::WhiteSpace(_) if self.current.is_empty() => {},
+            cssparser::Token::Comment(text) if self.current.is_empty() => self.items().push(Item::Comment(text)),
+            cssparser::Token::Semicolon => self.end_statement(),
+            cssparser::Token::CurlyBracketBlock => {
+                let prelude = std::mem::take(&mut self.current);
+                self.blocks.push((prelude, Vec::new()));
+            },
+            // a `}` that does not close anything is kept as is
+            cssparser::Token::CloseCurlyBracket if self.blocks.len() > 1 => {
+                self.end_statement();
+                let (prelude, body) = self.blocks.pop().unwrap();
+                self.items().push(Item::Rule { prelude, body });
+            },
+            _ => {
+                if let Some(closing) = closing_token(token) {
+                    self.closing.push(closing);
+                }
+                self.current.push(token.clone());
+            },
+        }
+    }
+
+    fn items(&mut self) -> &mut Vec<Item<'i>> {
+        &mut self.blocks.last_mut().unwrap().1
+    }
+
+    fn end_statement(&mut self) {
+        let mut statement = std::mem::take(&mut self.current);
+        while let Some(cssparser::Token::WhiteSpace(_)) = statement.last() {
+            statement.pop();
+        }
+        if !statement.is_empty() {
+            self.items().push(Item::Statement(statement));
+        }
+    }
+
+    fn finish(mut self) -> Vec<Item<'i>> {
+        self.end_statement();
+        // `walk_including_whitespace` closes every block, so only the stylesheet is left
+        self.blocks.pop().unwrap().1
+    }
+}
+
+/// The token that ends the block `token` opens, if it opens one.
+fn closing_token<'i>(token: &cssparser::Token) -> Option<cssparser::Token<'i>> {
+    match token {
+        cssparser::Token::Function(_) | cssparser::Token::ParenthesisBlock => Some(cssparser::Token::CloseParenthesis),
+        cssparser::Token::SquareBracketBlock => Some(cssparser::Token::CloseSquareBracket),
+        cssparser::Token::CurlyBracketBlock => Some(cssparser::Token::CloseCurlyBracket),
+        _ => None,
+    }
+}
+
+struct Printer {
+    options: FormatOptions,
+    css: String,
+}
+
+impl Printer {
+    fn indent(&mut self, depth: usize) {
+        for _ in 0..depth {
+            if self.options.use_tabs {
+                self.css.push('\t');
+            } else {
+                self.css.extend(std::iter::repeat_n(' ', self.options.indent_width as usize));
+            }
+        }
+    }
+
+    fn items(&mut self, items: &[Item], depth: usize) {
+        let mut previous: Option<&Item> = None;
+        for item in items {
+            let same_line = !self.options.one_declaration_per_line
+                && matches!((previous, item), (Some(Item::Statement(_)), Item::Statement(_)));
+            let blank_line = self.options.blank_line_between_rules
+                && matches!((previous, item), (Some(Item::Rule { .. }), _) | (Some(Item::Statement(_)), Item::Rule { .. }));
+            if same_line {
+                // replace the line break after the previous statement
+                self.css.pop();
+                self.css.push(' ');
+            } else {
+                if blank_line {
+                    self.css.push('\n');
+                }
+                self.indent(depth);
+            }
+            previous = Some(item);
+
+            match item {
+                Item::Comment(text) => {
+                    let _ = write!(self.css, "/*{text}*/");
+                },
+                Item::Statement(tokens) => {
+                    self.statement(tokens);
+                    self.css.push(';');
+                },
+                Item::Rule { prelude, body } => {
+                    self.tokens(prelude);
+                    if !prelude.is_empty() {
+                        self.css.push(' ');
+                    }
+                    if body.is_empty() {
+                        self.css.push_str("{}");
+                    } else if !self.options.one_declaration_per_line && body.iter().all(|item| matches!(item, Item::Statement(_))) {
+                        self.css.push_str("{ ");
+                        for item in body {
+                            if let Item::Statement(tokens) = item {
+                                self.statement(tokens);
+                                self.css.push_str("; ");
+                            }
+                        }
+                        self.css.push('}');
+                    } else {
+                        self.css.push_str("{\n");
+                        self.items(body, depth + 1);
+                        self.indent(depth);
+                        self.css.push('}');
+                    }
+                },
+            }
+            self.css.push('\n');
+        }
+    }
+
+    /// Writes a declaration as `name: value`, and any other statement like [`Printer::tokens`] does.
+    fn statement(&mut self, tokens: &[cssparser::Token]) {
+        let colon = tokens.iter().position(|token| !matches!(token, cssparser::Token::Ident(_) | cssparser::Token::WhiteSpace(_) | cssparser::Token::Comment(_)));
+        match (tokens.first(), colon) {
+            (Some(cssparser::Token::Ident(_)), Some(colon)) if tokens[colon] == cssparser::Token::Colon => {
+                self.tokens(&tokens[..colon]);
+                self.css.push(':');
+                if tokens[colon + 1..].iter().any(|token| !matches!(token, cssparser::Token::WhiteSpace(_))) {
+                    self.css.push(' ');
+                    self.tokens(&tokens[colon + 1..]);
+                }
+            },
+            _ => self.tokens(tokens),
+        }
+    }
+
+    /// Writes `tokens` on a single line.
+    ///
+    /// Whitespace is collapsed to a single space, removed right inside of brackets and before commas
+    /// and added after commas, it is left alone everywhere else (`a > b` and `a>b` both stay).
+    fn tokens(&mut self, tokens: &[cssparser::Token]) {
+        let mut previous: Option<&cssparser::Token> = None;
+        let mut space = false;
+        for token in tokens {
+            if let cssparser::Token::WhiteSpace(_) = token {
+                space = true;
+                continue;
+            }
+            if let Some(previous) = previous {
+                let separate = if closing_token(previous).is_some() || matches!(token, cssparser::Token::Comma | cssparser::Token::Semicolon | cssparser::Token::CloseParenthesis | cssparser::Token::CloseSquareBracket | cssparser::Token::CloseCurlyBracket) {
+                    false
+                } else {
+                    space || *previous == cssparser::Token::Comma || previous.serialization_type().needs_separator_when_before(token.serialization_type())
+                };
+                if separate {
+                    self.css.push(' ');
+                }
+            }
+            self.write(token);
+            previous = Some(token);
+            space = false;
+        }
+    }
+
+    fn write(&mut self, token: &cssparser::Token) {
+        match token {
+            cssparser::Token::QuotedString(string) if self.options.quote_style == QuoteStyle::Single => {
+                self.css.push('\'');
+                for c in string.chars() {
+                    match c {
+                        '\'' | '\\' => {
+                            self.css.push('\\');
+                            self.css.push(c);
+                        },
+                        '\0' => self.css.push('\u{FFFD}'),
+                        '\x01'..='\x1F' | '\x7F' => {
+                            let _ = write!(self.css, "\\{:x} ", c as u32);
+                        },
+                        _ => self.css.push(c),
+                    }
+                }
+                self.css.push('\'');
+            },
+            // writing to a `String` cannot fail
+            _ => {
+                let _ = token.to_css(&mut self.css);
+            },
+        }
+    }
+}
+
+/// Re-indents `input` according to `options`, tokenizing it the same way `parse_css` does.
+///
+/// Comments are kept, every other bit of whitespace is replaced,
+/// so formatting the result again gives back the same text.
+pub fn format(input: &str, options: FormatOptions) -> Result<String, Error> {
+    let mut builder = Builder { blocks: vec![(Vec::new(), Vec::new())], current: Vec::new(), closing: Vec::new() };
+
+    let mut parser_input = cssparser::ParserInput::new(input);
+    let mut parser = cssparser::Parser::new(&mut parser_input);
+    walk_including_whitespace::<Infallible>(&mut parser, &mut |token, _| builder.push(token))
+        .map_err(|e| Error::from_parse_error(&e, parser.position()))?;
+
+    let mut printer = Printer { options, css: String::new() };
+    printer.items(&builder.finish(), 0);
+    Ok(printer.css)
+}
+
+#[ffi_export]
+pub fn format_options_default() -> FormatOptions {
+    FormatOptions::default()
+}
+
+/// Formats the NUL-terminated string `input`, writing the result to `css`.
+///
+/// `options` may be null to use `format_options_default`.
+/// `css` is always written to and must be released with `free_string`,
+/// `error` behaves like it does for `parse_css`.
+#[ffi_export]
+pub fn format_css(input: *const safer_ffi::c_char, options: Option<&FormatOptions>, css: Out<'_, cstr>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        format(input_str(input)?, options.copied().unwrap_or_default()).map(Into::into)
+    });
+
+    finish(result, css, || String::new().into(), error)
+}
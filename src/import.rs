@@ -0,0 +1,337 @@
+#![allow(nonstandard_style)]
+use std::ffi::c_void;
+
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{repr_c, Out}};
+
+use crate::{
+    block_end,
+    cstr,
+    error::{catch_panic, finish, Error, ErrorKind, Status},
+    input_str,
+    layout::breadth_first,
+    stylesheet::{rule_nodes, Rule, RuleNode, RuleType, StyleSheet},
+    skip_whitespace,
+    view::StrView,
+    Token,
+};
+
+/// A stylesheet with its imports inlined, free it with `free_loaded_stylesheet`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct LoadedStyleSheet {
+    /// The rules of every file, where each `@import` is replaced by the rules of the file it imports.
+    /// Those are wrapped in an `@media`, `@supports` and `@layer` rule for the conditions of the import, if it has any.
+    /// The errors of every file are in `sheet.errors`, along with the imports that were skipped.
+    pub sheet: StyleSheet,
+    /// The resolved name of every file that was loaded, the entry first.
+    pub files: repr_c::Vec<cstr>,
+    /// The file each rule of `sheet.rules` comes from, as an index in `files`.
+    /// The rules that wrap an import come from the file with the `@import`.
+    pub rule_files: repr_c::Vec<usize>,
+    /// The file each error of `sheet.errors` occurred in, locations are in that file.
+    pub error_files: repr_c::Vec<usize>,
+}
+
+/// Where a rule comes from, shaped like the [`RuleNode`] it belongs to.
+struct Origin {
+    file: usize,
+    children: Vec<Origin>,
+}
+
+impl Origin {
+    /// The origin of `node` and its nested rules, which all come from `file`.
+    fn of(node: &RuleNode, file: usize) -> Self {
+        Self { file, children: node.children.iter().map(|child| Self::of(child, file)).collect() }
+    }
+}
+
+/// What an `@import` prelude holds, such as `url("a.css") layer(base) supports(display: grid) screen`.
+struct Import<'a> {
+    url: String,
+    /// The tokens of the layer name, empty for an anonymous `layer`.
+    layer: Option<&'a [Token]>,
+    /// The `supports(` token, the tokens of the condition, and the `)`.
+    supports: Option<(&'a Token, &'a [Token], &'a Token)>,
+    /// The media query list, empty when there is none.
+    media: &'a [Token],
+}
+
+/// Splits the prelude of an `@import`, or says why it is invalid.
+fn parse_import(prelude: &[Token]) -> Result<Import<'_>, &'static str> {
+    let invalid = "@import must start with a URL";
+    let function_end = |index: usize, name: &str| match cssparser::Token::from(&*prelude.get(index)?.value) {
+        cssparser::Token::Function(function) if function.eq_ignore_ascii_case(name) => block_end(prelude, index),
+        _ => None,
+    };
+
+    let (url, mut index) = match cssparser::Token::from(&*prelude.first().ok_or(invalid)?.value) {
+        cssparser::Token::QuotedString(url) | cssparser::Token::UnquotedUrl(url) => (url.to_string(), 1),
+        _ => {
+            // `url("a.css")`
+            let end = function_end(0, "url").ok_or(invalid)?;
+            let [argument] = &prelude[skip_whitespace(prelude, 1)..end] else { return Err(invalid) };
+            match cssparser::Token::from(&*argument.value) {
+                cssparser::Token::QuotedString(url) => (url.to_string(), end + 1),
+                _ => return Err(invalid),
+            }
+        },
+    };
+
+    index = skip_whitespace(prelude, index);
+    let mut layer = None;
+    if let Some(cssparser::Token::Ident(ident)) = prelude.get(index).map(|token| cssparser::Token::from(&*token.value)) {
+        if ident.eq_ignore_ascii_case("layer") {
+            layer = Some(&prelude[..0]);
+            index += 1;
+        }
+    } else if let Some(end) = function_end(index, "layer") {
+        // only the `layer` keyword makes an anonymous layer
+        if skip_whitespace(prelude, index + 1) == end {
+            return Err("`layer()` must contain a layer name");
+        }
+        layer = Some(&prelude[index + 1..end]);
+        index = end + 1;
+    }
+
+    index = skip_whitespace(prelude, index);
+    let mut supports = None;
+    if let Some(end) = function_end(index, "supports") {
+        supports = Some((&prelude[index], &prelude[index + 1..end], &prelude[end]));
+        index = end + 1;
+    }
+
+    index = skip_whitespace(prelude, index);
+    Ok(Import { url, layer, supports, media: &prelude[index..] })
+}
+
+/// Whether `url` starts with a scheme such as `https:` or `data:`.
+fn has_scheme(url: &str) -> bool {
+    let Some((scheme, _)) = url.split_once(':') else { return false };
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic()) && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+}
+
+/// Resolves `url` against `base`, the name of the file that imports it, like a browser resolves a relative URL.
+///
+/// `.` and `..` segments are removed so that a file has one name however it is reached.
+pub fn resolve_url(url: &str, base: &str) -> String {
+    if has_scheme(url) && !url.contains("://") {
+        // `data:` and the like have no path
+        return url.to_string();
+    }
+    // the scheme and host of an absolute URL are left alone
+    let path_start = |url: &str| url.find("://").map_or(0, |separator| url[separator + 3..].find('/').map_or(url.len(), |slash| separator + 3 + slash));
+    let url = if has_scheme(url) {
+        url.to_string()
+    } else if url.starts_with("//") {
+        format!("{}{url}", &base[..base.find("//").unwrap_or(0)])
+    } else if url.starts_with('/') {
+        format!("{}{url}", &base[..path_start(base)])
+    } else {
+        // relative to the directory of `base`, or to its root when it is only a scheme and host
+        let (directory, origin) = (base.rfind('/').map_or(0, |slash| slash + 1), path_start(base));
+        match directory > origin || origin == 0 {
+            true => format!("{}{url}", &base[..directory]),
+            false => format!("{}/{url}", &base[..origin]),
+        }
+    };
+
+    let (origin, path) = url.split_at(path_start(&url));
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "." => {},
+            ".." => match segments.last() {
+                // above the root
+                Some(&"") if segments.len() == 1 => {},
+                Some(&last) if last != ".." => {
+                    segments.pop();
+                },
+                _ => segments.push(".."),
+            },
+            segment => segments.push(segment),
+        }
+    }
+    format!("{origin}{}", segments.join("/"))
+}
+
+/// Loads files through the host and inlines their imports.
+struct Loader<'a> {
+    load: &'a mut dyn FnMut(&str) -> Option<String>,
+    /// The name and text of every file that was loaded.
+    files: Vec<(String, String)>,
+    /// Whether the errors of each file were recorded, a file imported twice is parsed twice.
+    parsed: Vec<bool>,
+    errors: Vec<(Error, usize)>,
+    /// The files being imported, the innermost last.
+    stack: Vec<usize>,
+}
+
+impl Loader<'_> {
+    /// The index of the file `name`, loading it the first time.
+    fn file(&mut self, name: &str) -> Option<usize> {
+        if let Some(index) = self.files.iter().position(|(file, _)| file == name) {
+            return Some(index);
+        }
+        let text = (self.load)(name)?;
+        self.files.push((name.to_string(), text));
+        self.parsed.push(false);
+        Some(self.files.len() - 1)
+    }
+
+    /// The rules of `file` with its imports inlined.
+    fn rules(&mut self, file: usize) -> Vec<(RuleNode, Origin)> {
+        let mut errors = Vec::new();
+        let nodes = rule_nodes(&self.files[file].1, &mut errors);
+        if !std::mem::replace(&mut self.parsed[file], true) {
+            self.errors.extend(errors.into_iter().map(|error| (error, file)));
+        }
+
+        self.stack.push(file);
+        let mut rules = Vec::new();
+        // only `@charset` and `@layer` statements may come before an `@import`
+        let mut imports_allowed = true;
+        for node in nodes {
+            let is_import = node.rule.rule_type == RuleType::At && node.rule.name.eq_ignore_ascii_case("import");
+            if is_import && imports_allowed {
+                rules.extend(self.import(node.rule, file));
+            } else if is_import {
+                let error = Error::new(ErrorKind::AtRuleInvalid, "@import must come before all other rules").at(node.rule.location);
+                self.errors.push((error, file));
+            } else {
+                let statement = node.rule.rule_type == RuleType::At && !node.rule.has_block && node.rule.name.eq_ignore_ascii_case("layer");
+                imports_allowed &= statement;
+                let origin = Origin::of(&node, file);
+                rules.push((node, origin));
+            }
+        }
+        self.stack.pop();
+        rules
+    }
+
+    /// The rules the `@import` rule `rule` of `file` stands for, wrapped in its conditions.
+    fn import(&mut self, rule: Rule, file: usize) -> Vec<(RuleNode, Origin)> {
+        let import = match parse_import(&rule.prelude) {
+            Ok(import) => import,
+            Err(message) => {
+                self.errors.push((Error::new(ErrorKind::AtRuleInvalid, message).at(rule.location), file));
+                return Vec::new();
+            },
+        };
+        let name = resolve_url(&import.url, &self.files[file].0);
+        if self.stack.iter().any(|&importing| self.files[importing].0 == name) {
+            let error = Error::new(ErrorKind::ImportCycle, format!("`{name}` is already being imported")).at(rule.location);
+            self.errors.push((error, file));
+            return Vec::new();
+        }
+        let Some(imported) = self.file(&name) else {
+            let error = Error::new(ErrorKind::LoadFailed, format!("could not load `{name}`")).at(rule.location);
+            self.errors.push((error, file));
+            return Vec::new();
+        };
+        let mut rules = self.rules(imported);
+
+        // the wrapping rules take the place of the `@import`
+        let location = rule.location;
+        let wrap = |name: &str, prelude: Vec<Token>, rules: Vec<(RuleNode, Origin)>| {
+            let (children, origins) = rules.into_iter().unzip();
+            let rule = Rule {
+                rule_type: RuleType::At,
+                name: name.to_string().into(),
+                prelude: prelude.into(),
+                has_block: true,
+                declarations: Vec::new().into(),
+                first_child: 0,
+                child_count: 0,
+                location,
+            };
+            vec![(RuleNode { rule, children }, Origin { file, children: origins })]
+        };
+        if let Some(layer) = import.layer {
+            rules = wrap("layer", layer.to_vec(), rules);
+        }
+        if let Some((open, condition, close)) = import.supports {
+            // `supports(display: grid)` becomes `@supports (display: grid)`
+            let open = Token::from(cssparser::Token::ParenthesisBlock).at(open.location);
+            let close = Token::from(cssparser::Token::CloseParenthesis).at(close.location);
+            rules = wrap("supports", [open].into_iter().chain(condition.iter().cloned()).chain([close]).collect(), rules);
+        }
+        if !import.media.is_empty() {
+            rules = wrap("media", import.media.to_vec(), rules);
+        }
+        rules
+    }
+}
+
+/// Loads the stylesheet `entry` and everything it imports through `load`, which returns the text of a file or `None`.
+///
+/// Imports are resolved against the name of the file they are in, see [`resolve_url`].
+/// Imports that cannot be loaded or would import a file into itself are skipped and recorded as errors,
+/// only failing to load `entry` fails.
+pub fn load(entry: &str, load: &mut dyn FnMut(&str) -> Option<String>) -> Result<LoadedStyleSheet, Error> {
+    let mut loader = Loader { load, files: Vec::new(), parsed: Vec::new(), errors: Vec::new(), stack: Vec::new() };
+    let entry = resolve_url(entry, "");
+    let Some(file) = loader.file(&entry) else {
+        return Err(Error::new(ErrorKind::LoadFailed, format!("could not load `{entry}`")));
+    };
+    let (rules, origins): (Vec<_>, Vec<_>) = loader.rules(file).into_iter().unzip();
+
+    let rule_files = breadth_first(origins, |origin| std::mem::take(&mut origin.children))
+        .into_iter()
+        .map(|(origin, ..)| origin.file)
+        .collect::<Vec<_>>();
+    let (errors, error_files): (Vec<_>, Vec<_>) = loader.errors.into_iter().unzip();
+    Ok(LoadedStyleSheet {
+        sheet: StyleSheet { errors: errors.into(), ..rules.into() },
+        files: loader.files.into_iter().map(|(name, _)| name.into()).collect::<Vec<_>>().into(),
+        rule_files: rule_files.into(),
+        error_files: error_files.into(),
+    })
+}
+
+/// Provides the text of the file `url` (already resolved, see `load_stylesheet`) to `load_stylesheet`.
+///
+/// It returns a null `ptr` when the file cannot be loaded. The text is copied right away,
+/// it only has to stay valid until the loader is called again or `load_stylesheet` returns.
+pub type StyleSheetLoader = unsafe extern "C" fn(url: StrView, user: *mut c_void) -> StrView;
+
+/// Loads the stylesheet named by the NUL-terminated string `entry`, calling `loader` for it and for every file it imports.
+///
+/// `@import` rules at the top of a file are replaced by the rules of the file they import, wrapped in `@media`,
+/// `@supports` and `@layer` rules for their conditions. Their URL is resolved against the name of the importing file
+/// (`css/main.css` importing `../base.css` loads `base.css`): URLs starting with `/` keep the scheme and host of the importing file,
+/// URLs with a scheme keep theirs, and `.` and `..` segments are removed from every path (see `resolve_url`).
+/// An import that cannot be loaded, or that would import a file being imported, is skipped and listed in `sheet.sheet.errors`.
+/// Files that are not valid UTF-8 are decoded with `U+FFFD` replacements.
+///
+/// The call fails with `ErrorKind::LoadFailed` when `entry` itself cannot be loaded.
+/// Out-params otherwise behave like they do for `parse_css`, `sheet` must be released with `free_loaded_stylesheet`.
+#[ffi_export]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn load_stylesheet(entry: *const safer_ffi::c_char, loader: Option<StyleSheetLoader>, user: *mut c_void, sheet: Out<'_, LoadedStyleSheet>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        let entry = input_str(entry)?;
+        let loader = loader.ok_or_else(|| Error::new(ErrorKind::NullPointer, "loader is null"))?;
+        load(entry, &mut |url| {
+            let text = unsafe { loader(StrView::borrowed(url), user) };
+            if text.ptr.is_null() {
+                return None;
+            }
+            let bytes = unsafe { std::slice::from_raw_parts(text.ptr, text.len) };
+            Some(String::from_utf8_lossy(bytes).into_owned())
+        })
+    });
+
+    let empty = || LoadedStyleSheet {
+        sheet: Vec::new().into(),
+        files: Vec::new().into(),
+        rule_files: Vec::new().into(),
+        error_files: Vec::new().into(),
+    };
+    finish(result, sheet, empty, error)
+}
+
+#[ffi_export]
+pub fn free_loaded_stylesheet(sheet: LoadedStyleSheet) {
+    drop(sheet);
+}
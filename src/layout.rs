@@ -0,0 +1,23 @@
+use std::collections::VecDeque;
+
+/// Lays a tree out breadth first, like a `TokenTree`: the children of a node follow each other,
+/// after every node that is less deep. `children` takes the children out of a node.
+///
+/// Each node comes with the index of its first child (0 when it has none) and the number of children.
+pub fn breadth_first<N>(roots: Vec<N>, mut children: impl FnMut(&mut N) -> Vec<N>) -> Vec<(N, usize, usize)> {
+    let mut flat = Vec::new();
+    let mut next_free = roots.len();
+    let mut queue = VecDeque::from([roots]);
+    while let Some(siblings) = queue.pop_front() {
+        for mut node in siblings {
+            let children = children(&mut node);
+            let first_child = if children.is_empty() { 0 } else { next_free };
+            next_free += children.len();
+            flat.push((node, first_child, children.len()));
+            if !children.is_empty() {
+                queue.push_back(children);
+            }
+        }
+    }
+    flat
+}
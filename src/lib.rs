@@ -1,5 +1,7 @@
 #![allow(nonstandard_style)]
-use safer_ffi::{derive_ReprC, ffi_export, prelude::repr_c};
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{repr_c, Out}};
+use error::{catch_panic, finish, Error, ErrorKind, Status};
+use location::SourceLocation;
 use token_types::{get_token_type, TokenType};
 use token_union::TokenValue;
 use std::fmt::Debug;
@@ -19,6 +21,27 @@ pub fn gen_headers() {
 // The C struct representations for their rust counterparts
 pub mod token_union;
 pub mod token_types;
+pub mod location;
+pub mod error;
+pub mod tokenizer;
+pub mod tree;
+pub mod view;
+pub mod token_list;
+pub mod serialize;
+pub mod minify;
+pub mod format;
+pub mod stylesheet;
+pub mod selector;
+pub mod matching;
+pub mod color;
+pub mod nth;
+pub mod unicode_range;
+pub mod events;
+pub mod media;
+pub mod import;
+pub mod custom_properties;
+pub mod calc;
+pub mod layout;
 
 #[derive_ReprC]
 #[repr(C)]
@@ -39,6 +62,9 @@ pub struct Token {
     /// }
     /// ```
     pub value: repr_c::Box<TokenValue>,
+    /// Where the token was found in the input.
+    /// Tokens that were not produced by parsing input have an all-zero location.
+    pub location: SourceLocation,
 }
 
 #[inline]
@@ -54,23 +80,20 @@ fn token2str(token: &Token) -> String {
         TokenType::Function => token.value.get_function().into(),
         TokenType::Percentage => {
             let percent = token.value.get_percentage();
-            let value = format!("{:?}", percent);
-            value.into()
+            format!("{:?}", percent)
         },
         TokenType::Dimension => {
             let dim = token.value.get_dimension();
-            let value = format!("{:?}", dim);
-            value.into()
+            format!("{:?}", dim)
         },
         TokenType::Number => {
             let num = token.value.get_number();
-            let value = format!("{:?}", num);
-            value.into()
+            format!("{:?}", num)
         },
         TokenType::WhiteSpace => token.value.get_whitespace().into(),
         TokenType::BadString => token.value.get_bad_string().into(),
         TokenType::BadUrl => token.value.get_bad_url().into(),
-        TokenType::Delim => token.value.get_delim().to_string(),
+        TokenType::Delim => token.value.get_delim_char().to_string(),
         TokenType::Colon => ":".to_string(),
         TokenType::Semicolon => ";".to_string(),
         TokenType::Comma => ",".to_string(),
@@ -101,6 +124,12 @@ impl Debug for Token {
     }
 }
 
+impl Clone for Token {
+    fn clone(&self) -> Self {
+        Token::from(cssparser::Token::from(&*self.value)).at(self.location)
+    }
+}
+
 #[ffi_export]
 pub fn debug_token(token: &Token) {
     println!("{:?}", token);
@@ -108,7 +137,11 @@ pub fn debug_token(token: &Token) {
 
 impl Token {
     pub fn new(token_type: TokenType, value: TokenValue) -> Token {
-        Self { token_type, value: Box::new(value).into() }
+        Self { token_type, value: Box::new(value).into(), location: SourceLocation::default() }
+    }
+
+    pub fn at(self, location: SourceLocation) -> Token {
+        Self { location, ..self }
     }
 }
 
@@ -124,33 +157,88 @@ impl<'a> From<cssparser::Token<'a>> for Token {
     }
 }
 
-#[no_mangle]
-pub fn parse<'a>(parser: &mut cssparser::Parser, mut tokens: Vec<Token>) -> Result<Vec<Token>, cssparser::ParseError<'a, Vec<Token>>> {
-    let parse_inner = |parser: &mut cssparser::Parser, tokens: Vec<Token>| parser.parse_nested_block::<_, Vec<Token>, Vec<Token>>(|p| parse(p, tokens)).expect("Failed to parse nested block");
-    while let Ok(token) = parser.next().cloned() {
-        tokens.push(Token::from(&token));
-        match token {
-            cssparser::Token::Function(_) => {
-                tokens = parse_inner(parser, tokens);
-            },
-            cssparser::Token::CurlyBracketBlock => {
-                tokens = parse_inner(parser, tokens);
-                // add a closing token
-                tokens.push(cssparser::Token::CloseCurlyBracket.into());
-            },
-            cssparser::Token::SquareBracketBlock => {
-                tokens = parse_inner(parser, tokens);
-                // add a closing token
-                tokens.push(cssparser::Token::CloseSquareBracket.into());
-            },
-            cssparser::Token::ParenthesisBlock => {
-                tokens = parse_inner(parser, tokens);
-                // add a closing token
-                tokens.push(cssparser::Token::CloseParenthesis.into());
+/// Visits the tokens of the current block (or the whole input at the top level) in the order `parse` collects them.
+///
+/// Whitespace and comments are skipped, nested blocks are walked in place
+/// and every block other than a function is followed by a synthesized closing token.
+pub fn walk<'i, E>(parser: &mut cssparser::Parser<'i, '_>, visit: &mut impl FnMut(&cssparser::Token<'i>, SourceLocation)) -> Result<(), cssparser::ParseError<'i, E>> {
+    walk_tokens(parser, false, false, visit)
+}
+
+/// Same as [`walk`], but functions are followed by a closing token too, so where each one ends is not lost.
+pub fn walk_closed<'i, E>(parser: &mut cssparser::Parser<'i, '_>, visit: &mut impl FnMut(&cssparser::Token<'i>, SourceLocation)) -> Result<(), cssparser::ParseError<'i, E>> {
+    walk_tokens(parser, false, true, visit)
+}
+
+/// Same as [`walk_closed`], but whitespace and comment tokens are visited too.
+pub fn walk_including_whitespace<'i, E>(parser: &mut cssparser::Parser<'i, '_>, visit: &mut impl FnMut(&cssparser::Token<'i>, SourceLocation)) -> Result<(), cssparser::ParseError<'i, E>> {
+    walk_tokens(parser, true, true, visit)
+}
+
+fn walk_tokens<'i, E>(parser: &mut cssparser::Parser<'i, '_>, whitespace: bool, close_functions: bool, visit: &mut impl FnMut(&cssparser::Token<'i>, SourceLocation)) -> Result<(), cssparser::ParseError<'i, E>> {
+    loop {
+        if !whitespace {
+            // skip ahead ourselves so the token's start does not include the whitespace `next` would skip
+            parser.skip_whitespace();
+        }
+        let start = parser.position();
+        let start_location = parser.current_source_location();
+        let Ok(token) = parser.next_including_whitespace_and_comments().cloned() else { break };
+        let block_start = parser.position();
+        visit(&token, SourceLocation::new(start, block_start, start_location));
+        let (closing, closing_char) = match token {
+            cssparser::Token::Function(_) => (cssparser::Token::CloseParenthesis, ')'),
+            cssparser::Token::CurlyBracketBlock => (cssparser::Token::CloseCurlyBracket, '}'),
+            cssparser::Token::SquareBracketBlock => (cssparser::Token::CloseSquareBracket, ']'),
+            cssparser::Token::ParenthesisBlock => (cssparser::Token::CloseParenthesis, ')'),
+            _ => continue,
+        };
+        parser.parse_nested_block(|p| walk_tokens(p, whitespace, close_functions, visit))?;
+        if matches!(token, cssparser::Token::Function(_)) && !close_functions {
+            continue;
+        }
+        // add a closing token
+        visit(&closing, SourceLocation::closing(parser, block_start, closing_char));
+    }
+    Ok(())
+}
+
+/// The index of the token closing the block or function opened at `open` in `tokens`.
+///
+/// Parsing always closes blocks, so this is `None` when `tokens` ends inside the block, as a slice of the tokens can,
+/// or when `open` is a function from `parse_css`, which does not close them.
+/// The functions left open inside of a block end with it.
+pub fn block_end(tokens: &[Token], open: usize) -> Option<usize> {
+    // whether each block still open is a function
+    let mut blocks = Vec::new();
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        match token.token_type {
+            TokenType::Function => blocks.push(true),
+            TokenType::ParenthesisBlock | TokenType::SquareBracketBlock | TokenType::CurlyBracketBlock => blocks.push(false),
+            TokenType::CloseParenthesis => {
+                blocks.pop();
             },
+            TokenType::CloseSquareBracket | TokenType::CloseCurlyBracket => while blocks.pop() == Some(true) {},
             _ => {},
         }
+        if blocks.is_empty() {
+            return Some(index);
+        }
     }
+    None
+}
+
+/// The index of the first token at or after `index` that is not whitespace, `tokens.len()` when there is none.
+pub fn skip_whitespace(tokens: &[Token], mut index: usize) -> usize {
+    while tokens.get(index).is_some_and(|token| token.token_type == TokenType::WhiteSpace) {
+        index += 1;
+    }
+    index
+}
+
+#[no_mangle]
+pub fn parse<'i>(parser: &mut cssparser::Parser<'i, '_>, mut tokens: Vec<Token>) -> Result<Vec<Token>, cssparser::ParseError<'i, Vec<Token>>> {
+    walk(parser, &mut |token, location| tokens.push(Token::from(token).at(location)))?;
     Ok(tokens)
 }
 
@@ -161,34 +249,70 @@ pub fn free_tokens(tokens: safer_ffi::Vec<Token>) {
 }
 
 #[ffi_export]
-pub fn parse_css<'i>(input: *const safer_ffi::c_char) -> safer_ffi::Vec<Token> {
-    let input = unsafe { std::ffi::CStr::from_ptr(input as *const _).to_str().expect("Failed to convert input to string") };
+pub fn free_token(token: Token) {
+    drop(token);
+}
+
+/// Borrows the NUL-terminated string `input`, which must be valid UTF-8.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn input_str<'a>(input: *const safer_ffi::c_char) -> Result<&'a str, Error> {
+    if input.is_null() {
+        return Err(Error::new(ErrorKind::NullPointer, "input is null"));
+    }
+    let input = unsafe { std::ffi::CStr::from_ptr(input as *const _) };
+    input.to_str().map_err(Error::invalid_utf8)
+}
+
+/// Tokenizes the whole input, see [`walk`] and [`walk_closed`] for `close_functions`.
+fn tokenize(input: &str, close_functions: bool) -> Result<Vec<Token>, Error> {
     let mut input = cssparser::ParserInput::new(input);
     let mut parser = cssparser::Parser::new(&mut input);
 
-    let mut tokens = None;
-    loop {
-        let toks = match parse(&mut parser, Vec::new()) {
-            Ok(v) => v,
-            Err(v) => {
-                match v.kind {
-                    cssparser::ParseErrorKind::Basic(_) => break,
-                    cssparser::ParseErrorKind::Custom(v) => {
-                        tokens = Some(v);
-                        break;
-                    },
-                }
-            },
-        };
+    let mut tokens = Vec::new();
+    let result = walk_tokens::<std::convert::Infallible>(&mut parser, false, close_functions, &mut |token, location| {
+        tokens.push(Token::from(token).at(location))
+    });
+    match result {
+        Ok(()) => Ok(tokens),
+        Err(e) => Err(Error::from_parse_error(&e, parser.position())),
+    }
+}
 
-        if toks.is_empty() {
-            break;
-        }
+/// Parses the NUL-terminated string `input` into a list of tokens written to `tokens`.
+///
+/// Every block is followed by a closing token, functions are not.
+/// `error` may be null, otherwise it receives the details of a failure (or an `ErrorKind::None` error
+/// on success), which must be released with `free_error`.
+/// `tokens` is always written to and must be released with `free_tokens`.
+#[ffi_export]
+pub fn parse_css(input: *const safer_ffi::c_char, tokens: Out<'_, safer_ffi::Vec<Token>>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        tokenize(input_str(input)?, false).map(Into::into)
+    });
 
-        tokens = Some(toks);
-    }
+    finish(result, tokens, || Vec::new().into(), error)
+}
 
-    let tokens: safer_ffi::Vec<Token> = tokens.expect("Failed to parse tokens").into();
+/// Parses `len` bytes starting at `input`, which do not need to be NUL-terminated.
+///
+/// Invalid UTF-8 sequences are replaced with `U+FFFD` before tokenizing and
+/// an empty input (or a null `input` with a `len` of 0) gives an empty list.
+/// Out-params behave like they do for `parse_css`.
+///
+/// When invalid UTF-8 was replaced, token locations refer to the decoded text rather than the raw bytes.
+#[ffi_export]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn parse_css_bytes(input: *const u8, len: usize, tokens: Out<'_, safer_ffi::Vec<Token>>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        if len == 0 {
+            return Ok(Vec::new().into());
+        }
+        if input.is_null() {
+            return Err(Error::new(ErrorKind::NullPointer, "input is null"));
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(input, len) };
+        tokenize(&String::from_utf8_lossy(bytes), false).map(Into::into)
+    });
 
-    tokens
+    finish(result, tokens, || Vec::new().into(), error)
 }
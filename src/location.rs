@@ -0,0 +1,50 @@
+#![allow(nonstandard_style)]
+use safer_ffi::derive_ReprC;
+
+/// Where a token was found in the original input.
+///
+/// `start` and `end` are byte offsets into the input (`end` is exclusive),
+/// `line` and `column` describe the first byte of the token and both start at 1.
+/// Columns are counted in UTF-16 code units, like `cssparser` does.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// Byte offset of the first byte of the token.
+    pub start: usize,
+    /// Byte offset one past the last byte of the token.
+    pub end: usize,
+    /// The line the token starts on, starting at 1.
+    pub line: u32,
+    /// The column the token starts at, starting at 1.
+    pub column: u32,
+}
+
+impl SourceLocation {
+    pub fn new(start: cssparser::SourcePosition, end: cssparser::SourcePosition, location: cssparser::SourceLocation) -> Self {
+        Self {
+            start: start.byte_index(),
+            end: end.byte_index(),
+            line: location.line + 1,
+            column: location.column,
+        }
+    }
+
+    /// The location of the closing token of a block that started at `block_start`
+    /// and was just consumed by `parse_nested_block`.
+    ///
+    /// Blocks left open at the end of the input have no closing character,
+    /// in which case the location is empty and sits at the end of the input.
+    pub fn closing(parser: &cssparser::Parser, block_start: cssparser::SourcePosition, closing: char) -> Self {
+        let end = parser.position().byte_index();
+        let location = parser.current_source_location();
+        let closed = parser.slice_from(block_start).ends_with(closing);
+        let width = if closed { closing.len_utf8() } else { 0 };
+        Self {
+            start: end - width,
+            end,
+            line: location.line + 1,
+            column: location.column - width as u32,
+        }
+    }
+}
@@ -0,0 +1,311 @@
+#![allow(nonstandard_style)]
+use std::ffi::c_void;
+
+use safer_ffi::{derive_ReprC, ffi_export, prelude::Out};
+
+use crate::{error::{catch_panic, finish, Error, ErrorKind, Status}, nth::Nth, selector::{AttributeOperator, Combinator, ComponentType, Selector, SelectorComponent, SelectorList}, view::StrView};
+
+/// An element of the host's tree, the library only ever passes it back to the callbacks.
+pub type Element = *const c_void;
+
+/// How the matching engine queries the host's tree.
+///
+/// Every callback receives the `user` pointer given to `selector_matches`.
+/// Strings are passed both ways as `StrView_t`s (their `owned` flag is not used),
+/// those returned by the host only need to stay valid until the matching call returns
+/// and a null `ptr` means the value is absent.
+/// Elements are opaque pointers, null meaning there is no such element.
+/// Every callback but `match_pseudo_class` is required, matching fails with `ERROR_KIND_NULL_POINTER` when one is null.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ElementCallbacks {
+    /// The local name of the element, compared ASCII case-insensitively with type selectors.
+    pub local_name: Option<unsafe extern "C" fn(element: Element, user: *mut c_void) -> StrView>,
+    /// The id of the element, or a null `ptr` when it has none.
+    pub id: Option<unsafe extern "C" fn(element: Element, user: *mut c_void) -> StrView>,
+    pub has_class: Option<unsafe extern "C" fn(element: Element, name: StrView, user: *mut c_void) -> bool>,
+    /// The value of the attribute `name` (as written in the selector), or a null `ptr` when it is not set.
+    pub attribute: Option<unsafe extern "C" fn(element: Element, name: StrView, user: *mut c_void) -> StrView>,
+    pub parent: Option<unsafe extern "C" fn(element: Element, user: *mut c_void) -> Element>,
+    /// The closest preceding sibling that is an element.
+    pub previous_sibling: Option<unsafe extern "C" fn(element: Element, user: *mut c_void) -> Element>,
+    /// The closest following sibling that is an element.
+    pub next_sibling: Option<unsafe extern "C" fn(element: Element, user: *mut c_void) -> Element>,
+    /// The first child that is an element, used by `:has()`.
+    pub first_child: Option<unsafe extern "C" fn(element: Element, user: *mut c_void) -> Element>,
+    pub is_root: Option<unsafe extern "C" fn(element: Element, user: *mut c_void) -> bool>,
+    /// Whether the element matches a pseudo-class the engine does not know about, such as `:hover`, `:checked` or `:empty`.
+    /// Such pseudo-classes never match when it is null.
+    pub match_pseudo_class: Option<unsafe extern "C" fn(element: Element, name: StrView, user: *mut c_void) -> bool>,
+}
+
+/// The callbacks of an [`ElementCallbacks`] once the required ones are known to be set.
+struct Callbacks {
+    local_name: unsafe extern "C" fn(element: Element, user: *mut c_void) -> StrView,
+    id: unsafe extern "C" fn(element: Element, user: *mut c_void) -> StrView,
+    has_class: unsafe extern "C" fn(element: Element, name: StrView, user: *mut c_void) -> bool,
+    attribute: unsafe extern "C" fn(element: Element, name: StrView, user: *mut c_void) -> StrView,
+    parent: unsafe extern "C" fn(element: Element, user: *mut c_void) -> Element,
+    previous_sibling: unsafe extern "C" fn(element: Element, user: *mut c_void) -> Element,
+    next_sibling: unsafe extern "C" fn(element: Element, user: *mut c_void) -> Element,
+    first_child: unsafe extern "C" fn(element: Element, user: *mut c_void) -> Element,
+    is_root: unsafe extern "C" fn(element: Element, user: *mut c_void) -> bool,
+    match_pseudo_class: Option<unsafe extern "C" fn(element: Element, name: StrView, user: *mut c_void) -> bool>,
+}
+
+impl TryFrom<&ElementCallbacks> for Callbacks {
+    type Error = Error;
+
+    fn try_from(callbacks: &ElementCallbacks) -> Result<Self, Error> {
+        fn required<F>(callback: Option<F>, name: &str) -> Result<F, Error> {
+            callback.ok_or_else(|| Error::new(ErrorKind::NullPointer, format!("the `{name}` callback is null")))
+        }
+        Ok(Self {
+            local_name: required(callbacks.local_name, "local_name")?,
+            id: required(callbacks.id, "id")?,
+            has_class: required(callbacks.has_class, "has_class")?,
+            attribute: required(callbacks.attribute, "attribute")?,
+            parent: required(callbacks.parent, "parent")?,
+            previous_sibling: required(callbacks.previous_sibling, "previous_sibling")?,
+            next_sibling: required(callbacks.next_sibling, "next_sibling")?,
+            first_child: required(callbacks.first_child, "first_child")?,
+            is_root: required(callbacks.is_root, "is_root")?,
+            match_pseudo_class: callbacks.match_pseudo_class,
+        })
+    }
+}
+
+/// The text of a view returned by the host, `None` when it is absent or not UTF-8.
+fn host_str<'a>(view: StrView) -> Option<&'a str> {
+    if view.ptr.is_null() {
+        return None;
+    }
+    std::str::from_utf8(unsafe { std::slice::from_raw_parts(view.ptr, view.len) }).ok()
+}
+
+struct Matcher<'a> {
+    list: &'a SelectorList,
+    callbacks: Callbacks,
+    user: *mut c_void,
+}
+
+impl Matcher<'_> {
+    fn parent(&self, element: Element) -> Element {
+        unsafe { (self.callbacks.parent)(element, self.user) }
+    }
+
+    fn previous_sibling(&self, element: Element) -> Element {
+        unsafe { (self.callbacks.previous_sibling)(element, self.user) }
+    }
+
+    fn next_sibling(&self, element: Element) -> Element {
+        unsafe { (self.callbacks.next_sibling)(element, self.user) }
+    }
+
+    fn local_name(&self, element: Element) -> Option<&str> {
+        host_str(unsafe { (self.callbacks.local_name)(element, self.user) })
+    }
+
+    /// The selectors a functional pseudo-class takes.
+    fn arguments(&self, component: &SelectorComponent) -> &[Selector] {
+        self.list.selectors.get(component.first_selector..component.first_selector + component.selector_count).unwrap_or_default()
+    }
+
+    /// Whether `element` is matched by `selector`.
+    ///
+    /// A selector with a pseudo-element only matches when `pseudo_element` is its name,
+    /// `anchor` is the element a relative selector (from `:has()`) is relative to.
+    fn matches(&self, selector: &Selector, element: Element, pseudo_element: Option<&str>, anchor: Element) -> bool {
+        // split into compound selectors, each with the combinator on its left
+        let mut compounds = Vec::new();
+        let mut combinator = Combinator::None;
+        let mut start = 0;
+        let components = &selector.components[..];
+        for (i, component) in components.iter().enumerate() {
+            if component.component_type == ComponentType::Combinator {
+                compounds.push((combinator, &components[start..i]));
+                combinator = component.combinator;
+                start = i + 1;
+            }
+        }
+        compounds.push((combinator, &components[start..]));
+
+        let (combinator, last) = compounds.pop().unwrap();
+        self.matches_compound(last, element, pseudo_element)
+            && self.matches_rest(&compounds, combinator, element, anchor)
+    }
+
+    /// Whether the compound selectors before `element`'s one match, walking the tree as `combinator` says.
+    fn matches_rest(&self, compounds: &[(Combinator, &[SelectorComponent])], combinator: Combinator, element: Element, anchor: Element) -> bool {
+        let Some(((next_combinator, compound), rest)) = compounds.split_last() else {
+            // a relative selector without a leading combinator is about the anchor's descendants (`:has(img)`)
+            return anchor.is_null() || self.related(Combinator::Descendant, element).contains(&anchor);
+        };
+        // the empty compound before the leading combinator of a relative selector (`:has(> img)`) stands for the anchor
+        if compound.is_empty() {
+            return self.related(combinator, element).contains(&anchor);
+        }
+        self.related(combinator, element)
+            .into_iter()
+            .any(|candidate| self.matches_compound(compound, candidate, None) && self.matches_rest(rest, *next_combinator, candidate, anchor))
+    }
+
+    /// The elements `combinator` allows on its left when `element` is on its right.
+    fn related(&self, combinator: Combinator, element: Element) -> Vec<Element> {
+        let (first, step): (Element, fn(&Self, Element) -> Element) = match combinator {
+            Combinator::Descendant => (self.parent(element), Self::parent),
+            Combinator::Child => (self.parent(element), |_, _| std::ptr::null()),
+            Combinator::NextSibling => (self.previous_sibling(element), |_, _| std::ptr::null()),
+            Combinator::SubsequentSibling => (self.previous_sibling(element), Self::previous_sibling),
+            Combinator::None => (std::ptr::null(), |_, _| std::ptr::null()),
+        };
+        self.chain(first, step)
+    }
+
+    /// `first` and the elements reached from it with `step`, up to the first null one.
+    fn chain(&self, first: Element, step: fn(&Self, Element) -> Element) -> Vec<Element> {
+        // the host is never handed a null element
+        let non_null = |element: Element| (!element.is_null()).then_some(element);
+        std::iter::successors(non_null(first), |&element| non_null(step(self, element))).collect()
+    }
+
+    fn matches_compound(&self, compound: &[SelectorComponent], element: Element, pseudo_element: Option<&str>) -> bool {
+        let has_pseudo_element = compound.iter().any(|component| component.component_type == ComponentType::PseudoElement);
+        if pseudo_element.is_some() != has_pseudo_element {
+            return false;
+        }
+        compound.iter().all(|component| self.matches_component(component, element, pseudo_element))
+    }
+
+    fn matches_component(&self, component: &SelectorComponent, element: Element, pseudo_element: Option<&str>) -> bool {
+        let name = component.name.to_string();
+        match component.component_type {
+            ComponentType::Type => self.local_name(element).is_some_and(|local_name| local_name.eq_ignore_ascii_case(&name)),
+            ComponentType::Universal | ComponentType::Combinator => true,
+            // outside of a style rule `&` is `:scope`, which is the root without a scoping element
+            ComponentType::Nesting => unsafe { (self.callbacks.is_root)(element, self.user) },
+            ComponentType::Id => host_str(unsafe { (self.callbacks.id)(element, self.user) }) == Some(&*name),
+            ComponentType::Class => unsafe { (self.callbacks.has_class)(element, StrView::borrowed(&name), self.user) },
+            ComponentType::Attribute => {
+                let Some(value) = host_str(unsafe { (self.callbacks.attribute)(element, StrView::borrowed(&name), self.user) }) else { return false };
+                let (value, expected) = match component.case_insensitive {
+                    true => (value.to_ascii_lowercase(), component.value.to_string().to_ascii_lowercase()),
+                    false => (value.to_string(), component.value.to_string()),
+                };
+                match component.attribute_operator {
+                    AttributeOperator::Exists => true,
+                    AttributeOperator::Equal => value == expected,
+                    AttributeOperator::IncludeMatch => value.split_ascii_whitespace().any(|word| word == expected),
+                    AttributeOperator::DashMatch => value == expected || value.strip_prefix(&expected).is_some_and(|rest| rest.starts_with('-')),
+                    // an empty value never matches the substring operators
+                    AttributeOperator::PrefixMatch => !expected.is_empty() && value.starts_with(&expected),
+                    AttributeOperator::SuffixMatch => !expected.is_empty() && value.ends_with(&expected),
+                    AttributeOperator::SubstringMatch => !expected.is_empty() && value.contains(&expected),
+                }
+            },
+            ComponentType::PseudoElement => pseudo_element.is_some_and(|pseudo_element| pseudo_element.eq_ignore_ascii_case(&name)),
+            ComponentType::PseudoClass => self.matches_pseudo_class(component, &name.to_ascii_lowercase(), element),
+        }
+    }
+
+    fn matches_pseudo_class(&self, component: &SelectorComponent, name: &str, element: Element) -> bool {
+        let arguments = self.arguments(component);
+        let any_argument = |element| arguments.iter().any(|selector| self.matches(selector, element, None, std::ptr::null()));
+        match name {
+            "root" => unsafe { (self.callbacks.is_root)(element, self.user) },
+            "is" | "where" | "matches" | "-webkit-any" | "-moz-any" => any_argument(element),
+            "not" => !any_argument(element),
+            "has" => self.has(arguments, element),
+            "first-child" => self.previous_sibling(element).is_null(),
+            "last-child" => self.next_sibling(element).is_null(),
+            "only-child" => self.previous_sibling(element).is_null() && self.next_sibling(element).is_null(),
+            "first-of-type" => self.position(element, Self::previous_sibling, true, &|_| true) == 1,
+            "last-of-type" => self.position(element, Self::next_sibling, true, &|_| true) == 1,
+            "only-of-type" => self.position(element, Self::previous_sibling, true, &|_| true) == 1 && self.position(element, Self::next_sibling, true, &|_| true) == 1,
+            "nth-child" | "nth-last-child" | "nth-of-type" | "nth-last-of-type" if component.has_nth => {
+                // `:nth-child(An+B of S)` only counts the siblings matched by S, and the element must be one of them
+                if component.selector_count > 0 && !any_argument(element) {
+                    return false;
+                }
+                let step = if name.starts_with("nth-last") { Self::next_sibling } else { Self::previous_sibling };
+                let index = self.position(element, step, name.ends_with("of-type"), &|sibling| arguments.is_empty() || any_argument(sibling));
+                Nth { a: component.nth_a, b: component.nth_b }.matches(index)
+            },
+            _ => match self.callbacks.match_pseudo_class {
+                Some(match_pseudo_class) => unsafe { match_pseudo_class(element, StrView::borrowed(&component.name.to_string()), self.user) },
+                None => false,
+            },
+        }
+    }
+
+    /// The 1-based position of `element` among the siblings reached with `step` that `counts`,
+    /// only counting those with the same local name when `of_type` is set.
+    fn position(&self, element: Element, step: fn(&Self, Element) -> Element, of_type: bool, counts: &dyn Fn(Element) -> bool) -> i32 {
+        let local_name = self.local_name(element).map(str::to_ascii_lowercase);
+        self.chain(step(self, element), step)
+            .into_iter()
+            .filter(|&sibling| !of_type || self.local_name(sibling).map(str::to_ascii_lowercase) == local_name)
+            .filter(|&sibling| counts(sibling))
+            .count() as i32 + 1
+    }
+
+    /// Whether any of the relative selectors of `:has()` matches an element relative to `anchor`.
+    fn has(&self, selectors: &[Selector], anchor: Element) -> bool {
+        // every descendant of the anchor, and every following sibling along with its descendants
+        let mut candidates = Vec::new();
+        let mut stack = vec![unsafe { (self.callbacks.first_child)(anchor, self.user) }, self.next_sibling(anchor)];
+        while let Some(element) = stack.pop() {
+            if element.is_null() {
+                continue;
+            }
+            candidates.push(element);
+            stack.push(self.next_sibling(element));
+            stack.push(unsafe { (self.callbacks.first_child)(element, self.user) });
+        }
+        selectors.iter().any(|selector| candidates.iter().any(|&candidate| self.matches(selector, candidate, None, anchor)))
+    }
+}
+
+/// Whether `element` is matched by the selector at `index` in `list` (any selector, including nested ones), written to `matches`.
+///
+/// With a null `pseudo_element` the element itself is matched, and selectors with a pseudo-element never match.
+/// Otherwise only selectors ending with that pseudo-element (given without its colons, such as `"before"`) match.
+/// An `index` out of bounds matches nothing.
+/// The call fails with `STATUS_INVALID_INPUT` when a required callback is null, `matches` is then `false`.
+#[ffi_export]
+#[allow(clippy::not_unsafe_ptr_arg_deref, clippy::too_many_arguments)]
+pub fn selector_matches(
+    list: &SelectorList,
+    index: usize,
+    element: Element,
+    pseudo_element: *const safer_ffi::c_char,
+    callbacks: &ElementCallbacks,
+    user: *mut c_void,
+    matches: Out<'_, bool>,
+    error: Option<Out<'_, Error>>,
+) -> Status {
+    let result = catch_panic(|| {
+        let matcher = Matcher { list, callbacks: callbacks.try_into()?, user };
+        let pseudo_element = match pseudo_element.is_null() {
+            true => None,
+            false => Some(crate::input_str(pseudo_element)?),
+        };
+        Ok(list.selectors.get(index).is_some_and(|selector| matcher.matches(selector, element, pseudo_element, std::ptr::null())))
+    });
+
+    finish(result, matches, || false, error)
+}
+
+/// Whether `element` itself is matched by any of the top-level selectors of `list`, written to `matches`.
+///
+/// Fails like `selector_matches` does.
+#[ffi_export]
+pub fn selector_list_matches(list: &SelectorList, element: Element, callbacks: &ElementCallbacks, user: *mut c_void, matches: Out<'_, bool>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        let matcher = Matcher { list, callbacks: callbacks.try_into()?, user };
+        Ok(list.selectors.iter().take(list.roots).any(|selector| matcher.matches(selector, element, None, std::ptr::null())))
+    });
+
+    finish(result, matches, || false, error)
+}
@@ -0,0 +1,765 @@
+#![allow(nonstandard_style)]
+use std::{cmp::Ordering, convert::Infallible};
+
+use cssparser::{match_ignore_ascii_case, Delimiter, ParseError, Parser, Token as CssToken};
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{c_slice, repr_c, Out}};
+
+use crate::{
+    calc::{length_unit, CalcContext},
+    cstr,
+    error::{catch_panic, finish, Error, Status},
+    input_str,
+    layout::breadth_first,
+    location::SourceLocation,
+    serialize::Reserialized,
+    stylesheet::skipped_item_error,
+    Token,
+};
+
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaQualifier {
+    None,
+    /// `only screen`, which matches like `screen`.
+    Only,
+    /// `not screen`, the whole query is negated.
+    Not,
+}
+
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaConditionType {
+    /// `(name)`, `(name: value)` or a range such as `(400px <= width < 700px)`.
+    Feature,
+    /// `not (...)`, its only operand is the first child.
+    Not,
+    /// `(...) and (...)`, true when all of its children are.
+    And,
+    /// `(...) or (...)`, true when one of its children is.
+    Or,
+    /// Something in parentheses or a function that is not a condition, such as `(foo bar)`.
+    /// It is kept in `name` as written and never matches.
+    Unknown,
+}
+
+/// How a feature compares with a value, the feature being on the left.
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaComparison {
+    /// There is no value, the feature is evaluated on its own (`(color)`).
+    None,
+    /// `(width: 500px)` or `(width = 500px)`.
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaValueType {
+    None,
+    Number,
+    /// A dimension in `unit`, such as `40em`.
+    Length,
+    /// `number / denominator`, such as `16/9`.
+    Ratio,
+    /// A dimension in `dpi`, `dpcm`, `dppx` or `x`.
+    Resolution,
+    /// A keyword, such as `landscape` or `dark`, in `ident`.
+    Ident,
+}
+
+/// The value a media feature is compared with.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct MediaValue {
+    pub value_type: MediaValueType,
+    pub number: f32,
+    /// The second number of a ratio, 1 for every other type.
+    pub denominator: f32,
+    /// The unit of a length or resolution, in lowercase.
+    pub unit: cstr,
+    /// The keyword of an `Ident`, in lowercase.
+    pub ident: cstr,
+}
+
+/// A node of the condition of a media query.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct MediaCondition {
+    pub condition_type: MediaConditionType,
+    /// The operands of `Not`, `And` and `Or`, they are `list.conditions.ptr[first_child .. first_child + child_count]`.
+    pub first_child: usize,
+    pub child_count: usize,
+    /// The name of a feature in lowercase, without its `min-` or `max-` prefix, which turn into `GreaterOrEqual` and `LessOrEqual`.
+    pub name: cstr,
+    pub comparison: MediaComparison,
+    pub value: MediaValue,
+    /// The upper or lower bound of a range with two of them, `(400px <= width <= 700px)` is `width >= 400px` then `width <= 700px`.
+    pub second_comparison: MediaComparison,
+    pub second_value: MediaValue,
+    /// The condition as written, including its parentheses.
+    pub location: SourceLocation,
+}
+
+/// One of the comma-separated queries of a [`MediaQueryList`].
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct MediaQuery {
+    pub qualifier: MediaQualifier,
+    /// The media type in lowercase, `all` when the query only has a condition.
+    pub media_type: cstr,
+    /// Whether the query has a condition, which is then `list.conditions.ptr[condition]`.
+    pub has_condition: bool,
+    pub condition: usize,
+    pub location: SourceLocation,
+}
+
+/// A parsed media query list, free it with `free_media_query_list`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct MediaQueryList {
+    pub queries: repr_c::Vec<MediaQuery>,
+    /// The conditions of every query and their operands, laid out breadth first: the condition of each query
+    /// that has one comes first, in the order of the queries.
+    pub conditions: repr_c::Vec<MediaCondition>,
+    /// The queries that could not be parsed, each of which is kept in `queries` as `not all`.
+    pub errors: repr_c::Vec<Error>,
+}
+
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaType {
+    Screen,
+    Print,
+}
+
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+/// The accuracy of a pointing device, for the `pointer` and `any-pointer` features.
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pointer {
+    None,
+    Coarse,
+    Fine,
+}
+
+/// What media queries are evaluated against, `media_environment_default` returns a desktop screen.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MediaEnvironment {
+    pub media_type: MediaType,
+    /// The size of the viewport in CSS pixels, for `width`, `height`, `aspect-ratio`, `orientation` and the `vw` and `vh` units.
+    pub width: f32,
+    pub height: f32,
+    /// The size of the screen in CSS pixels.
+    pub device_width: f32,
+    pub device_height: f32,
+    /// Device pixels per CSS pixel, what `1dppx` (or `96dpi`) is.
+    pub resolution: f32,
+    /// The initial font size in pixels, what `1em` and `1rem` are.
+    pub font_size: f32,
+    /// Bits per color component, 0 on a monochrome device.
+    pub color: u32,
+    /// The number of entries in the color lookup table, 0 when there is none.
+    pub color_index: u32,
+    /// Bits per pixel of a monochrome device, 0 otherwise.
+    pub monochrome: u32,
+    pub grid: bool,
+    pub prefers_color_scheme: ColorScheme,
+    pub prefers_reduced_motion: bool,
+    /// The primary pointing device.
+    pub pointer: Pointer,
+    pub hover: bool,
+    /// The most capable of all pointing devices.
+    pub any_pointer: Pointer,
+    pub any_hover: bool,
+}
+
+impl Default for MediaEnvironment {
+    fn default() -> Self {
+        Self {
+            media_type: MediaType::Screen,
+            width: 1024.0,
+            height: 768.0,
+            device_width: 1024.0,
+            device_height: 768.0,
+            resolution: 1.0,
+            font_size: 16.0,
+            color: 8,
+            color_index: 0,
+            monochrome: 0,
+            grid: false,
+            prefers_color_scheme: ColorScheme::Light,
+            prefers_reduced_motion: false,
+            pointer: Pointer::Fine,
+            hover: true,
+            any_pointer: Pointer::Fine,
+            any_hover: true,
+        }
+    }
+}
+
+type Result<'i, T> = std::result::Result<T, ParseError<'i, Infallible>>;
+
+/// Dimension units that are resolutions rather than lengths.
+const RESOLUTION_UNITS: &[&str] = &["dpi", "dpcm", "dppx", "x"];
+
+impl MediaValue {
+    fn new(value_type: MediaValueType, number: f32) -> Self {
+        Self { value_type, number, denominator: 1.0, unit: String::new().into(), ident: String::new().into() }
+    }
+
+    fn none() -> Self {
+        Self::new(MediaValueType::None, 0.0)
+    }
+}
+
+impl MediaComparison {
+    /// The comparison with its operands swapped, `a < b` being `b > a`.
+    fn flip(self) -> Self {
+        match self {
+            Self::Less => Self::Greater,
+            Self::LessOrEqual => Self::GreaterOrEqual,
+            Self::Greater => Self::Less,
+            Self::GreaterOrEqual => Self::LessOrEqual,
+            comparison => comparison,
+        }
+    }
+
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Self::None => false,
+            Self::Equal => ordering.is_eq(),
+            Self::Less => ordering.is_lt(),
+            Self::LessOrEqual => ordering.is_le(),
+            Self::Greater => ordering.is_gt(),
+            Self::GreaterOrEqual => ordering.is_ge(),
+        }
+    }
+}
+
+/// A condition that owns its operands, before it is laid out in a [`MediaQueryList`].
+struct ConditionNode {
+    condition: MediaCondition,
+    children: Vec<ConditionNode>,
+}
+
+impl ConditionNode {
+    fn new(condition_type: MediaConditionType, name: &str, children: Vec<ConditionNode>) -> Self {
+        let condition = MediaCondition {
+            condition_type,
+            first_child: 0,
+            child_count: children.len(),
+            name: name.to_string().into(),
+            comparison: MediaComparison::None,
+            value: MediaValue::none(),
+            second_comparison: MediaComparison::None,
+            second_value: MediaValue::none(),
+            location: SourceLocation::default(),
+        };
+        Self { condition, children }
+    }
+
+    fn feature(name: &str, comparison: MediaComparison, value: MediaValue) -> Self {
+        let mut node = Self::new(MediaConditionType::Feature, name, Vec::new());
+        node.condition.comparison = comparison;
+        node.condition.value = value;
+        node
+    }
+}
+
+/// Parses a query, which must be all of `input`.
+fn parse_query<'i>(input: &mut Parser<'i, '_>) -> Result<'i, (MediaQuery, Option<ConditionNode>)> {
+    let start = input.position();
+    let start_location = input.current_source_location();
+    let mut query = MediaQuery { qualifier: MediaQualifier::None, media_type: "all".to_string().into(), has_condition: false, condition: 0, location: SourceLocation::default() };
+
+    let condition = match input.try_parse(|input| parse_condition(input, true)) {
+        Ok(condition) => Some(condition),
+        Err(_) => {
+            query.qualifier = input.try_parse(|input| {
+                match_ignore_ascii_case! { input.expect_ident().map_err(|_| ())?,
+                    "only" => Ok(MediaQualifier::Only),
+                    "not" => Ok(MediaQualifier::Not),
+                    _ => Err(()),
+                }
+            }).unwrap_or(MediaQualifier::None);
+
+            let location = input.current_source_location();
+            let media_type = input.expect_ident()?.to_ascii_lowercase();
+            if ["only", "not", "and", "or", "layer"].contains(&&*media_type) {
+                return Err(location.new_unexpected_token_error(CssToken::Ident(media_type.into())));
+            }
+            query.media_type = media_type.into();
+
+            match input.try_parse(|input| input.expect_ident_matching("and")) {
+                Ok(()) => Some(parse_condition(input, false)?),
+                Err(_) => None,
+            }
+        },
+    };
+    query.has_condition = condition.is_some();
+    query.location = SourceLocation::new(start, input.position(), start_location);
+    Ok((query, condition))
+}
+
+/// Parses a condition, `or` is only allowed when the query does not start with a media type.
+fn parse_condition<'i>(input: &mut Parser<'i, '_>, allow_or: bool) -> Result<'i, ConditionNode> {
+    input.skip_whitespace();
+    let start = input.position();
+    let start_location = input.current_source_location();
+
+    let mut node = if input.try_parse(|input| input.expect_ident_matching("not")).is_ok() {
+        ConditionNode::new(MediaConditionType::Not, "", vec![parse_in_parens(input)?])
+    } else {
+        let first = parse_in_parens(input)?;
+        let operator = input.try_parse(|input| {
+            match_ignore_ascii_case! { input.expect_ident().map_err(|_| ())?,
+                "and" => Ok((MediaConditionType::And, "and")),
+                "or" if allow_or => Ok((MediaConditionType::Or, "or")),
+                _ => Err(()),
+            }
+        });
+        let Ok((condition_type, keyword)) = operator else { return Ok(first) };
+        let mut children = vec![first, parse_in_parens(input)?];
+        // `and` and `or` cannot be mixed without parentheses
+        while input.try_parse(|input| input.expect_ident_matching(keyword)).is_ok() {
+            children.push(parse_in_parens(input)?);
+        }
+        ConditionNode::new(condition_type, "", children)
+    };
+    node.condition.location = SourceLocation::new(start, input.position(), start_location);
+    Ok(node)
+}
+
+/// Parses a condition or feature in parentheses, or anything else in parentheses or a function as an `Unknown` condition.
+fn parse_in_parens<'i>(input: &mut Parser<'i, '_>) -> Result<'i, ConditionNode> {
+    input.skip_whitespace();
+    let start = input.position();
+    let start_location = input.current_source_location();
+
+    let mut node = match input.next()? {
+        CssToken::ParenthesisBlock => input.parse_nested_block(|input| {
+            if let Ok(condition) = input.try_parse(|input| input.parse_entirely(|input| parse_condition(input, true))) {
+                return Ok(condition);
+            }
+            if let Ok(feature) = input.try_parse(|input| input.parse_entirely(parse_feature)) {
+                return Ok(feature);
+            }
+            while input.next().is_ok() {}
+            Ok(ConditionNode::new(MediaConditionType::Unknown, "", Vec::new()))
+        })?,
+        CssToken::Function(_) => {
+            input.parse_nested_block(|input| -> Result<'i, ()> {
+                while input.next().is_ok() {}
+                Ok(())
+            })?;
+            ConditionNode::new(MediaConditionType::Unknown, "", Vec::new())
+        },
+        token => {
+            let token = token.clone();
+            return Err(start_location.new_unexpected_token_error(token));
+        },
+    };
+    if node.condition.condition_type == MediaConditionType::Unknown {
+        node.condition.name = input.slice_from(start).to_string().into();
+    }
+    node.condition.location = SourceLocation::new(start, input.position(), start_location);
+    Ok(node)
+}
+
+/// Parses the inside of `(name)`, `(name: value)`, `(name < value)`, `(value < name)` or `(value < name < value)`.
+fn parse_feature<'i>(input: &mut Parser<'i, '_>) -> Result<'i, ConditionNode> {
+    let location = input.current_source_location();
+    if let Ok(name) = input.try_parse(|input| input.expect_ident().map(|name| name.to_ascii_lowercase())) {
+        let prefixed = name.starts_with("min-") || name.starts_with("max-");
+        if input.is_exhausted() && !prefixed {
+            return Ok(ConditionNode::feature(&name, MediaComparison::None, MediaValue::none()));
+        }
+        if input.try_parse(|input| input.expect_colon()).is_ok() {
+            let value = parse_value(input)?;
+            let (name, comparison) = match name.split_at(name.len().min(4)) {
+                ("min-", name) => (name, MediaComparison::GreaterOrEqual),
+                ("max-", name) => (name, MediaComparison::LessOrEqual),
+                _ => (&*name, MediaComparison::Equal),
+            };
+            return Ok(ConditionNode::feature(name, comparison, value));
+        }
+        if prefixed {
+            // `min-` and `max-` only go with a colon
+            return Err(location.new_unexpected_token_error(CssToken::Ident(name.into())));
+        }
+        let comparison = parse_comparison(input)?;
+        return Ok(ConditionNode::feature(&name, comparison, parse_value(input)?));
+    }
+
+    let value = parse_value(input)?;
+    let comparison = parse_comparison(input)?;
+    let name = input.expect_ident()?.to_ascii_lowercase();
+    let mut node = ConditionNode::feature(&name, comparison.flip(), value);
+    if !input.is_exhausted() {
+        let location = input.current_source_location();
+        let state = input.state();
+        let second = parse_comparison(input)?;
+        // both bounds must go the same way, `a < width > b` is invalid
+        let same_direction = matches!(
+            (comparison, second),
+            (MediaComparison::Less | MediaComparison::LessOrEqual, MediaComparison::Less | MediaComparison::LessOrEqual)
+                | (MediaComparison::Greater | MediaComparison::GreaterOrEqual, MediaComparison::Greater | MediaComparison::GreaterOrEqual)
+        );
+        if !same_direction {
+            input.reset(&state);
+            let token = input.next()?.clone();
+            return Err(location.new_unexpected_token_error(token));
+        }
+        node.condition.second_comparison = second;
+        node.condition.second_value = parse_value(input)?;
+    }
+    Ok(node)
+}
+
+/// Parses `=`, `<`, `<=`, `>` or `>=`.
+fn parse_comparison<'i>(input: &mut Parser<'i, '_>) -> Result<'i, MediaComparison> {
+    let location = input.current_source_location();
+    let (strict, or_equal) = match input.next()? {
+        CssToken::Delim('=') => return Ok(MediaComparison::Equal),
+        CssToken::Delim('<') => (MediaComparison::Less, MediaComparison::LessOrEqual),
+        CssToken::Delim('>') => (MediaComparison::Greater, MediaComparison::GreaterOrEqual),
+        token => {
+            let token = token.clone();
+            return Err(location.new_unexpected_token_error(token));
+        },
+    };
+    // `<=` is two tokens, which cannot be separated by whitespace
+    match input.try_parse(|input| match input.next_including_whitespace() {
+        Ok(CssToken::Delim('=')) => Ok(()),
+        _ => Err(()),
+    }) {
+        Ok(()) => Ok(or_equal),
+        Err(()) => Ok(strict),
+    }
+}
+
+fn parse_value<'i>(input: &mut Parser<'i, '_>) -> Result<'i, MediaValue> {
+    let location = input.current_source_location();
+    match input.next()?.clone() {
+        CssToken::Number { value, .. } => match input.try_parse(|input| input.expect_delim('/')) {
+            Ok(()) => Ok(MediaValue { denominator: input.expect_number()?, ..MediaValue::new(MediaValueType::Ratio, value) }),
+            Err(_) => Ok(MediaValue::new(MediaValueType::Number, value)),
+        },
+        CssToken::Dimension { value, unit, .. } => {
+            let unit = unit.to_ascii_lowercase();
+            let value_type = match RESOLUTION_UNITS.contains(&&*unit) {
+                true => MediaValueType::Resolution,
+                false => MediaValueType::Length,
+            };
+            Ok(MediaValue { unit: unit.into(), ..MediaValue::new(value_type, value) })
+        },
+        CssToken::Ident(ident) => Ok(MediaValue { ident: ident.to_ascii_lowercase().into(), ..MediaValue::new(MediaValueType::Ident, 0.0) }),
+        token => Err(location.new_unexpected_token_error(token)),
+    }
+}
+
+impl MediaQueryList {
+    /// Lays the conditions out breadth first, like a `TokenTree`, the ones of the queries coming first.
+    fn new(queries: Vec<(MediaQuery, Option<ConditionNode>)>, errors: Vec<Error>) -> Self {
+        let mut roots = Vec::new();
+        let queries = queries.into_iter().map(|(mut query, condition)| {
+            if let Some(condition) = condition {
+                query.condition = roots.len();
+                roots.push(condition);
+            }
+            query
+        }).collect::<Vec<_>>();
+        let conditions = breadth_first(roots, |node| std::mem::take(&mut node.children))
+            .into_iter()
+            .map(|(node, first_child, child_count)| MediaCondition { first_child, child_count, ..node.condition })
+            .collect::<Vec<_>>();
+        Self { queries: queries.into(), conditions: conditions.into(), errors: errors.into() }
+    }
+
+    /// Whether one of the queries matches `environment`, an empty list always does.
+    pub fn matches(&self, environment: &MediaEnvironment) -> bool {
+        self.queries.is_empty() || (0..self.queries.len()).any(|index| self.query_matches(index, environment))
+    }
+
+    /// Whether the query at `index` matches `environment`, a condition that cannot be evaluated does not.
+    pub fn query_matches(&self, index: usize, environment: &MediaEnvironment) -> bool {
+        let Some(query) = self.queries.get(index) else { return false };
+        let media_type = match &*query.media_type {
+            "all" => Some(true),
+            "screen" => Some(environment.media_type == MediaType::Screen),
+            "print" => Some(environment.media_type == MediaType::Print),
+            // the deprecated types, such as `tv`, match nothing
+            _ => Some(false),
+        };
+        let condition = match query.has_condition {
+            true => self.evaluate(query.condition, environment),
+            false => Some(true),
+        };
+        let result = all([media_type, condition]);
+        match query.qualifier {
+            MediaQualifier::Not => result.map(|result| !result),
+            _ => result,
+        }
+        .unwrap_or(false)
+    }
+
+    /// Evaluates the condition at `index`, `None` meaning unknown.
+    fn evaluate(&self, index: usize, environment: &MediaEnvironment) -> Option<bool> {
+        let condition = &self.conditions[index];
+        let mut children = (condition.first_child..condition.first_child + condition.child_count).map(|child| self.evaluate(child, environment));
+        match condition.condition_type {
+            MediaConditionType::Feature => evaluate_feature(condition, environment),
+            MediaConditionType::Not => children.next().flatten().map(|result| !result),
+            MediaConditionType::And => all(children),
+            MediaConditionType::Or => all(children.map(|child| child.map(|result| !result))).map(|result| !result),
+            MediaConditionType::Unknown => None,
+        }
+    }
+}
+
+/// Three-valued `and`: false as soon as one result is, unknown if one is and none is false.
+fn all(results: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    let mut all = Some(true);
+    for result in results {
+        match result {
+            Some(false) => return Some(false),
+            None => all = None,
+            Some(true) => {},
+        }
+    }
+    all
+}
+
+/// The value of a media feature in the environment.
+enum Feature {
+    /// In pixels.
+    Length(f64),
+    Ratio(f64),
+    /// In `dppx`.
+    Resolution(f64),
+    Integer(f64),
+    Keyword(&'static str),
+}
+
+impl Feature {
+    fn get(name: &str, environment: &MediaEnvironment) -> Option<Self> {
+        let pointer = |pointer| match pointer {
+            Pointer::None => "none",
+            Pointer::Coarse => "coarse",
+            Pointer::Fine => "fine",
+        };
+        let hover = |hover| if hover { "hover" } else { "none" };
+        let ratio = |width: f32, height: f32| f64::from(width) / f64::from(height);
+        let feature = match name {
+            "width" => Self::Length(environment.width.into()),
+            "height" => Self::Length(environment.height.into()),
+            "device-width" => Self::Length(environment.device_width.into()),
+            "device-height" => Self::Length(environment.device_height.into()),
+            "aspect-ratio" => Self::Ratio(ratio(environment.width, environment.height)),
+            "device-aspect-ratio" => Self::Ratio(ratio(environment.device_width, environment.device_height)),
+            "orientation" => Self::Keyword(if environment.height >= environment.width { "portrait" } else { "landscape" }),
+            "resolution" => Self::Resolution(environment.resolution.into()),
+            "color" => Self::Integer(environment.color.into()),
+            "color-index" => Self::Integer(environment.color_index.into()),
+            "monochrome" => Self::Integer(environment.monochrome.into()),
+            "grid" => Self::Integer(u8::from(environment.grid).into()),
+            "prefers-color-scheme" => Self::Keyword(match environment.prefers_color_scheme {
+                ColorScheme::Light => "light",
+                ColorScheme::Dark => "dark",
+            }),
+            "prefers-reduced-motion" => Self::Keyword(if environment.prefers_reduced_motion { "reduce" } else { "no-preference" }),
+            "pointer" => Self::Keyword(pointer(environment.pointer)),
+            "any-pointer" => Self::Keyword(pointer(environment.any_pointer)),
+            "hover" => Self::Keyword(hover(environment.hover)),
+            "any-hover" => Self::Keyword(hover(environment.any_hover)),
+            _ => return None,
+        };
+        Some(feature)
+    }
+
+    /// Whether the feature is true on its own, as in `(color)` or `(hover)`.
+    fn is_true(&self) -> bool {
+        match *self {
+            Self::Length(value) | Self::Ratio(value) | Self::Resolution(value) | Self::Integer(value) => value != 0.0,
+            Self::Keyword(keyword) => keyword != "none" && keyword != "no-preference",
+        }
+    }
+
+    /// How the feature compares with `value`, `None` when they cannot be compared.
+    fn compare(&self, value: &MediaValue, environment: &MediaEnvironment) -> Option<Ordering> {
+        let number = f64::from(value.number);
+        let other = match (self, value.value_type) {
+            (Self::Length(_), MediaValueType::Length) => number * length_unit(&value.unit, &CalcContext::new(environment.font_size, environment.width, environment.height))?,
+            // only 0 can be written without a unit
+            (Self::Length(_), MediaValueType::Number) if number == 0.0 => 0.0,
+            (Self::Ratio(_), MediaValueType::Ratio) => number / f64::from(value.denominator),
+            (Self::Ratio(_), MediaValueType::Number) => number,
+            (Self::Resolution(_), MediaValueType::Resolution) => number * resolution_unit(&value.unit)?,
+            (Self::Integer(_), MediaValueType::Number) => number,
+            _ => return None,
+        };
+        match *self {
+            Self::Length(feature) | Self::Ratio(feature) | Self::Resolution(feature) | Self::Integer(feature) => feature.partial_cmp(&other),
+            Self::Keyword(_) => None,
+        }
+    }
+
+    /// Whether the feature compares with `value` as `comparison` says, `None` when they cannot be compared.
+    fn holds(&self, comparison: MediaComparison, value: &MediaValue, environment: &MediaEnvironment) -> Option<bool> {
+        match (self, comparison, value.value_type) {
+            // keywords can only be equal or not
+            (Self::Keyword(keyword), MediaComparison::Equal, MediaValueType::Ident) => Some(*keyword == &*value.ident),
+            (Self::Keyword(_), ..) => None,
+            _ => self.compare(value, environment).map(|ordering| comparison.holds(ordering)),
+        }
+    }
+}
+
+/// How many `dppx` one `unit` is.
+fn resolution_unit(unit: &str) -> Option<f64> {
+    match unit {
+        "dppx" | "x" => Some(1.0),
+        "dpi" => Some(1.0 / 96.0),
+        "dpcm" => Some(2.54 / 96.0),
+        _ => None,
+    }
+}
+
+fn evaluate_feature(condition: &MediaCondition, environment: &MediaEnvironment) -> Option<bool> {
+    let feature = Feature::get(&condition.name, environment)?;
+    if condition.comparison == MediaComparison::None {
+        return Some(feature.is_true());
+    }
+    let second = match condition.second_comparison {
+        MediaComparison::None => Some(true),
+        comparison => feature.holds(comparison, &condition.second_value, environment),
+    };
+    all([feature.holds(condition.comparison, &condition.value, environment), second])
+}
+
+/// Parses `input` as a comma-separated media query list, such as the prelude of `@media`.
+///
+/// A query that cannot be parsed does not invalidate the list, it is kept as `not all` and its error is recorded.
+pub fn media_query_list(input: &str) -> MediaQueryList {
+    let mut parser_input = cssparser::ParserInput::new(input);
+    let mut parser = Parser::new(&mut parser_input);
+    let mut queries = Vec::new();
+    let mut errors = Vec::new();
+    if parser.is_exhausted() {
+        return MediaQueryList::new(queries, errors);
+    }
+    loop {
+        parser.skip_whitespace();
+        let start = parser.position();
+        let start_location = parser.current_source_location();
+        match parser.parse_until_before(Delimiter::Comma, |input| input.parse_entirely(parse_query)) {
+            Ok(query) => queries.push(query),
+            Err(error) => {
+                let end = parser.position();
+                let error = skipped_item_error(&mut parser, &error, start, start_location, end);
+                let not_all = MediaQuery { qualifier: MediaQualifier::Not, media_type: "all".to_string().into(), has_condition: false, condition: 0, location: error.location };
+                queries.push((not_all, None));
+                errors.push(error);
+            },
+        }
+        // the comma
+        if parser.next().is_err() {
+            break;
+        }
+    }
+    MediaQueryList::new(queries, errors)
+}
+
+/// Like [`media_query_list`], for tokens returned by `parse_css` (such as the `prelude` of a `Rule_t`).
+///
+/// The locations are the ones of the tokens in the input they came from.
+pub fn media_query_list_tokens(tokens: &[Token]) -> MediaQueryList {
+    let reserialized = Reserialized::new(tokens);
+    let mut list = media_query_list(&reserialized.css);
+    for query in list.queries.iter_mut() {
+        query.location = reserialized.relocate(query.location);
+    }
+    for condition in list.conditions.iter_mut() {
+        condition.location = reserialized.relocate(condition.location);
+    }
+    for error in list.errors.iter_mut() {
+        error.location = reserialized.relocate(error.location);
+    }
+    list
+}
+
+fn empty_list() -> MediaQueryList {
+    MediaQueryList::new(Vec::new(), Vec::new())
+}
+
+/// Parses the NUL-terminated string `input` (such as `screen and (min-width: 40em), print`) into the queries written to `list`.
+///
+/// Invalid queries do not make the call fail, they are kept as `not all` and listed in `list.errors`.
+/// Out-params otherwise behave like they do for `parse_css`, `list` must be released with `free_media_query_list`.
+#[ffi_export]
+pub fn parse_media_query_list(input: *const safer_ffi::c_char, list: Out<'_, MediaQueryList>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        Ok(media_query_list(input_str(input)?))
+    });
+
+    finish(result, list, empty_list, error)
+}
+
+/// Like `parse_media_query_list`, for tokens returned by `parse_css` (such as the `prelude` of an `@media` `Rule_t`).
+///
+/// Locations are the ones of the tokens, in the input they came from.
+#[ffi_export]
+pub fn parse_media_query_list_tokens(tokens: c_slice::Ref<'_, Token>, list: Out<'_, MediaQueryList>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        Ok(media_query_list_tokens(tokens.as_slice()))
+    });
+
+    finish(result, list, empty_list, error)
+}
+
+#[ffi_export]
+pub fn media_environment_default() -> MediaEnvironment {
+    MediaEnvironment::default()
+}
+
+/// Whether one of the queries of `list` matches `environment`, an empty list always does.
+#[ffi_export]
+pub fn media_query_list_matches(list: &MediaQueryList, environment: &MediaEnvironment) -> bool {
+    catch_panic(|| Ok(list.matches(environment))).unwrap_or(false)
+}
+
+/// Whether the query `list.queries.ptr[index]` matches `environment`.
+///
+/// Features the library does not know and conditions that compare values of the wrong type never match.
+#[ffi_export]
+pub fn media_query_matches(list: &MediaQueryList, index: usize, environment: &MediaEnvironment) -> bool {
+    catch_panic(|| Ok(list.query_matches(index, environment))).unwrap_or(false)
+}
+
+#[ffi_export]
+pub fn free_media_query_list(list: MediaQueryList) {
+    drop(list);
+}
@@ -0,0 +1,167 @@
+#![allow(nonstandard_style)]
+use std::convert::Infallible;
+
+use cssparser::{ToCss, TokenSerializationType};
+use safer_ffi::{ffi_export, prelude::Out};
+
+use crate::{cstr, error::{catch_panic, finish, Error, Status}, input_str, walk_including_whitespace};
+
+/// Units `0` can stand in for, as every zero length is the same length.
+const LENGTH_UNITS: &[&str] = &[
+    "px", "em", "rem", "ex", "ch", "vw", "vh", "vmin", "vmax", "cm", "mm", "q", "in", "pt", "pc",
+];
+
+/// Writes tokens back out with as little text as possible.
+///
+/// Comments are dropped, whitespace is collapsed to a single space and then removed entirely
+/// next to tokens that can never need it (`{`, `}`, `;`, `,`, ...), numbers are written in their shortest form
+/// and the last semicolon of a block is left out.
+#[derive(Default)]
+struct Minifier {
+    css: String,
+    /// How the last written token serializes, to know when two tokens need a separator.
+    previous: TokenSerializationType,
+    /// Whether whitespace after the last written token can always be left out.
+    previous_is_open: bool,
+    /// Whitespace was found since the last written token.
+    space: bool,
+    /// A semicolon was found since the last written token, it is only written once another token follows.
+    semicolon: bool,
+    /// The blocks we are in, `true` for functions.
+    blocks: Vec<bool>,
+    /// The last written token is an identifier starting with `--`.
+    previous_is_custom_name: bool,
+    /// The depth of `blocks` at which the value of a custom property (`--x: 0px`) is being written.
+    /// Units are kept there, the value may end up in a `calc()` through `var()`.
+    custom_value: Option<usize>,
+}
+
+impl Minifier {
+    fn push(&mut self, token: &cssparser::Token) {
+        match token {
+            cssparser::Token::WhiteSpace(_) => {
+                self.space = true;
+                return;
+            },
+            // unlike whitespace comments separate nothing (`a/**/.b` is `a.b`), so they go without a trace
+            cssparser::Token::Comment(_) => return,
+            cssparser::Token::Semicolon => {
+                if self.custom_value == Some(self.blocks.len()) {
+                    self.custom_value = None;
+                }
+                self.semicolon = true;
+                self.space = false;
+                return;
+            },
+            // the last declaration of a block does not need its semicolon
+            cssparser::Token::CloseCurlyBracket => self.semicolon = false,
+            _ => {},
+        }
+
+        if std::mem::take(&mut self.semicolon) {
+            self.write(&cssparser::Token::Semicolon);
+        }
+
+        let needs_space = self.previous.needs_separator_when_before(token.serialization_type())
+            || (std::mem::take(&mut self.space) && !self.previous_is_open && !closes_gap(token));
+        if needs_space {
+            self.css.push(' ');
+        }
+        let after_custom_name = self.previous_is_custom_name;
+        self.write(token);
+
+        match token {
+            cssparser::Token::Function(_) => self.blocks.push(true),
+            cssparser::Token::ParenthesisBlock | cssparser::Token::SquareBracketBlock | cssparser::Token::CurlyBracketBlock => self.blocks.push(false),
+            cssparser::Token::CloseParenthesis | cssparser::Token::CloseSquareBracket | cssparser::Token::CloseCurlyBracket => {
+                self.blocks.pop();
+                if self.custom_value.is_some_and(|depth| self.blocks.len() < depth) {
+                    self.custom_value = None;
+                }
+            },
+            cssparser::Token::Colon if after_custom_name => self.custom_value = Some(self.blocks.len()),
+            _ => {},
+        }
+    }
+
+    fn write(&mut self, token: &cssparser::Token) {
+        let in_function = self.blocks.contains(&true);
+        match token {
+            // `calc(0px + 1px)` is valid but `calc(0 + 1px)` is not, so units are only dropped outside of functions
+            // and of custom properties
+            cssparser::Token::Dimension { value, unit, .. } if *value == 0.0 && !in_function && self.custom_value.is_none() && LENGTH_UNITS.contains(&&*unit.to_ascii_lowercase()) => {
+                self.css.push('0');
+            },
+            cssparser::Token::Number { .. } | cssparser::Token::Dimension { .. } | cssparser::Token::Percentage { .. } => {
+                let number = token.to_css_string();
+                // `0.5` → `.5`, keeping the sign
+                let (sign, digits) = match number.strip_prefix(['-', '+']) {
+                    Some(digits) => (&number[..1], digits),
+                    None => ("", &number[..]),
+                };
+                self.css.push_str(sign);
+                self.css.push_str(digits.strip_prefix("0.").map_or(digits, |_| &digits[1..]));
+            },
+            _ => {
+                // writing to a `String` cannot fail
+                let _ = token.to_css(&mut self.css);
+            },
+        }
+        self.previous = token.serialization_type();
+        self.previous_is_open = opens_gap(token);
+        self.previous_is_custom_name = matches!(token, cssparser::Token::Ident(name) if name.starts_with("--"));
+    }
+
+    fn finish(mut self) -> String {
+        // a semicolon at the very end is kept, it may end an `@import` or `@charset`
+        if self.semicolon {
+            self.css.push(';');
+        }
+        self.css
+    }
+}
+
+/// Whitespace after these tokens is never needed.
+fn opens_gap(token: &cssparser::Token) -> bool {
+    matches!(
+        token,
+        cssparser::Token::CurlyBracketBlock | cssparser::Token::CloseCurlyBracket
+        | cssparser::Token::ParenthesisBlock | cssparser::Token::SquareBracketBlock | cssparser::Token::Function(_)
+        | cssparser::Token::Semicolon | cssparser::Token::Comma | cssparser::Token::Colon
+    )
+}
+
+/// Whitespace before these tokens is never needed.
+fn closes_gap(token: &cssparser::Token) -> bool {
+    matches!(
+        token,
+        cssparser::Token::CurlyBracketBlock | cssparser::Token::CloseCurlyBracket
+        | cssparser::Token::CloseParenthesis | cssparser::Token::CloseSquareBracket
+        | cssparser::Token::Semicolon | cssparser::Token::Comma
+    )
+}
+
+/// Minifies `input`, tokenizing it the same way `parse_css` does.
+pub fn minify(input: &str) -> Result<String, Error> {
+    let mut minifier = Minifier { previous_is_open: true, ..Default::default() };
+
+    let mut input = cssparser::ParserInput::new(input);
+    let mut parser = cssparser::Parser::new(&mut input);
+    walk_including_whitespace::<Infallible>(&mut parser, &mut |token, _| minifier.push(token))
+        .map_err(|e| Error::from_parse_error(&e, parser.position()))?;
+
+    Ok(minifier.finish())
+}
+
+/// Minifies the NUL-terminated string `input`, writing the result to `css`.
+///
+/// `css` is always written to and must be released with `free_string`,
+/// `error` behaves like it does for `parse_css`.
+#[ffi_export]
+pub fn minify_css(input: *const safer_ffi::c_char, css: Out<'_, cstr>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        minify(input_str(input)?).map(Into::into)
+    });
+
+    finish(result, css, || String::new().into(), error)
+}
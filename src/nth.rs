@@ -0,0 +1,74 @@
+#![allow(nonstandard_style)]
+use std::convert::Infallible;
+
+use cssparser::{ParseError, Parser};
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{c_slice, Out}};
+
+use crate::{error::{catch_panic, finish, Error, Status}, input_str, serialize::parse_tokens, Token};
+
+/// An `An+B` value, such as the argument of `:nth-child()`.
+///
+/// `odd` is `2n+1`, `even` is `2n+0` and a plain number `B` is `0n+B`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Nth {
+    pub a: i32,
+    pub b: i32,
+}
+
+impl Nth {
+    /// Whether the 1-based `index` is `a×n + b` for some integer `n ≥ 0`.
+    pub fn matches(&self, index: i32) -> bool {
+        // in 64 bits so that `index - b` cannot overflow
+        let (a, b, index) = (i64::from(self.a), i64::from(self.b), i64::from(index));
+        match a {
+            0 => index == b,
+            _ => (index - b) % a == 0 && (index - b) / a >= 0,
+        }
+    }
+}
+
+/// Parses an `An+B` value from `input`.
+pub fn parse_nth_value<'i>(input: &mut Parser<'i, '_>) -> Result<Nth, ParseError<'i, Infallible>> {
+    let (a, b) = cssparser::parse_nth(input)?;
+    Ok(Nth { a, b })
+}
+
+/// Parses `input` as `An+B`, surrounded by optional whitespace.
+pub fn nth(input: &str) -> Result<Nth, Error> {
+    let mut parser_input = cssparser::ParserInput::new(input);
+    let mut parser = Parser::new(&mut parser_input);
+    parser.parse_entirely(parse_nth_value)
+        .map_err(|e| Error::from_parse_error(&e, parser.position()))
+}
+
+/// Parses the NUL-terminated string `input` (such as `2n+1`, `-n + 3` or `odd`) into `nth`.
+///
+/// Out-params behave like they do for `parse_css`, on failure `nth` is `0n+0`.
+#[ffi_export]
+pub fn parse_nth(input: *const safer_ffi::c_char, nth: Out<'_, Nth>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        self::nth(input_str(input)?)
+    });
+
+    finish(result, nth, Nth::default, error)
+}
+
+/// Like `parse_nth`, for tokens returned by `parse_css` (such as the `arguments` of a `SelectorComponent_t`).
+///
+/// The error location is the one of the token the error occurred at.
+#[ffi_export]
+pub fn parse_nth_tokens(tokens: c_slice::Ref<'_, Token>, nth: Out<'_, Nth>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        parse_tokens(tokens.as_slice(), parse_nth_value)
+    });
+
+    finish(result, nth, Nth::default, error)
+}
+
+/// Whether the 1-based `index` (such as the position of an element among its siblings) is selected by `nth`.
+#[ffi_export]
+pub fn nth_matches(nth: Nth, index: i32) -> bool {
+    nth.matches(index)
+}
@@ -0,0 +1,436 @@
+#![allow(nonstandard_style)]
+use std::convert::Infallible;
+
+use cssparser::{ParseError, Parser};
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{repr_c, Out}};
+
+use crate::{cstr, error::{catch_panic, finish, Error, Status}, input_str, layout::breadth_first, location::SourceLocation, stylesheet::component_tokens, Token};
+
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentType {
+    /// A type selector such as `div`, `name` is the element name.
+    Type,
+    /// `*`
+    Universal,
+    /// The `&` of a nested rule, it does not know its parent so its specificity is 0.
+    Nesting,
+    /// `#name`
+    Id,
+    /// `.name`
+    Class,
+    /// `[name]`, or `[name <operator> value]` when `attribute_operator` is not `Exists`.
+    Attribute,
+    /// `:name` or `:name(...)`.
+    PseudoClass,
+    /// `::name` or `::name(...)`, as well as the legacy `:before`, `:after`, `:first-line` and `:first-letter`.
+    PseudoElement,
+    /// Separates two compound selectors, see `combinator`.
+    Combinator,
+}
+
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combinator {
+    /// The component is not a combinator.
+    None,
+    /// Whitespace, `a b`
+    Descendant,
+    /// `a > b`
+    Child,
+    /// `a + b`
+    NextSibling,
+    /// `a ~ b`
+    SubsequentSibling,
+}
+
+/// How an attribute selector compares the attribute's value, named after the matching token types.
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeOperator {
+    /// `[name]`
+    Exists,
+    /// `[name=value]`
+    Equal,
+    /// `[name~=value]`, one of the whitespace-separated words is `value`.
+    IncludeMatch,
+    /// `[name|=value]`, the value is `value` or starts with `value-`.
+    DashMatch,
+    /// `[name^=value]`
+    PrefixMatch,
+    /// `[name$=value]`
+    SuffixMatch,
+    /// `[name*=value]`
+    SubstringMatch,
+}
+
+/// The specificity of a selector, compared `a` first, then `b`, then `c`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Specificity {
+    /// The number of id selectors.
+    pub a: u32,
+    /// The number of class, attribute and pseudo-class selectors.
+    pub b: u32,
+    /// The number of type and pseudo-element selectors.
+    pub c: u32,
+}
+
+impl std::ops::Add for Specificity {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self { a: self.a + other.a, b: self.b + other.b, c: self.c + other.c }
+    }
+}
+
+/// One simple selector or combinator of a [`Selector`].
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct SelectorComponent {
+    pub component_type: ComponentType,
+    /// The element, id, class, attribute or pseudo-class/element name, empty for the other types.
+    pub name: cstr,
+    /// Which combinator a `Combinator` is, `None` for every other type.
+    pub combinator: Combinator,
+    /// How an attribute is compared with `value`, `Exists` unless the component is `[name <op> value]`.
+    pub attribute_operator: AttributeOperator,
+    /// The value an attribute is compared with.
+    pub value: cstr,
+    /// The attribute selector ends with the `i` flag (`[type="a" i]`).
+    pub case_insensitive: bool,
+    /// The pseudo-class or pseudo-element is a function (`:lang(en)`), even when it has no arguments.
+    pub is_function: bool,
+    /// The arguments of a function that takes neither selectors nor `An+B`, such as `:lang(en)`.
+    pub arguments: repr_c::Vec<Token>,
+    /// The selectors a function takes (`:not(a, b)`, or the `of S` part of `:nth-child(2n of S)`),
+    /// they are `list.selectors.ptr[first_selector .. first_selector + selector_count]`.
+    pub first_selector: usize,
+    pub selector_count: usize,
+    /// The `An+B` of `:nth-child` and the other `:nth-*` pseudo-classes.
+    pub has_nth: bool,
+    pub nth_a: i32,
+    pub nth_b: i32,
+}
+
+/// A complex selector, its components are in source order.
+///
+/// Compound selectors are separated by `Combinator` components,
+/// a relative selector in `:has()` starts with one.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct Selector {
+    pub components: repr_c::Vec<SelectorComponent>,
+    pub specificity: Specificity,
+    pub location: SourceLocation,
+}
+
+/// A parsed selector list, free it with `free_selector_list`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct SelectorList {
+    /// Every selector, the selectors of the list itself are `selectors.ptr[0 .. roots]`,
+    /// the ones after them are the arguments of functional pseudo-classes.
+    pub selectors: repr_c::Vec<Selector>,
+    pub roots: usize,
+}
+
+/// A selector that owns the selectors of its functional pseudo-classes, before it is laid out in a [`SelectorList`].
+#[derive(Debug)]
+pub struct SelectorNode {
+    pub components: Vec<(SelectorComponent, Vec<SelectorNode>)>,
+    pub specificity: Specificity,
+    pub location: SourceLocation,
+}
+
+/// Pseudo-classes whose argument is a selector list.
+const SELECTOR_LIST_PSEUDO_CLASSES: &[&str] = &["not", "is", "where", "matches", "-webkit-any", "-moz-any", "has", "host", "host-context"];
+
+/// Pseudo-classes whose argument is `An+B`, only the first two can be followed by `of <selector list>`.
+const NTH_PSEUDO_CLASSES: &[&str] = &["nth-child", "nth-last-child", "nth-of-type", "nth-last-of-type", "nth-col", "nth-last-col"];
+
+/// Pseudo-elements that CSS 2 allows to be written with a single colon.
+const LEGACY_PSEUDO_ELEMENTS: &[&str] = &["before", "after", "first-line", "first-letter"];
+
+type Result<'i, T> = std::result::Result<T, ParseError<'i, Infallible>>;
+
+impl SelectorComponent {
+    fn new(component_type: ComponentType, name: &str) -> Self {
+        Self {
+            component_type,
+            name: name.to_string().into(),
+            combinator: Combinator::None,
+            attribute_operator: AttributeOperator::Exists,
+            value: String::new().into(),
+            case_insensitive: false,
+            is_function: false,
+            arguments: Vec::new().into(),
+            first_selector: 0,
+            selector_count: 0,
+            has_nth: false,
+            nth_a: 0,
+            nth_b: 0,
+        }
+    }
+
+    fn combinator(combinator: Combinator) -> Self {
+        Self { combinator, ..Self::new(ComponentType::Combinator, "") }
+    }
+
+    /// How much the component adds to the specificity of its selector, `selectors` being its arguments.
+    fn specificity(&self, selectors: &[SelectorNode]) -> Specificity {
+        let most_specific = selectors.iter().map(|selector| selector.specificity).max().unwrap_or_default();
+        let name = self.name.to_string().to_ascii_lowercase();
+        match self.component_type {
+            ComponentType::Id => Specificity { a: 1, b: 0, c: 0 },
+            ComponentType::Class | ComponentType::Attribute => Specificity { a: 0, b: 1, c: 0 },
+            ComponentType::Type | ComponentType::PseudoElement => Specificity { a: 0, b: 0, c: 1 },
+            ComponentType::PseudoClass => match &*name {
+                "where" => Specificity::default(),
+                // these are replaced by their most specific argument
+                "not" | "is" | "matches" | "-webkit-any" | "-moz-any" | "has" => most_specific,
+                _ => Specificity { a: 0, b: 1, c: 0 } + most_specific,
+            },
+            ComponentType::Universal | ComponentType::Nesting | ComponentType::Combinator => Specificity::default(),
+        }
+    }
+}
+
+/// Parses a comma-separated list of selectors, `relative` ones may start with a combinator (as in `:has(> a)`).
+pub fn parse_selectors<'i>(input: &mut Parser<'i, '_>, relative: bool) -> Result<'i, Vec<SelectorNode>> {
+    input.parse_comma_separated(|input| parse_selector(input, relative))
+}
+
+fn parse_selector<'i>(input: &mut Parser<'i, '_>, relative: bool) -> Result<'i, SelectorNode> {
+    input.skip_whitespace();
+    let start = input.position();
+    let start_location = input.current_source_location();
+    let mut components = Vec::new();
+
+    if relative {
+        if let Ok(combinator) = input.try_parse(parse_explicit_combinator) {
+            components.push((SelectorComponent::combinator(combinator), Vec::new()));
+        }
+    }
+    loop {
+        if !parse_compound(input, &mut components)? {
+            return Err(input.new_error_for_next_token());
+        }
+        match parse_combinator(input)? {
+            Some(combinator) => components.push((SelectorComponent::combinator(combinator), Vec::new())),
+            None => break,
+        }
+    }
+
+    let specificity = components.iter().fold(Specificity::default(), |total, (component, selectors)| total + component.specificity(selectors));
+    Ok(SelectorNode { components, specificity, location: SourceLocation::new(start, input.position(), start_location) })
+}
+
+fn parse_explicit_combinator<'i>(input: &mut Parser<'i, '_>) -> Result<'i, Combinator> {
+    let location = input.current_source_location();
+    let combinator = match input.next()? {
+        cssparser::Token::Delim('>') => Combinator::Child,
+        cssparser::Token::Delim('+') => Combinator::NextSibling,
+        cssparser::Token::Delim('~') => Combinator::SubsequentSibling,
+        token => return Err(location.new_unexpected_token_error(token.clone())),
+    };
+    input.skip_whitespace();
+    Ok(combinator)
+}
+
+/// Parses the combinator after a compound selector, `None` at the end of the selector.
+fn parse_combinator<'i>(input: &mut Parser<'i, '_>) -> Result<'i, Option<Combinator>> {
+    let before_whitespace = input.state();
+    input.skip_whitespace();
+    if input.is_exhausted() {
+        // leave the trailing whitespace out of the selector's location
+        input.reset(&before_whitespace);
+        return Ok(None);
+    }
+    if let Ok(combinator) = input.try_parse(parse_explicit_combinator) {
+        return Ok(Some(combinator));
+    }
+    if input.position() == before_whitespace.position() {
+        return Err(input.new_error_for_next_token());
+    }
+    Ok(Some(Combinator::Descendant))
+}
+
+/// Parses a compound selector into `components`, returns whether there was one at all.
+fn parse_compound<'i>(input: &mut Parser<'i, '_>, components: &mut Vec<(SelectorComponent, Vec<SelectorNode>)>) -> Result<'i, bool> {
+    let first = components.len();
+    let state = input.state();
+    match input.next_including_whitespace().cloned() {
+        Ok(cssparser::Token::Ident(name)) => components.push((SelectorComponent::new(ComponentType::Type, &name), Vec::new())),
+        Ok(cssparser::Token::Delim('*')) => components.push((SelectorComponent::new(ComponentType::Universal, ""), Vec::new())),
+        _ => input.reset(&state),
+    }
+
+    loop {
+        let state = input.state();
+        let location = input.current_source_location();
+        let component = match input.next_including_whitespace().cloned() {
+            Ok(cssparser::Token::IDHash(id)) => (SelectorComponent::new(ComponentType::Id, &id), Vec::new()),
+            Ok(cssparser::Token::Delim('&')) => (SelectorComponent::new(ComponentType::Nesting, ""), Vec::new()),
+            Ok(cssparser::Token::Delim('.')) => {
+                let location = input.current_source_location();
+                match input.next_including_whitespace()? {
+                    cssparser::Token::Ident(class) => (SelectorComponent::new(ComponentType::Class, class), Vec::new()),
+                    token => return Err(location.new_unexpected_token_error(token.clone())),
+                }
+            },
+            Ok(cssparser::Token::SquareBracketBlock) => (input.parse_nested_block(parse_attribute)?, Vec::new()),
+            Ok(cssparser::Token::Colon) => parse_pseudo(input)?,
+            Ok(token @ (cssparser::Token::Ident(_) | cssparser::Token::Delim('*'))) => {
+                // a type selector can only come first
+                return Err(location.new_unexpected_token_error(token));
+            },
+            _ => {
+                input.reset(&state);
+                break;
+            },
+        };
+        components.push(component);
+    }
+    Ok(components.len() > first)
+}
+
+fn parse_attribute<'i>(input: &mut Parser<'i, '_>) -> Result<'i, SelectorComponent> {
+    let name = input.expect_ident()?.clone();
+    let mut attribute = SelectorComponent::new(ComponentType::Attribute, &name);
+    if input.is_exhausted() {
+        return Ok(attribute);
+    }
+
+    let location = input.current_source_location();
+    attribute.attribute_operator = match input.next()? {
+        cssparser::Token::Delim('=') => AttributeOperator::Equal,
+        cssparser::Token::IncludeMatch => AttributeOperator::IncludeMatch,
+        cssparser::Token::DashMatch => AttributeOperator::DashMatch,
+        cssparser::Token::PrefixMatch => AttributeOperator::PrefixMatch,
+        cssparser::Token::SuffixMatch => AttributeOperator::SuffixMatch,
+        cssparser::Token::SubstringMatch => AttributeOperator::SubstringMatch,
+        token => return Err(location.new_unexpected_token_error(token.clone())),
+    };
+    attribute.value = input.expect_ident_or_string()?.to_string().into();
+
+    if !input.is_exhausted() {
+        let location = input.current_source_location();
+        let flag = input.expect_ident()?.clone();
+        match &*flag.to_ascii_lowercase() {
+            "i" => attribute.case_insensitive = true,
+            "s" => {},
+            _ => return Err(location.new_unexpected_token_error(cssparser::Token::Ident(flag))),
+        }
+    }
+    Ok(attribute)
+}
+
+/// Parses what follows the `:` of a pseudo-class or pseudo-element.
+fn parse_pseudo<'i>(input: &mut Parser<'i, '_>) -> Result<'i, (SelectorComponent, Vec<SelectorNode>)> {
+    let mut component_type = ComponentType::PseudoClass;
+    let mut location = input.current_source_location();
+    let mut token = input.next_including_whitespace()?.clone();
+    if token == cssparser::Token::Colon {
+        component_type = ComponentType::PseudoElement;
+        location = input.current_source_location();
+        token = input.next_including_whitespace()?.clone();
+    }
+
+    match token {
+        cssparser::Token::Ident(name) => {
+            if LEGACY_PSEUDO_ELEMENTS.iter().any(|legacy| name.eq_ignore_ascii_case(legacy)) {
+                component_type = ComponentType::PseudoElement;
+            }
+            Ok((SelectorComponent::new(component_type, &name), Vec::new()))
+        },
+        cssparser::Token::Function(name) => {
+            let mut component = SelectorComponent { is_function: true, ..SelectorComponent::new(component_type, &name) };
+            let name = name.to_ascii_lowercase();
+            let selectors = input.parse_nested_block(|input| {
+                if component_type == ComponentType::PseudoClass && SELECTOR_LIST_PSEUDO_CLASSES.contains(&&*name) {
+                    return parse_selectors(input, name == "has");
+                }
+                if component_type == ComponentType::PseudoClass && NTH_PSEUDO_CLASSES.contains(&&*name) {
+                    (component.nth_a, component.nth_b) = cssparser::parse_nth(input)?;
+                    component.has_nth = true;
+                    if (name == "nth-child" || name == "nth-last-child") && input.try_parse(|input| input.expect_ident_matching("of")).is_ok() {
+                        return parse_selectors(input, false);
+                    }
+                    return Ok(Vec::new());
+                }
+                component.arguments = component_tokens(input).map(|tokens| tokens.into_iter().map(|(token, location)| Token::from(token).at(location)).collect::<Vec<_>>().into())?;
+                Ok(Vec::new())
+            })?;
+            Ok((component, selectors))
+        },
+        token => Err(location.new_unexpected_token_error(token)),
+    }
+}
+
+impl From<Vec<SelectorNode>> for SelectorList {
+    /// Lays the selectors out breadth first, like a `TokenTree`.
+    fn from(selectors: Vec<SelectorNode>) -> Self {
+        let roots = selectors.len();
+        // the children of a selector are the selectors of its components, one component after the other
+        let children = |selector: &mut SelectorNode| {
+            selector.components.iter_mut().flat_map(|(component, selectors)| {
+                component.selector_count = selectors.len();
+                std::mem::take(selectors)
+            }).collect()
+        };
+        let flat = breadth_first(selectors, children)
+            .into_iter()
+            .map(|(selector, mut next_child, _)| {
+                let components = selector.components.into_iter().map(|(mut component, _)| {
+                    if component.selector_count > 0 {
+                        component.first_selector = next_child;
+                        next_child += component.selector_count;
+                    }
+                    component
+                });
+                Selector { components: components.collect::<Vec<_>>().into(), specificity: selector.specificity, location: selector.location }
+            })
+            .collect::<Vec<_>>();
+        Self { selectors: flat.into(), roots }
+    }
+}
+
+/// Parses `input` as a selector list, which is invalid as a whole as soon as one of its selectors is.
+pub fn selector_list(input: &str) -> std::result::Result<SelectorList, Error> {
+    let mut parser_input = cssparser::ParserInput::new(input);
+    let mut parser = Parser::new(&mut parser_input);
+    parser.parse_entirely(|input| parse_selectors(input, false))
+        .map(Into::into)
+        .map_err(|e| Error::from_parse_error(&e, parser.position()))
+}
+
+/// Parses the NUL-terminated string `input` (such as the prelude of a style rule) into the selectors written to `list`.
+///
+/// Out-params behave like they do for `parse_css`, `list` must be released with `free_selector_list`.
+#[ffi_export]
+pub fn parse_selector_list(input: *const safer_ffi::c_char, list: Out<'_, SelectorList>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        selector_list(input_str(input)?)
+    });
+
+    finish(result, list, || Vec::new().into(), error)
+}
+
+/// Compares two specificities, returning a negative number, 0 or a positive number when `a` is less than, equal to or greater than `b`.
+#[ffi_export]
+pub fn specificity_compare(a: Specificity, b: Specificity) -> i32 {
+    a.cmp(&b) as i32
+}
+
+#[ffi_export]
+pub fn free_selector_list(list: SelectorList) {
+    drop(list);
+}
@@ -0,0 +1,178 @@
+#![allow(nonstandard_style)]
+use cssparser::{ToCss, TokenSerializationType};
+use safer_ffi::{ffi_export, prelude::{c_slice, Out}};
+
+use crate::{cstr, error::{catch_panic, finish, Error, Status}, input_str, location::SourceLocation, Token};
+
+/// Serializes `tokens` back to CSS the way `cssparser::ToCss` does.
+///
+/// `parse_css` drops whitespace and comments, so wherever two tokens would otherwise
+/// run together and tokenize differently (`a` `b` becoming `ab`) a single space is written between them.
+/// It does not close functions either, a function without its closing token is closed
+/// right before the block around it is, or at the end.
+/// Parsing the result gives back the same tokens.
+pub fn tokens_to_string(tokens: &[Token]) -> String {
+    serialize_tokens(tokens).0
+}
+
+/// Like [`tokens_to_string`], also returning where each token starts in the result.
+fn serialize_tokens(tokens: &[Token]) -> (String, Vec<usize>) {
+    let mut css = String::new();
+    let mut starts = Vec::with_capacity(tokens.len());
+    let mut previous = TokenSerializationType::default();
+    // whether each block still open is a function
+    let mut blocks = Vec::new();
+    let close_functions = |blocks: &mut Vec<bool>, css: &mut String, previous: &mut TokenSerializationType| {
+        while blocks.last() == Some(&true) {
+            blocks.pop();
+            css.push(')');
+            *previous = cssparser::Token::CloseParenthesis.serialization_type();
+        }
+    };
+    for token in tokens {
+        let token = cssparser::Token::from(&*token.value);
+        match token {
+            cssparser::Token::CloseSquareBracket | cssparser::Token::CloseCurlyBracket => {
+                close_functions(&mut blocks, &mut css, &mut previous);
+                blocks.pop();
+            },
+            cssparser::Token::CloseParenthesis => {
+                blocks.pop();
+            },
+            _ => {},
+        }
+        let serialization_type = token.serialization_type();
+        if previous.needs_separator_when_before(serialization_type) {
+            css.push(' ');
+        }
+        starts.push(css.len());
+        // writing to a `String` cannot fail
+        let _ = token.to_css(&mut css);
+        previous = serialization_type;
+        match token {
+            cssparser::Token::Function(_) => blocks.push(true),
+            cssparser::Token::ParenthesisBlock | cssparser::Token::SquareBracketBlock | cssparser::Token::CurlyBracketBlock => blocks.push(false),
+            _ => {},
+        }
+    }
+    close_functions(&mut blocks, &mut css, &mut previous);
+    (css, starts)
+}
+
+/// `tokens` (as returned by `parse_css`) serialized back to CSS, for parsers that need text rather than tokens.
+pub struct Reserialized<'a> {
+    pub css: String,
+    /// Where each token starts in `css`.
+    starts: Vec<usize>,
+    tokens: &'a [Token],
+}
+
+impl<'a> Reserialized<'a> {
+    pub fn new(tokens: &'a [Token]) -> Self {
+        let (css, starts) = serialize_tokens(tokens);
+        Self { css, starts, tokens }
+    }
+
+    /// The token `offset` (a byte offset in `css`) falls in, the last one past the end.
+    fn token_at(&self, offset: usize) -> Option<&'a Token> {
+        let index = self.starts.partition_point(|&start| start <= offset).saturating_sub(1);
+        self.tokens.get(index)
+    }
+
+    /// Moves `location`, a range of `css`, to the input the tokens came from,
+    /// where it spans the tokens it overlaps.
+    pub fn relocate(&self, location: SourceLocation) -> SourceLocation {
+        match (self.token_at(location.start), self.token_at(location.end.max(location.start + 1) - 1)) {
+            (Some(first), Some(last)) => SourceLocation { end: last.location.end, ..first.location },
+            _ => location,
+        }
+    }
+}
+
+/// Runs `parse` over `tokens` (as returned by `parse_css`), which must consume all of them.
+///
+/// The tokens are serialized back to CSS and tokenized again, so an error is located
+/// at the whole token it occurred at, in the input the tokens came from.
+/// An error at the end of the tokens points at the last one.
+pub fn parse_tokens<T, E: std::fmt::Display>(
+    tokens: &[Token],
+    parse: impl for<'i, 't> FnOnce(&mut cssparser::Parser<'i, 't>) -> Result<T, cssparser::ParseError<'i, E>>,
+) -> Result<T, Error> {
+    let reserialized = Reserialized::new(tokens);
+    let mut input = cssparser::ParserInput::new(&reserialized.css);
+    let mut parser = cssparser::Parser::new(&mut input);
+    parser.parse_entirely(parse).map_err(|e| {
+        let error = Error::from_parse_error(&e, parser.position());
+        let offset = byte_offset(&reserialized.css, e.location);
+        match reserialized.token_at(offset) {
+            Some(token) => error.at(token.location),
+            None => error,
+        }
+    })
+}
+
+/// The byte offset of `location` in `css`, the inverse of `Parser::current_source_location`.
+fn byte_offset(css: &str, location: cssparser::SourceLocation) -> usize {
+    let line_start = match location.line {
+        0 => 0,
+        line => css.match_indices('\n').nth(line as usize - 1).map_or(css.len(), |(i, _)| i + 1),
+    };
+    let mut column = 1;
+    for (i, c) in css[line_start..].char_indices() {
+        if column >= location.column {
+            return line_start + i;
+        }
+        column += c.len_utf16() as u32;
+    }
+    css.len()
+}
+
+/// Serializes the tokens of `tokens` (as returned by `parse_css`) back to CSS, written to `css`.
+///
+/// `css` is always written to and must be released with `free_string`,
+/// `error` behaves like it does for `parse_css`.
+#[ffi_export]
+pub fn tokens_to_css(tokens: c_slice::Ref<'_, Token>, css: Out<'_, cstr>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        Ok(tokens_to_string(tokens.as_slice()).into())
+    });
+
+    finish(result, css, || String::new().into(), error)
+}
+
+/// Writes `input` escaped by `serialize`, which writes to a `String` and so cannot fail.
+fn escape(input: *const safer_ffi::c_char, css: Out<'_, cstr>, error: Option<Out<'_, Error>>, serialize: fn(&str, &mut String) -> std::fmt::Result) -> Status {
+    let result = catch_panic(|| {
+        let mut escaped = String::new();
+        let _ = serialize(input_str(input)?, &mut escaped);
+        Ok(escaped.into())
+    });
+
+    finish(result, css, || String::new().into(), error)
+}
+
+/// Escapes the NUL-terminated string `input` so that it is read back as a single identifier,
+/// such as a class name (`2col` → `\32 col`), written to `css`.
+///
+/// `css` is always written to and must be released with `free_string`,
+/// `error` behaves like it does for `parse_css`.
+#[ffi_export]
+pub fn serialize_identifier(input: *const safer_ffi::c_char, css: Out<'_, cstr>, error: Option<Out<'_, Error>>) -> Status {
+    escape(input, css, error, cssparser::serialize_identifier)
+}
+
+/// Like `serialize_identifier`, for the name of a hash or of a custom property,
+/// which may start with a digit or `-` (`#2col` needs no escape).
+#[ffi_export]
+pub fn serialize_name(input: *const safer_ffi::c_char, css: Out<'_, cstr>, error: Option<Out<'_, Error>>) -> Status {
+    escape(input, css, error, cssparser::serialize_name)
+}
+
+/// Quotes the NUL-terminated string `input` as a CSS string, such as a font name (`Foo "Bar"` → `"Foo \"Bar\""`),
+/// written to `css`.
+///
+/// Out-params behave like they do for `serialize_identifier`.
+#[ffi_export]
+pub fn serialize_string(input: *const safer_ffi::c_char, css: Out<'_, cstr>, error: Option<Out<'_, Error>>) -> Status {
+    escape(input, css, error, cssparser::serialize_string)
+}
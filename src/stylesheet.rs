@@ -0,0 +1,441 @@
+#![allow(nonstandard_style)]
+use std::convert::Infallible;
+
+use cssparser::{AtRuleParser, BasicParseErrorKind, CowRcStr, DeclarationParser, ParseError, Parser, ParserState, QualifiedRuleParser, RuleBodyItemParser, RuleBodyParser, StyleSheetParser};
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{repr_c, Out}};
+
+use crate::{cstr, error::{catch_panic, finish, Error, Status}, input_str, layout::breadth_first, location::SourceLocation, walk_including_whitespace, Token};
+
+#[derive_ReprC]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleType {
+    /// A style rule such as `a:hover { color: red }`.
+    Qualified,
+    /// A rule starting with an at-keyword such as `@media screen { ... }` or `@import "a.css";`.
+    At,
+}
+
+/// A `name: value` declaration.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct Declaration {
+    /// The property name as written, custom properties keep their `--`.
+    pub name: cstr,
+    /// The tokens of the value, without the `!important` and without leading or trailing whitespace.
+    pub value: repr_c::Vec<Token>,
+    pub important: bool,
+    /// From the first byte of the name to the last byte of the value (or `!important`), the `;` is not included.
+    pub location: SourceLocation,
+}
+
+/// A qualified rule or an at-rule.
+///
+/// The nested rules of a rule are `sheet.rules.ptr[first_child .. first_child + child_count]`,
+/// like the children of a `Node_t` they live in the stylesheet's list.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct Rule {
+    pub rule_type: RuleType,
+    /// The name of an at-rule without its `@`, empty for qualified rules.
+    pub name: cstr,
+    /// The tokens before the block (or `;`), without leading or trailing whitespace.
+    pub prelude: repr_c::Vec<Token>,
+    /// `false` for at-rules that end with a `;` instead of a block, qualified rules always have one.
+    pub has_block: bool,
+    /// The declarations inside of the block.
+    pub declarations: repr_c::Vec<Declaration>,
+    /// Index of the first nested rule in the stylesheet's rule list.
+    pub first_child: usize,
+    /// The number of rules inside of the block (the rules of an `@media`, or nested style rules).
+    pub child_count: usize,
+    /// From the first byte of the rule to the end of its block or `;`.
+    pub location: SourceLocation,
+}
+
+/// The rules of a stylesheet, free it with `free_stylesheet`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct StyleSheet {
+    /// Every rule of the stylesheet, the top-level rules are `rules.ptr[0 .. roots]`.
+    pub rules: repr_c::Vec<Rule>,
+    pub roots: usize,
+    /// The rules and declarations that could not be parsed and were left out,
+    /// the location of each error spans the input that was skipped.
+    pub errors: repr_c::Vec<Error>,
+}
+
+/// A rule that owns its nested rules, before it is laid out in a [`StyleSheet`].
+#[derive(Debug)]
+pub struct RuleNode {
+    pub rule: Rule,
+    pub children: Vec<RuleNode>,
+}
+
+/// What a block can hold.
+pub enum Item {
+    Declaration(Declaration),
+    Rule(RuleNode),
+}
+
+/// At-rules whose block holds rules rather than declarations.
+const RULE_LIST_AT_RULES: &[&str] = &[
+    "media", "supports", "document", "-moz-document", "layer", "container", "scope", "starting-style",
+    "keyframes", "-webkit-keyframes", "-moz-keyframes",
+];
+
+/// Parses the rules of a stylesheet and the contents of their blocks, collecting what it has to skip.
+pub struct RulesParser<'a> {
+    pub errors: &'a mut Vec<Error>,
+    /// Whether the current block may hold declarations.
+    pub declarations: bool,
+    /// Whether the current block may hold qualified rules.
+    pub qualified: bool,
+    /// Whether the current block may hold at-rules, a declaration list cannot.
+    pub at_rules: bool,
+    /// Whether the current block is inside of a style rule, where grouping rules also hold declarations.
+    pub nested: bool,
+}
+
+/// The tokens of the rest of `input` with their location.
+///
+/// Unlike in `parse_css` whitespace is kept, as it matters in selectors (`a b` is not `ab`),
+/// but comments and whitespace at either end are not.
+pub fn component_tokens<'i>(input: &mut Parser<'i, '_>) -> Result<Vec<(cssparser::Token<'i>, SourceLocation)>, ParseError<'i, Infallible>> {
+    component_tokens_and_comments(input, &mut Vec::new())
+}
+
+/// Like [`component_tokens`], the text of the comments is added to `comments`.
+pub fn component_tokens_and_comments<'i>(input: &mut Parser<'i, '_>, comments: &mut Vec<(&'i str, SourceLocation)>) -> Result<Vec<(cssparser::Token<'i>, SourceLocation)>, ParseError<'i, Infallible>> {
+    let mut tokens = Vec::new();
+    walk_including_whitespace(input, &mut |token, location| match token {
+        cssparser::Token::Comment(text) => comments.push((text, location)),
+        _ => tokens.push((token.clone(), location)),
+    })?;
+    while let Some((cssparser::Token::WhiteSpace(_), _)) = tokens.last() {
+        tokens.pop();
+    }
+    let leading = tokens.iter().take_while(|(token, _)| matches!(token, cssparser::Token::WhiteSpace(_))).count();
+    tokens.drain(..leading);
+    Ok(tokens)
+}
+
+/// Removes a trailing `!important` from the tokens of a declaration value, returning whether there was one.
+pub fn strip_important(value: &mut Vec<(cssparser::Token, SourceLocation)>) -> bool {
+    let mut important = false;
+    if let [.., (cssparser::Token::Delim('!'), _), (cssparser::Token::Ident(ident), _)] = &value[..] {
+        important = ident.eq_ignore_ascii_case("important");
+    }
+    // `! important` is valid too
+    if let [.., (cssparser::Token::Delim('!'), _), (cssparser::Token::WhiteSpace(_), _), (cssparser::Token::Ident(ident), _)] = &value[..] {
+        important = ident.eq_ignore_ascii_case("important");
+    }
+    if important {
+        while !matches!(value.pop(), Some((cssparser::Token::Delim('!'), _))) {}
+        while let Some((cssparser::Token::WhiteSpace(_), _)) = value.last() {
+            value.pop();
+        }
+    }
+    important
+}
+
+/// Whether the block of a rule holds `(declarations, qualified rules)`,
+/// `at_rule` is the name of an at-rule and `nested` is set inside of a style rule.
+pub fn block_contents(at_rule: Option<&str>, nested: bool) -> (bool, bool) {
+    let Some(name) = at_rule else {
+        // style rules hold declarations and, with CSS nesting, more style rules
+        return (true, true);
+    };
+    let holds_rules = RULE_LIST_AT_RULES.iter().any(|rule_list| name.eq_ignore_ascii_case(rule_list));
+    // inside of a style rule `@media` and friends hold declarations as well
+    let declarations = !holds_rules || (nested && !name.to_ascii_lowercase().ends_with("keyframes"));
+    (declarations, holds_rules)
+}
+
+/// The error for an item a rule or declaration list skipped, `start` being where it began and `end` where the list stopped.
+///
+/// It spans the input that was skipped.
+pub fn skipped_item_error(input: &mut Parser, error: &ParseError<'_, Infallible>, start: cssparser::SourcePosition, start_location: cssparser::SourceLocation, end: cssparser::SourcePosition) -> Error {
+    // a block after an invalid prelude is only consumed once the parser moves on
+    input.skip_whitespace();
+    let skipped = input.slice(start..input.position()).trim_end().len();
+    let location = SourceLocation { end: start.byte_index() + skipped, ..SourceLocation::new(start, start, start_location) };
+    Error::from_parse_error(error, end).at(location)
+}
+
+fn into_tokens(tokens: Vec<(cssparser::Token, SourceLocation)>) -> repr_c::Vec<Token> {
+    tokens.into_iter().map(|(token, location)| Token::from(token).at(location)).collect::<Vec<_>>().into()
+}
+
+/// `StyleSheetParser` and `RuleBodyParser` both keep the parser they were given in public fields.
+pub trait ItemList<'i, 't>: Iterator<Item = Result<Item, (ParseError<'i, Infallible>, &'i str)>> {
+    fn input(&mut self) -> &mut Parser<'i, 't>;
+    fn errors(&mut self) -> &mut Vec<Error>;
+}
+
+impl<'i, 't> ItemList<'i, 't> for StyleSheetParser<'i, 't, '_, RulesParser<'_>> {
+    fn input(&mut self) -> &mut Parser<'i, 't> {
+        self.input
+    }
+
+    fn errors(&mut self) -> &mut Vec<Error> {
+        self.parser.errors
+    }
+}
+
+impl<'i, 't> ItemList<'i, 't> for RuleBodyParser<'i, 't, '_, RulesParser<'_>, Item, Infallible> {
+    fn input(&mut self) -> &mut Parser<'i, 't> {
+        self.input
+    }
+
+    fn errors(&mut self) -> &mut Vec<Error> {
+        self.parser.errors
+    }
+}
+
+/// Runs `list` to the end, locating what it returns and turning what it skips into errors.
+pub fn collect_items<'i: 't, 't>(mut list: impl ItemList<'i, 't>) -> Vec<Item> {
+    let mut items = Vec::new();
+    loop {
+        // the list skips whitespace and stray semicolons itself, doing it first tells where the next item starts
+        let input = list.input();
+        input.skip_whitespace();
+        while input.try_parse(|input| input.expect_semicolon()).is_ok() {
+            input.skip_whitespace();
+        }
+        let start = input.position();
+        let start_location = input.current_source_location();
+
+        let Some(item) = list.next() else { break };
+        let end = list.input().position();
+        match item {
+            Ok(Item::Declaration(mut declaration)) => {
+                // the end is set by `parse_value`, which does not include the `;`
+                let end = declaration.location.end;
+                declaration.location = SourceLocation { end, ..SourceLocation::new(start, start, start_location) };
+                items.push(Item::Declaration(declaration));
+            },
+            Ok(Item::Rule(mut rule)) => {
+                // the start is set by the rule parsers, as a skipped `@charset` may come first
+                rule.rule.location.end = end.byte_index();
+                items.push(Item::Rule(rule));
+            },
+            Err((error, _)) => {
+                let error = skipped_item_error(list.input(), &error, start, start_location, end);
+                list.errors().push(error);
+            },
+        }
+    }
+    items
+}
+
+impl<'a> RulesParser<'a> {
+    pub fn new(errors: &'a mut Vec<Error>) -> Self {
+        Self { errors, declarations: false, qualified: true, at_rules: true, nested: false }
+    }
+
+    /// A parser for a block with the given contents, recording its errors in the same list.
+    fn block(&mut self, declarations: bool, qualified: bool, nested: bool) -> RulesParser<'_> {
+        RulesParser { errors: self.errors, declarations, qualified, at_rules: true, nested }
+    }
+
+    /// Parses the contents of a block into `rule`.
+    fn parse_body<'i>(&mut self, mut rule: Rule, input: &mut Parser<'i, '_>) -> Item {
+        let mut declarations = Vec::new();
+        let mut children = Vec::new();
+        for item in collect_items(RuleBodyParser::new(input, self)) {
+            match item {
+                Item::Declaration(declaration) => declarations.push(declaration),
+                Item::Rule(child) => children.push(child),
+            }
+        }
+        rule.has_block = true;
+        rule.declarations = declarations.into();
+        Item::Rule(RuleNode { rule, children })
+    }
+}
+
+fn new_rule(rule_type: RuleType, name: &str, prelude: repr_c::Vec<Token>, start: &ParserState) -> Rule {
+    Rule {
+        rule_type,
+        name: name.to_string().into(),
+        prelude,
+        has_block: false,
+        declarations: Vec::new().into(),
+        first_child: 0,
+        child_count: 0,
+        location: SourceLocation::new(start.position(), start.position(), start.source_location()),
+    }
+}
+
+impl<'i> QualifiedRuleParser<'i> for RulesParser<'_> {
+    type Prelude = repr_c::Vec<Token>;
+    type QualifiedRule = Item;
+    type Error = Infallible;
+
+    fn parse_prelude<'t>(&mut self, input: &mut Parser<'i, 't>) -> Result<Self::Prelude, ParseError<'i, Self::Error>> {
+        component_tokens(input).map(into_tokens)
+    }
+
+    fn parse_block<'t>(&mut self, prelude: Self::Prelude, start: &ParserState, input: &mut Parser<'i, 't>) -> Result<Self::QualifiedRule, ParseError<'i, Self::Error>> {
+        let (declarations, qualified) = block_contents(None, self.nested);
+        Ok(self.block(declarations, qualified, true).parse_body(new_rule(RuleType::Qualified, "", prelude, start), input))
+    }
+}
+
+impl<'i> AtRuleParser<'i> for RulesParser<'_> {
+    type Prelude = (CowRcStr<'i>, repr_c::Vec<Token>);
+    type AtRule = Item;
+    type Error = Infallible;
+
+    fn parse_prelude<'t>(&mut self, name: CowRcStr<'i>, input: &mut Parser<'i, 't>) -> Result<Self::Prelude, ParseError<'i, Self::Error>> {
+        if !self.at_rules {
+            return Err(input.new_error(BasicParseErrorKind::AtRuleInvalid(name)));
+        }
+        Ok((name, component_tokens(input).map(into_tokens)?))
+    }
+
+    fn rule_without_block(&mut self, (name, prelude): Self::Prelude, start: &ParserState) -> Result<Self::AtRule, ()> {
+        Ok(Item::Rule(RuleNode { rule: new_rule(RuleType::At, &name, prelude, start), children: Vec::new() }))
+    }
+
+    fn parse_block<'t>(&mut self, (name, prelude): Self::Prelude, start: &ParserState, input: &mut Parser<'i, 't>) -> Result<Self::AtRule, ParseError<'i, Self::Error>> {
+        let (declarations, qualified) = block_contents(Some(&name), self.nested);
+        let nested = self.nested;
+        Ok(self.block(declarations, qualified, nested).parse_body(new_rule(RuleType::At, &name, prelude, start), input))
+    }
+}
+
+impl<'i> DeclarationParser<'i> for RulesParser<'_> {
+    type Declaration = Item;
+    type Error = Infallible;
+
+    fn parse_value<'t>(&mut self, name: CowRcStr<'i>, input: &mut Parser<'i, 't>) -> Result<Self::Declaration, ParseError<'i, Self::Error>> {
+        let end = input.position();
+        let mut value = component_tokens(input)?;
+        let end = value.last().map_or(end.byte_index(), |(_, location)| location.end);
+        let important = strip_important(&mut value);
+
+        Ok(Item::Declaration(Declaration {
+            name: name.to_string().into(),
+            value: into_tokens(value),
+            important,
+            location: SourceLocation { end, ..Default::default() },
+        }))
+    }
+}
+
+impl<'i> RuleBodyItemParser<'i, Item, Infallible> for RulesParser<'_> {
+    fn parse_declarations(&self) -> bool {
+        self.declarations
+    }
+
+    fn parse_qualified(&self) -> bool {
+        self.qualified
+    }
+}
+
+impl From<Vec<RuleNode>> for StyleSheet {
+    fn from(rules: Vec<RuleNode>) -> Self {
+        let roots = rules.len();
+        let flat = breadth_first(rules, |node| std::mem::take(&mut node.children))
+            .into_iter()
+            .map(|(node, first_child, child_count)| Rule { first_child, child_count, ..node.rule })
+            .collect::<Vec<_>>();
+        Self { rules: flat.into(), roots, errors: Vec::new().into() }
+    }
+}
+
+/// Parses the rules of `input` without laying them out, rules that cannot be parsed are skipped and recorded in `errors`.
+pub fn rule_nodes(input: &str, errors: &mut Vec<Error>) -> Vec<RuleNode> {
+    let mut parser_input = cssparser::ParserInput::new(input);
+    let mut parser = Parser::new(&mut parser_input);
+    let mut rules_parser = RulesParser::new(errors);
+
+    collect_items(StyleSheetParser::new(&mut parser, &mut rules_parser))
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::Rule(rule) => Some(rule),
+            // `StyleSheetParser` never parses declarations
+            Item::Declaration(_) => None,
+        })
+        .collect()
+}
+
+/// Parses the rules of `input`, rules that cannot be parsed are skipped and recorded in the stylesheet's `errors`.
+pub fn stylesheet(input: &str) -> StyleSheet {
+    let mut errors = Vec::new();
+    let rules = rule_nodes(input, &mut errors);
+    StyleSheet { errors: errors.into(), ..rules.into() }
+}
+
+/// The declarations of a `style` attribute (or any other list of declarations), free it with `free_declaration_list`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct DeclarationList {
+    pub declarations: repr_c::Vec<Declaration>,
+    /// The declarations that could not be parsed and were left out,
+    /// the location of each error spans the input that was skipped.
+    pub errors: repr_c::Vec<Error>,
+}
+
+/// Parses `input` as the contents of a declaration block, such as `color: red; margin: 0 !important`.
+///
+/// Invalid declarations are skipped and recorded in the list's `errors`, so are at-rules as they cannot appear in one.
+pub fn declaration_list(input: &str) -> DeclarationList {
+    let mut errors = Vec::new();
+    let mut parser_input = cssparser::ParserInput::new(input);
+    let mut parser = Parser::new(&mut parser_input);
+    let mut rules_parser = RulesParser { declarations: true, qualified: false, at_rules: false, ..RulesParser::new(&mut errors) };
+
+    let declarations = collect_items(RuleBodyParser::new(&mut parser, &mut rules_parser))
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::Declaration(declaration) => Some(declaration),
+            // at-rules are rejected by the parser
+            Item::Rule(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    DeclarationList { declarations: declarations.into(), errors: errors.into() }
+}
+
+/// Parses the NUL-terminated string `input` into the stylesheet written to `sheet`.
+///
+/// Invalid rules and declarations do not make the call fail, they are left out and listed in `sheet.errors`.
+/// Out-params otherwise behave like they do for `parse_css`, `sheet` must be released with `free_stylesheet`.
+#[ffi_export]
+pub fn parse_stylesheet(input: *const safer_ffi::c_char, sheet: Out<'_, StyleSheet>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        Ok(stylesheet(input_str(input)?))
+    });
+
+    finish(result, sheet, || Vec::new().into(), error)
+}
+
+#[ffi_export]
+pub fn free_stylesheet(sheet: StyleSheet) {
+    drop(sheet);
+}
+
+/// Parses the NUL-terminated string `input` as a list of declarations (the value of a `style` attribute) written to `list`.
+///
+/// Invalid declarations do not make the call fail, they are left out and listed in `list.errors`.
+/// Out-params otherwise behave like they do for `parse_css`, `list` must be released with `free_declaration_list`.
+#[ffi_export]
+pub fn parse_declaration_list(input: *const safer_ffi::c_char, list: Out<'_, DeclarationList>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        Ok(declaration_list(input_str(input)?))
+    });
+
+    finish(result, list, || DeclarationList { declarations: Vec::new().into(), errors: Vec::new().into() }, error)
+}
+
+#[ffi_export]
+pub fn free_declaration_list(list: DeclarationList) {
+    drop(list);
+}
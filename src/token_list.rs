@@ -0,0 +1,92 @@
+#![allow(nonstandard_style)]
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{repr_c, Out}};
+
+use crate::{error::{catch_panic, finish, Error, ErrorKind, Status}, view::{StrView, TokenView}, walk};
+
+/// The tokens of one parse and everything they point to.
+///
+/// Tokens are stored inline as [`TokenView`]s and all of their text lives in a single buffer,
+/// so a list makes a handful of allocations no matter how many tokens it holds,
+/// and it is released with a single `token_list_free`.
+/// Every `text` of a token in the list is `owned` by it.
+#[derive_ReprC]
+#[repr(opaque)]
+pub struct TokenList {
+    tokens: Vec<TokenView>,
+    /// The buffer the text of the tokens points into, it is only kept alive.
+    #[allow(dead_code)]
+    text: String,
+}
+
+impl TokenList {
+    /// Tokenizes `input` like `parse_css` does.
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        let mut tokens = Vec::new();
+        let mut text = String::new();
+
+        let mut parser_input = cssparser::ParserInput::new(input);
+        let mut parser = cssparser::Parser::new(&mut parser_input);
+        walk::<std::convert::Infallible>(&mut parser, &mut |token, location| {
+            tokens.push(TokenView::new(token, location, input, &mut |s| {
+                // the buffer may still grow, so only remember the offset for now
+                let view = StrView { ptr: text.len() as *const u8, len: s.len(), owned: true };
+                text.push_str(s);
+                view
+            }));
+        }).map_err(|e| Error::from_parse_error(&e, parser.position()))?;
+
+        let base = text.as_ptr();
+        for token in tokens.iter_mut().filter(|token| token.text.owned) {
+            token.text.ptr = base.wrapping_add(token.text.ptr as usize);
+        }
+
+        Ok(Self { tokens, text })
+    }
+
+    pub fn tokens(&self) -> &[TokenView] {
+        &self.tokens
+    }
+}
+
+/// Parses the `len` bytes starting at `input` into a token list written to `list`.
+///
+/// Invalid UTF-8 is replaced the same way `parse_css_bytes` does, the input is not needed after this call.
+/// On failure `list` is set to null, otherwise it must be released with `token_list_free`.
+#[ffi_export]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn parse_css_list(input: *const u8, len: usize, list: Out<'_, Option<repr_c::Box<TokenList>>>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        if len == 0 {
+            return TokenList::parse("").map(|list| Some(Box::new(list).into()));
+        }
+        if input.is_null() {
+            return Err(Error::new(ErrorKind::NullPointer, "input is null"));
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(input, len) };
+        TokenList::parse(&String::from_utf8_lossy(bytes)).map(|list| Some(Box::new(list).into()))
+    });
+
+    finish(result, list, || None, error)
+}
+
+#[ffi_export]
+pub fn token_list_len(list: &TokenList) -> usize {
+    list.tokens.len()
+}
+
+/// A pointer to the first of the `token_list_len` tokens of `list`, valid until the list is freed.
+#[ffi_export]
+pub fn token_list_tokens(list: &TokenList) -> *const TokenView {
+    list.tokens.as_ptr()
+}
+
+/// The token at `index`, or null when it is out of bounds.
+#[ffi_export]
+pub fn token_list_get(list: &TokenList, index: usize) -> Option<&TokenView> {
+    list.tokens.get(index)
+}
+
+#[ffi_export]
+pub fn token_list_free(list: repr_c::Box<TokenList>) {
+    drop(list);
+}
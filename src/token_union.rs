@@ -1,13 +1,18 @@
 #![allow(nonstandard_style)]
-use std::ffi::{c_char, c_float, c_int};
+use std::ffi::{c_float, c_int};
+use std::mem::ManuallyDrop;
 
 use safer_ffi::{derive_ReprC, prelude::repr_c};
 use paste::paste;
-use crate::cstr;
+use crate::{cstr, token_types::TokenType};
 
 #[derive_ReprC]
 #[repr(opaque)]
-pub struct TokenValue(Value);
+pub struct TokenValue {
+    /// Which field of `value` is active.
+    token_type: TokenType,
+    value: Value,
+}
 
 macro_rules! value_getter {
     ($name:ident, $type:ty) => {
@@ -39,215 +44,370 @@ make_getters! {
     whitespace, cstr;
     bad_string, cstr;
     bad_url, cstr;
-    delim, c_char;
+    delim, u32;
+    delim_utf8, cstr;
 }
 
 impl<'i> From<cssparser::Token<'i>> for TokenValue {
     fn from(value: cssparser::Token<'i>) -> Self {
-        match value {
-            cssparser::Token::Ident(ident) => Self(Value::new_ident(ident.to_string().into())),
-            cssparser::Token::AtKeyword(at_keyword) => Self(Value::new_at_keyword(at_keyword.to_string().into())),
-            cssparser::Token::Hash(hash) => Self(Value::new_hash(hash.to_string().into())),
-            cssparser::Token::IDHash(id_hash) => Self(Value::new_id_hash(id_hash.to_string().into())),
-            cssparser::Token::QuotedString(quoted_string) => Self(Value::new_quoted_string(quoted_string.to_string().into())),
-            cssparser::Token::UnquotedUrl(unquoted_url) => Self(Value::new_unquoted_url(unquoted_url.to_string().into())),
-            cssparser::Token::Comment(comment) => Self(Value::new_comment(comment.to_string().into())),
-            cssparser::Token::Function(function) => Self(Value::new_function(function.to_string().into())),
+        let token_type = TokenType::from(&value);
+        let value = match value {
+            cssparser::Token::Ident(ident) => Value::new_ident(ident.to_string().into()),
+            cssparser::Token::AtKeyword(at_keyword) => Value::new_at_keyword(at_keyword.to_string().into()),
+            cssparser::Token::Hash(hash) => Value::new_hash(hash.to_string().into()),
+            cssparser::Token::IDHash(id_hash) => Value::new_id_hash(id_hash.to_string().into()),
+            cssparser::Token::QuotedString(quoted_string) => Value::new_quoted_string(quoted_string.to_string().into()),
+            cssparser::Token::UnquotedUrl(unquoted_url) => Value::new_unquoted_url(unquoted_url.to_string().into()),
+            cssparser::Token::Comment(comment) => Value::new_comment(comment.to_string().into()),
+            cssparser::Token::Function(function) => Value::new_function(function.to_string().into()),
             cssparser::Token::Percentage {
                 has_sign,
                 int_value,
                 unit_value,
-            } => Self(Value::new_percentage(
+            } => Value::new_percentage(
                 Percentage {
                     has_sign,
                     int_value: int_value.map(|v| Box::new(v).into()),
                     unit_value,
                 }
-            )),
+            ),
             cssparser::Token::Dimension {
                 has_sign,
                 value,
                 int_value,
                 unit,
-            } => Self(Value::new_dimension(
+            } => Value::new_dimension(
                 Dimension {
                     has_sign,
                     value,
                     int_value: int_value.map(|v| Box::new(v).into()),
                     unit: unit.to_string().into(),
                 }
-            )),
+            ),
             cssparser::Token::Number {
                 has_sign,
                 value,
                 int_value,
-            } => Self(Value::new_number(
+            } => Value::new_number(
                 Number {
                     has_sign,
                     value,
                     int_value: int_value.map(|v| Box::new(v).into()),
                 }
-            )),
-            cssparser::Token::Delim(delim) => Self(Value::new_delim(delim as c_char)),
-            cssparser::Token::WhiteSpace(whitespace) => Self(Value::new_whitespace(whitespace.to_string().into())),
-            cssparser::Token::BadString(bad_string) => Self(Value::new_bad_string(bad_string.to_string().into())),
-            cssparser::Token::BadUrl(bad_url) => Self(Value::new_bad_url(bad_url.to_string().into())),
-            cssparser::Token::CDC => Self(Value::empty()),
-            cssparser::Token::CDO => Self(Value::empty()),
-            cssparser::Token::CloseCurlyBracket => Self(Value::empty()),
-            cssparser::Token::CloseParenthesis => Self(Value::empty()),
-            cssparser::Token::CloseSquareBracket => Self(Value::empty()),
-            cssparser::Token::Colon => Self(Value::empty()),
-            cssparser::Token::Comma => Self(Value::empty()),
-            cssparser::Token::CurlyBracketBlock => Self(Value::empty()),
-            cssparser::Token::DashMatch => Self(Value::empty()),
-            cssparser::Token::IncludeMatch => Self(Value::empty()),
-            cssparser::Token::ParenthesisBlock => Self(Value::empty()),
-            cssparser::Token::PrefixMatch => Self(Value::empty()),
-            cssparser::Token::Semicolon => Self(Value::empty()),
-            cssparser::Token::SquareBracketBlock => Self(Value::empty()),
-            cssparser::Token::SubstringMatch => Self(Value::empty()),
-            cssparser::Token::SuffixMatch => Self(Value::empty()),
+            ),
+            cssparser::Token::Delim(delim) => Value::new_delim(delim),
+            cssparser::Token::WhiteSpace(whitespace) => Value::new_whitespace(whitespace.to_string().into()),
+            cssparser::Token::BadString(bad_string) => Value::new_bad_string(bad_string.to_string().into()),
+            cssparser::Token::BadUrl(bad_url) => Value::new_bad_url(bad_url.to_string().into()),
+            cssparser::Token::CDC => Value::empty(),
+            cssparser::Token::CDO => Value::empty(),
+            cssparser::Token::CloseCurlyBracket => Value::empty(),
+            cssparser::Token::CloseParenthesis => Value::empty(),
+            cssparser::Token::CloseSquareBracket => Value::empty(),
+            cssparser::Token::Colon => Value::empty(),
+            cssparser::Token::Comma => Value::empty(),
+            cssparser::Token::CurlyBracketBlock => Value::empty(),
+            cssparser::Token::DashMatch => Value::empty(),
+            cssparser::Token::IncludeMatch => Value::empty(),
+            cssparser::Token::ParenthesisBlock => Value::empty(),
+            cssparser::Token::PrefixMatch => Value::empty(),
+            cssparser::Token::Semicolon => Value::empty(),
+            cssparser::Token::SquareBracketBlock => Value::empty(),
+            cssparser::Token::SubstringMatch => Value::empty(),
+            cssparser::Token::SuffixMatch => Value::empty(),
+        };
+        unsafe { Self::new(token_type, value) }
+    }
+}
+
+impl<'a> From<&'a TokenValue> for cssparser::Token<'a> {
+    /// Rebuilds the `cssparser` token this is the value of, borrowing its strings.
+    fn from(value: &'a TokenValue) -> Self {
+        let text = |s: &'a ManuallyDrop<cstr>| -> &'a str { s };
+        let int_value = |v: &Option<repr_c::Box<c_int>>| v.as_ref().map(|v| **v);
+        let v = &value.value;
+        unsafe {
+            match value.token_type {
+                TokenType::Ident => cssparser::Token::Ident(text(&v.ident).into()),
+                TokenType::AtKeyword => cssparser::Token::AtKeyword(text(&v.at_keyword).into()),
+                TokenType::Hash => cssparser::Token::Hash(text(&v.hash).into()),
+                TokenType::IDHash => cssparser::Token::IDHash(text(&v.id_hash).into()),
+                TokenType::QuotedString => cssparser::Token::QuotedString(text(&v.quoted_string).into()),
+                TokenType::UnquotedUrl => cssparser::Token::UnquotedUrl(text(&v.unquoted_url).into()),
+                TokenType::Delim => cssparser::Token::Delim(value.get_delim_char()),
+                TokenType::Number => cssparser::Token::Number {
+                    has_sign: v.number.has_sign,
+                    value: v.number.value,
+                    int_value: int_value(&v.number.int_value),
+                },
+                TokenType::Percentage => cssparser::Token::Percentage {
+                    has_sign: v.percentage.has_sign,
+                    unit_value: v.percentage.unit_value,
+                    int_value: int_value(&v.percentage.int_value),
+                },
+                TokenType::Dimension => cssparser::Token::Dimension {
+                    has_sign: v.dimension.has_sign,
+                    value: v.dimension.value,
+                    int_value: int_value(&v.dimension.int_value),
+                    unit: (&*v.dimension.unit as &str).into(),
+                },
+                TokenType::WhiteSpace => cssparser::Token::WhiteSpace(text(&v.whitespace)),
+                TokenType::Comment => cssparser::Token::Comment(text(&v.comment)),
+                TokenType::Colon => cssparser::Token::Colon,
+                TokenType::Semicolon => cssparser::Token::Semicolon,
+                TokenType::Comma => cssparser::Token::Comma,
+                TokenType::IncludeMatch => cssparser::Token::IncludeMatch,
+                TokenType::DashMatch => cssparser::Token::DashMatch,
+                TokenType::PrefixMatch => cssparser::Token::PrefixMatch,
+                TokenType::SuffixMatch => cssparser::Token::SuffixMatch,
+                TokenType::SubstringMatch => cssparser::Token::SubstringMatch,
+                TokenType::CDO => cssparser::Token::CDO,
+                TokenType::CDC => cssparser::Token::CDC,
+                TokenType::Function => cssparser::Token::Function(text(&v.function).into()),
+                TokenType::ParenthesisBlock => cssparser::Token::ParenthesisBlock,
+                TokenType::SquareBracketBlock => cssparser::Token::SquareBracketBlock,
+                TokenType::CurlyBracketBlock => cssparser::Token::CurlyBracketBlock,
+                TokenType::BadUrl => cssparser::Token::BadUrl(text(&v.bad_url).into()),
+                TokenType::BadString => cssparser::Token::BadString(text(&v.bad_string).into()),
+                TokenType::CloseParenthesis => cssparser::Token::CloseParenthesis,
+                TokenType::CloseSquareBracket => cssparser::Token::CloseSquareBracket,
+                TokenType::CloseCurlyBracket => cssparser::Token::CloseCurlyBracket,
+            }
         }
     }
 }
 
 impl TokenValue {
-    pub fn new(value: Value) -> Self {
-        Self(value)
+    /// # Safety
+    /// `value` must have been created by the `Value` constructor for `token_type`
+    /// (`Value::empty` for types without a value), as that is the field that will be dropped.
+    pub unsafe fn new(token_type: TokenType, value: Value) -> Self {
+        Self { token_type, value }
     }
 
-    fn pull_str(s: &std::mem::ManuallyDrop<repr_c::String>) -> repr_c::String {
-        (**s).clone()
+    /// The type of token this is the value of, which decides the active field of the union.
+    pub fn token_type(&self) -> TokenType {
+        self.token_type
+    }
+
+    /// Clones a string field, `field` is only called when `token_type` is the active one.
+    ///
+    /// Asking for the value of another token type gives an empty string rather than reading the wrong field.
+    fn pull_str(&self, token_type: TokenType, field: impl FnOnce(&Value) -> &ManuallyDrop<cstr>) -> cstr {
+        if self.token_type != token_type {
+            return String::new().into();
+        }
+        (**field(&self.value)).clone()
     }
 
     pub fn get_ident(&self) -> cstr {
-        Self::pull_str(unsafe { &self.0.ident })
+        self.pull_str(TokenType::Ident, |v| unsafe { &v.ident })
     }
 
     pub fn get_at_keyword(&self) -> cstr {
-        Self::pull_str(unsafe { &self.0.at_keyword })
+        self.pull_str(TokenType::AtKeyword, |v| unsafe { &v.at_keyword })
     }
 
     pub fn get_percentage(&self) -> Percentage {
-        let value = unsafe { &self.0.percentage };
+        if self.token_type != TokenType::Percentage {
+            return Percentage { has_sign: false, unit_value: 0.0, int_value: None };
+        }
+        let value = unsafe { &self.value.percentage };
         Percentage {
             has_sign: value.has_sign,
             unit_value: value.unit_value,
-            int_value: value.int_value.as_ref().map(|v| (*v).clone()),
+            int_value: value.int_value.clone(),
         }
     }
 
     pub fn get_dimension(&self) -> Dimension {
-        let value = unsafe { &self.0.dimension };
+        if self.token_type != TokenType::Dimension {
+            return Dimension { has_sign: false, value: 0.0, int_value: None, unit: String::new().into() };
+        }
+        let value = unsafe { &self.value.dimension };
         Dimension {
             has_sign: value.has_sign,
             value: value.value,
-            int_value: value.int_value.as_ref().map(|v| (*v).clone()),
+            int_value: value.int_value.clone(),
             unit: value.unit.clone(),
         }
     }
 
     pub fn get_number(&self) -> Number {
-        let value = unsafe { &self.0.number };
+        if self.token_type != TokenType::Number {
+            return Number { has_sign: false, value: 0.0, int_value: None };
+        }
+        let value = unsafe { &self.value.number };
         Number {
             has_sign: value.has_sign,
             value: value.value,
-            int_value: value.int_value.as_ref().map(|v| (*v).clone()),
+            int_value: value.int_value.clone(),
         }
     }
 
     pub fn get_whitespace(&self) -> cstr {
-        Self::pull_str(unsafe { &self.0.whitespace })
+        self.pull_str(TokenType::WhiteSpace, |v| unsafe { &v.whitespace })
     }
 
     pub fn get_comment(&self) -> cstr {
-        Self::pull_str(unsafe { &self.0.comment })
+        self.pull_str(TokenType::Comment, |v| unsafe { &v.comment })
     }
 
     pub fn get_function(&self) -> cstr {
-        Self::pull_str(unsafe { &self.0.function })
+        self.pull_str(TokenType::Function, |v| unsafe { &v.function })
     }
 
     pub fn get_bad_string(&self) -> cstr {
-        Self::pull_str(unsafe { &self.0.bad_string })
+        self.pull_str(TokenType::BadString, |v| unsafe { &v.bad_string })
     }
 
     pub fn get_bad_url(&self) -> cstr {
-        Self::pull_str(unsafe { &self.0.bad_url })
+        self.pull_str(TokenType::BadUrl, |v| unsafe { &v.bad_url })
+    }
+
+    /// The delimiter as a Unicode scalar value, `0` for other token types.
+    pub fn get_delim(&self) -> u32 {
+        if self.token_type != TokenType::Delim {
+            return 0;
+        }
+        unsafe { self.value.delim }
+    }
+
+    pub fn get_delim_char(&self) -> char {
+        char::from_u32(self.get_delim()).unwrap_or_default()
     }
 
-    pub fn get_delim(&self) -> c_char {
-        unsafe { self.0.delim }
+    /// The delimiter encoded as UTF-8, empty for other token types.
+    pub fn get_delim_utf8(&self) -> cstr {
+        if self.token_type != TokenType::Delim {
+            return String::new().into();
+        }
+        self.get_delim_char().to_string().into()
     }
 
     pub fn get_hash(&self) -> cstr {
-        Self::pull_str(unsafe { &self.0.hash })
+        self.pull_str(TokenType::Hash, |v| unsafe { &v.hash })
     }
 
     pub fn get_id_hash(&self) -> cstr {
-        Self::pull_str(unsafe { &self.0.id_hash })
+        self.pull_str(TokenType::IDHash, |v| unsafe { &v.id_hash })
     }
 
     pub fn get_quoted_string(&self) -> cstr {
-        Self::pull_str(unsafe { &self.0.quoted_string })
+        self.pull_str(TokenType::QuotedString, |v| unsafe { &v.quoted_string })
     }
 
     pub fn get_unquoted_url(&self) -> cstr {
-        Self::pull_str(unsafe { &self.0.unquoted_url })
+        self.pull_str(TokenType::UnquotedUrl, |v| unsafe { &v.unquoted_url })
     }
 
     pub fn is_empty(&self) -> bool {
-        unsafe { self.0.empty == () }
+        !matches!(
+            self.token_type,
+            TokenType::Ident | TokenType::AtKeyword | TokenType::Hash | TokenType::IDHash
+            | TokenType::QuotedString | TokenType::UnquotedUrl | TokenType::Comment | TokenType::Function
+            | TokenType::Percentage | TokenType::Dimension | TokenType::Number | TokenType::WhiteSpace
+            | TokenType::BadString | TokenType::BadUrl | TokenType::Delim
+        )
     }
 }
 
+impl Drop for TokenValue {
+    fn drop(&mut self) {
+        // only the field matching the token type was ever initialized
+        unsafe {
+            match self.token_type {
+                TokenType::Ident => ManuallyDrop::drop(&mut self.value.ident),
+                TokenType::AtKeyword => ManuallyDrop::drop(&mut self.value.at_keyword),
+                TokenType::Hash => ManuallyDrop::drop(&mut self.value.hash),
+                TokenType::IDHash => ManuallyDrop::drop(&mut self.value.id_hash),
+                TokenType::QuotedString => ManuallyDrop::drop(&mut self.value.quoted_string),
+                TokenType::UnquotedUrl => ManuallyDrop::drop(&mut self.value.unquoted_url),
+                TokenType::Comment => ManuallyDrop::drop(&mut self.value.comment),
+                TokenType::Function => ManuallyDrop::drop(&mut self.value.function),
+                TokenType::Percentage => ManuallyDrop::drop(&mut self.value.percentage),
+                TokenType::Dimension => ManuallyDrop::drop(&mut self.value.dimension),
+                TokenType::Number => ManuallyDrop::drop(&mut self.value.number),
+                TokenType::WhiteSpace => ManuallyDrop::drop(&mut self.value.whitespace),
+                TokenType::BadString => ManuallyDrop::drop(&mut self.value.bad_string),
+                TokenType::BadUrl => ManuallyDrop::drop(&mut self.value.bad_url),
+                _ => {},
+            }
+        }
+    }
+}
+
+/// Frees a string returned by one of the value getters.
+#[safer_ffi::ffi_export]
+pub fn free_string(string: cstr) {
+    drop(string);
+}
+
+/// Frees a value returned by `percentage`.
+#[safer_ffi::ffi_export]
+pub fn free_percentage(percentage: Percentage) {
+    drop(percentage);
+}
+
+/// Frees a value returned by `dimension`.
+#[safer_ffi::ffi_export]
+pub fn free_dimension(dimension: Dimension) {
+    drop(dimension);
+}
+
+/// Frees a value returned by `number`.
+#[safer_ffi::ffi_export]
+pub fn free_number(number: Number) {
+    drop(number);
+}
+
 #[repr(C)]
 pub union Value {
     /// The value of an [`Ident`](TokenType::Ident) token.
-    ident: std::mem::ManuallyDrop<cstr>,
+    ident: ManuallyDrop<cstr>,
 
     /// The value of an [`AtKeyword`](TokenType::AtKeyword) token.
-    at_keyword: std::mem::ManuallyDrop<cstr>,
+    at_keyword: ManuallyDrop<cstr>,
 
     /// The value of a [`Hash`](TokenType::Hash) token.
-    hash: std::mem::ManuallyDrop<cstr>,
+    hash: ManuallyDrop<cstr>,
 
     /// The value of a [`QuotedString`](TokenType::QuotedString) token.
-    quoted_string: std::mem::ManuallyDrop<cstr>,
+    quoted_string: ManuallyDrop<cstr>,
 
     /// The value of a [`UnquotedUrl`](TokenType::UnquotedUrl) token.
-    unquoted_url: std::mem::ManuallyDrop<cstr>,
+    unquoted_url: ManuallyDrop<cstr>,
 
     /// The value of a [`Comment`](TokenType::Comment) token.
-    comment: std::mem::ManuallyDrop<cstr>,
+    comment: ManuallyDrop<cstr>,
 
     /// The value of a [`Function`](TokenType::Function) token.
-    function: std::mem::ManuallyDrop<cstr>,
+    function: ManuallyDrop<cstr>,
 
     /// The value of a [`Percentage`](TokenType::Percentage) token.
-    percentage: std::mem::ManuallyDrop<Percentage>,
+    percentage: ManuallyDrop<Percentage>,
 
     /// The value of a [`Dimension`](TokenType::Dimension) token.
-    dimension: std::mem::ManuallyDrop<Dimension>,
+    dimension: ManuallyDrop<Dimension>,
 
     /// The value of a [`Number`](TokenType::Number) token.
-    number: std::mem::ManuallyDrop<Number>,
+    number: ManuallyDrop<Number>,
 
     /// The value of a [`Whitespace`](TokenType::WhiteSpace) token
-    whitespace: std::mem::ManuallyDrop<cstr>,
+    whitespace: ManuallyDrop<cstr>,
 
     /// The value of a [`BadString`](TokenType::BadString) token.
-    bad_string: std::mem::ManuallyDrop<cstr>,
+    bad_string: ManuallyDrop<cstr>,
 
     /// The value of a [`BadUrl`](TokenType::BadUrl) token.
-    bad_url: std::mem::ManuallyDrop<cstr>,
+    bad_url: ManuallyDrop<cstr>,
 
     /// The value of a [`Delim`](TokenType::Delim) token.
-    delim: c_char,
+    /// It is always a valid `char`, stored as a `u32` so C can read it.
+    delim: u32,
 
     /// The value of a [`IDHash`](TokenType::IDHash) token.
-    id_hash: std::mem::ManuallyDrop<cstr>,
+    id_hash: ManuallyDrop<cstr>,
 
     /// The value of any token that does not have a value.
     empty: (),
@@ -259,63 +419,63 @@ impl Value {
     }
 
     pub fn new_ident(ident: cstr) -> Self {
-        Self { ident: std::mem::ManuallyDrop::new(ident) }
+        Self { ident: ManuallyDrop::new(ident) }
     }
 
     pub fn new_at_keyword(at_keyword: cstr) -> Self {
-        Self { at_keyword: std::mem::ManuallyDrop::new(at_keyword) }
+        Self { at_keyword: ManuallyDrop::new(at_keyword) }
     }
 
     pub fn new_hash(hash: cstr) -> Self {
-        Self { hash: std::mem::ManuallyDrop::new(hash) }
+        Self { hash: ManuallyDrop::new(hash) }
     }
 
     pub fn new_quoted_string(quoted_string: cstr) -> Self {
-        Self { quoted_string: std::mem::ManuallyDrop::new(quoted_string) }
+        Self { quoted_string: ManuallyDrop::new(quoted_string) }
     }
 
     pub fn new_unquoted_url(unquoted_url: cstr) -> Self {
-        Self { unquoted_url: std::mem::ManuallyDrop::new(unquoted_url) }
+        Self { unquoted_url: ManuallyDrop::new(unquoted_url) }
     }
 
     pub fn new_comment(comment: cstr) -> Self {
-        Self { comment: std::mem::ManuallyDrop::new(comment) }
+        Self { comment: ManuallyDrop::new(comment) }
     }
 
     pub fn new_function(function: cstr) -> Self {
-        Self { function: std::mem::ManuallyDrop::new(function) }
+        Self { function: ManuallyDrop::new(function) }
     }
 
     pub fn new_percentage(percentage: Percentage) -> Self {
-        Self { percentage: std::mem::ManuallyDrop::new(percentage) }
+        Self { percentage: ManuallyDrop::new(percentage) }
     }
 
     pub fn new_dimension(dimension: Dimension) -> Self {
-        Self { dimension: std::mem::ManuallyDrop::new(dimension) }
+        Self { dimension: ManuallyDrop::new(dimension) }
     }
 
     pub fn new_number(number: Number) -> Self {
-        Self { number: std::mem::ManuallyDrop::new(number) }
+        Self { number: ManuallyDrop::new(number) }
     }
 
     pub fn new_whitespace(whitespace: cstr) -> Self {
-        Self { whitespace: std::mem::ManuallyDrop::new(whitespace) }
+        Self { whitespace: ManuallyDrop::new(whitespace) }
     }
 
     pub fn new_bad_string(bad_string: cstr) -> Self {
-        Self { bad_string: std::mem::ManuallyDrop::new(bad_string) }
+        Self { bad_string: ManuallyDrop::new(bad_string) }
     }
 
     pub fn new_bad_url(bad_url: cstr) -> Self {
-        Self { bad_url: std::mem::ManuallyDrop::new(bad_url) }
+        Self { bad_url: ManuallyDrop::new(bad_url) }
     }
 
-    pub fn new_delim(delim: c_char) -> Self {
-        Self { delim }
+    pub fn new_delim(delim: char) -> Self {
+        Self { delim: delim as u32 }
     }
 
     pub fn new_id_hash(id_hash: cstr) -> Self {
-        Self { id_hash: std::mem::ManuallyDrop::new(id_hash) }
+        Self { id_hash: ManuallyDrop::new(id_hash) }
     }
 }
 
@@ -0,0 +1,134 @@
+#![allow(nonstandard_style)]
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{repr_c, Out}};
+
+use crate::{error::{catch_panic, finish, Error, ErrorKind, Status}, location::SourceLocation, Token};
+
+/// An incremental tokenizer over a copy of some input.
+///
+/// Unlike `parse_css`, every token is produced (including whitespace and comments)
+/// and blocks are not descended into by a nested parser, so the closing token of a block
+/// is the one actually found in the input.
+/// Only the token being returned is ever held in memory.
+#[derive_ReprC]
+#[repr(opaque)]
+pub struct Tokenizer {
+    /// The single parser input over `source`, it keeps the position, line and column between calls.
+    /// Declared before `source` so that it is dropped first.
+    input: cssparser::ParserInput<'static>,
+    /// Only kept alive for `input`, it is never modified so its buffer does not move.
+    _source: String,
+    /// Where the next token starts.
+    position: SourceLocation,
+}
+
+impl Tokenizer {
+    pub fn new(source: String) -> Self {
+        // SAFETY: the text lives in the heap buffer of `source`, which outlives `input` (see the fields)
+        let text: &'static str = unsafe { &*(source.as_str() as *const str) };
+        Self {
+            input: cssparser::ParserInput::new(text),
+            _source: source,
+            position: SourceLocation { start: 0, end: 0, line: 1, column: 1 },
+        }
+    }
+
+    /// Tokenizes the token at the current position and moves past it.
+    ///
+    /// A `cssparser::Parser` only enters blocks through a closure and skips the content of a block
+    /// it returned the opening token of, so the `Parser` is only a view over `input` for one token.
+    /// Creating it costs nothing: the tokenizer state stays in `input`,
+    /// at the top level blocks are not consumed and closing tokens are returned as-is.
+    fn lex(&mut self) -> Option<Token> {
+        let mut parser = cssparser::Parser::new(&mut self.input);
+        let start = parser.position();
+        let start_location = parser.current_source_location();
+        let token = parser.next_including_whitespace_and_comments().ok()?.clone();
+        let end = parser.position();
+        self.position = SourceLocation::new(end, end, parser.current_source_location());
+        Some(Token::from(token).at(SourceLocation::new(start, end, start_location)))
+    }
+
+    pub fn peek(&mut self) -> Option<Token> {
+        let state = cssparser::Parser::new(&mut self.input).state();
+        let position = self.position;
+        let token = self.lex();
+        // the parser input caches the token, so the following `next` does not tokenize it again
+        cssparser::Parser::new(&mut self.input).reset(&state);
+        self.position = position;
+        token
+    }
+
+    /// Where the next token starts.
+    pub fn position(&self) -> SourceLocation {
+        self.position
+    }
+}
+
+impl Iterator for Tokenizer {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        self.lex()
+    }
+}
+
+/// Writes the token `lex` returns to `token` and whether there was one to `has_token`.
+fn write_token(tokenizer: &mut Tokenizer, lex: fn(&mut Tokenizer) -> Option<Token>, token: Out<'_, Token>, has_token: Out<'_, bool>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| Ok(lex(tokenizer))).map(|next| match next {
+        Some(next) => {
+            token.write(next);
+            true
+        },
+        None => false,
+    });
+    finish(result, has_token, || false, error)
+}
+
+/// Creates a tokenizer over a copy of the `len` bytes starting at `input`.
+///
+/// Invalid UTF-8 is replaced the same way `parse_css_bytes` does.
+/// On failure `tokenizer` is set to null, otherwise it must be released with `tokenizer_free`.
+#[ffi_export]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn tokenizer_new(input: *const u8, len: usize, tokenizer: Out<'_, Option<repr_c::Box<Tokenizer>>>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        if len == 0 {
+            return Ok(Some(Box::new(Tokenizer::new(String::new())).into()));
+        }
+        if input.is_null() {
+            return Err(Error::new(ErrorKind::NullPointer, "input is null"));
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(input, len) };
+        let source = String::from_utf8_lossy(bytes).into_owned();
+        Ok(Some(Box::new(Tokenizer::new(source)).into()))
+    });
+
+    finish(result, tokenizer, || None, error)
+}
+
+/// Advances the tokenizer, writing the next token to `token` and `true` to `has_token`.
+///
+/// At the end of the input `has_token` is `false` and `token` is not written to, the status is still `STATUS_OK`.
+/// A caught panic returns `STATUS_PANIC`, also without a token.
+/// A token written by this function must be released with `free_token`.
+#[ffi_export]
+pub fn tokenizer_next(tokenizer: &mut Tokenizer, token: Out<'_, Token>, has_token: Out<'_, bool>, error: Option<Out<'_, Error>>) -> Status {
+    write_token(tokenizer, Tokenizer::next, token, has_token, error)
+}
+
+/// Same as `tokenizer_next`, but does not advance the tokenizer.
+#[ffi_export]
+pub fn tokenizer_peek(tokenizer: &mut Tokenizer, token: Out<'_, Token>, has_token: Out<'_, bool>, error: Option<Out<'_, Error>>) -> Status {
+    write_token(tokenizer, Tokenizer::peek, token, has_token, error)
+}
+
+/// The location the next token will start at, `start` and `end` are equal.
+#[ffi_export]
+pub fn tokenizer_position(tokenizer: &Tokenizer) -> SourceLocation {
+    tokenizer.position()
+}
+
+#[ffi_export]
+pub fn tokenizer_free(tokenizer: repr_c::Box<Tokenizer>) {
+    drop(tokenizer);
+}
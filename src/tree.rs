@@ -0,0 +1,123 @@
+#![allow(nonstandard_style)]
+use std::convert::Infallible;
+
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{repr_c, Out}};
+
+use crate::{error::{catch_panic, finish, Error, Status}, input_str, layout::breadth_first, location::SourceLocation, Token};
+
+/// A component value, as described in [CSS Syntax §5](https://drafts.csswg.org/css-syntax/#component-value).
+///
+/// `Function`, `ParenthesisBlock`, `SquareBracketBlock` and `CurlyBracketBlock` tokens own the
+/// component values inside of them as children, no closing tokens are produced.
+/// Every other token is a leaf with no children.
+///
+/// The children of a node are `tree.nodes.ptr[first_child .. first_child + child_count]`
+/// (a node cannot hold a list of itself over FFI, so they live in the tree's node list).
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct Node {
+    /// The token, for blocks and functions this is the opening token.
+    pub token: Token,
+    /// Index of the first child in the tree's node list.
+    pub first_child: usize,
+    /// The number of component values inside of a block or function.
+    pub child_count: usize,
+    /// Where the closing token of a block or function is.
+    /// It is empty when the block was left open at the end of the input, and all-zero for leaves.
+    pub closing: SourceLocation,
+}
+
+/// The component values of a stylesheet, free it with `free_tree`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug)]
+pub struct TokenTree {
+    /// Every node of the tree, the top-level component values are `nodes.ptr[0 .. roots]`.
+    pub nodes: repr_c::Vec<Node>,
+    pub roots: usize,
+}
+
+/// A component value that owns its children, before it is laid out in a [`TokenTree`].
+#[derive(Debug)]
+pub struct ComponentValue {
+    pub token: Token,
+    pub children: Vec<ComponentValue>,
+    pub closing: SourceLocation,
+}
+
+pub fn closing_char(token: &cssparser::Token) -> Option<char> {
+    match token {
+        cssparser::Token::Function(_) | cssparser::Token::ParenthesisBlock => Some(')'),
+        cssparser::Token::SquareBracketBlock => Some(']'),
+        cssparser::Token::CurlyBracketBlock => Some('}'),
+        _ => None,
+    }
+}
+
+/// Parses the component values of the current block (or the whole input at the top level).
+///
+/// Like [`parse`](crate::parse) whitespace and comments are skipped.
+pub fn parse_tree<'i>(parser: &mut cssparser::Parser<'i, '_>) -> Result<Vec<ComponentValue>, cssparser::ParseError<'i, Infallible>> {
+    let mut values = Vec::new();
+    loop {
+        parser.skip_whitespace();
+        let start = parser.position();
+        let start_location = parser.current_source_location();
+        let Ok(token) = parser.next().cloned() else { break };
+        let block_start = parser.position();
+
+        let (children, closing) = match closing_char(&token) {
+            Some(closing) => {
+                let children = parser.parse_nested_block(parse_tree)?;
+                (children, SourceLocation::closing(parser, block_start, closing))
+            },
+            None => (Vec::new(), SourceLocation::default()),
+        };
+
+        values.push(ComponentValue {
+            token: Token::from(token).at(SourceLocation::new(start, block_start, start_location)),
+            children,
+            closing,
+        });
+    }
+    Ok(values)
+}
+
+impl From<Vec<ComponentValue>> for TokenTree {
+    /// Lays the values out breadth first, so the children of every node are next to each other.
+    fn from(values: Vec<ComponentValue>) -> Self {
+        let roots = values.len();
+        let nodes = breadth_first(values, |value| std::mem::take(&mut value.children))
+            .into_iter()
+            .map(|(value, first_child, child_count)| Node { token: value.token, first_child, child_count, closing: value.closing })
+            .collect::<Vec<_>>();
+        Self { nodes: nodes.into(), roots }
+    }
+}
+
+fn tree(input: &str) -> Result<TokenTree, Error> {
+    let mut input = cssparser::ParserInput::new(input);
+    let mut parser = cssparser::Parser::new(&mut input);
+
+    parse_tree(&mut parser)
+        .map(Into::into)
+        .map_err(|e| Error::from_parse_error(&e, parser.position()))
+}
+
+/// Parses the NUL-terminated string `input` into a tree of component values written to `tree`.
+///
+/// Out-params behave like they do for `parse_css`, `tree` must be released with `free_tree`.
+#[ffi_export]
+pub fn parse_css_tree(input: *const safer_ffi::c_char, tree: Out<'_, TokenTree>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        self::tree(input_str(input)?)
+    });
+
+    finish(result, tree, || TokenTree { nodes: Vec::new().into(), roots: 0 }, error)
+}
+
+#[ffi_export]
+pub fn free_tree(tree: TokenTree) {
+    drop(tree);
+}
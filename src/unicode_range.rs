@@ -0,0 +1,63 @@
+#![allow(nonstandard_style)]
+use std::convert::Infallible;
+
+use cssparser::{ParseError, Parser};
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{c_slice, repr_c, Out}};
+
+use crate::{error::{catch_panic, finish, Error, Status}, input_str, location::SourceLocation};
+
+/// The code points from `start` to `end`, both included, `start` is never greater than `end`.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnicodeRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl UnicodeRange {
+    pub fn contains(&self, codepoint: u32) -> bool {
+        (self.start..=self.end).contains(&codepoint)
+    }
+}
+
+/// Parses `input` as the comma-separated ranges of a `unicode-range` descriptor (`U+0025-00FF, u+4??`).
+///
+/// The error of an invalid range is located at its `U`.
+pub fn unicode_ranges(input: &str) -> Result<Vec<UnicodeRange>, Error> {
+    let mut parser_input = cssparser::ParserInput::new(input);
+    let mut parser = Parser::new(&mut parser_input);
+    let mut start = (parser.position(), parser.current_source_location());
+    parser.parse_entirely(|input| input.parse_comma_separated(|input| {
+        input.skip_whitespace();
+        start = (input.position(), input.current_source_location());
+        let range = cssparser::UnicodeRange::parse(input)?;
+        Ok(UnicodeRange { start: range.start, end: range.end })
+    }))
+    .map_err(|e: ParseError<'_, Infallible>| Error::from_parse_error(&e, start.0).at(SourceLocation::new(start.0, start.0, start.1)))
+}
+
+/// Parses the NUL-terminated string `input`, such as the value of a `unicode-range` descriptor, into `ranges`.
+///
+/// The ranges are in the order they were written and may overlap.
+/// The value must be given as text, as the tokens `parse_css` returns do not keep what a range is made of (`U+00??` has leading zeros).
+/// Out-params behave like they do for `parse_css`, `ranges` must be released with `free_unicode_ranges`.
+#[ffi_export]
+pub fn parse_unicode_range(input: *const safer_ffi::c_char, ranges: Out<'_, repr_c::Vec<UnicodeRange>>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        unicode_ranges(input_str(input)?).map(Into::into)
+    });
+
+    finish(result, ranges, || Vec::new().into(), error)
+}
+
+/// Whether `codepoint` is in any of `ranges`.
+#[ffi_export]
+pub fn unicode_range_contains(ranges: c_slice::Ref<'_, UnicodeRange>, codepoint: u32) -> bool {
+    ranges.as_slice().iter().any(|range| range.contains(codepoint))
+}
+
+#[ffi_export]
+pub fn free_unicode_ranges(ranges: repr_c::Vec<UnicodeRange>) {
+    drop(ranges);
+}
@@ -0,0 +1,176 @@
+#![allow(nonstandard_style)]
+use safer_ffi::{derive_ReprC, ffi_export, prelude::{repr_c, Out}};
+
+use crate::{error::{catch_panic, finish, Error, ErrorKind, Status}, location::SourceLocation, token_types::TokenType, walk};
+
+/// A `(ptr, len)` view of UTF-8 text, it is *not* NUL-terminated.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct StrView {
+    pub ptr: *const u8,
+    pub len: usize,
+    /// `false` when the text points into the caller's input,
+    /// `true` when it lives in memory owned by the [`TokenViews`] (or `TokenList`) it came from.
+    pub owned: bool,
+}
+
+impl StrView {
+    pub fn empty() -> Self {
+        Self { ptr: std::ptr::NonNull::dangling().as_ptr(), len: 0, owned: false }
+    }
+
+    /// A view of `text`, which the caller keeps alive for as long as the view is used.
+    pub fn borrowed(text: &str) -> Self {
+        Self { ptr: text.as_ptr(), len: text.len(), owned: false }
+    }
+
+    /// # Safety
+    /// The memory `ptr` points to (the input or the arena) must still be alive.
+    pub unsafe fn as_str<'a>(&self) -> &'a str {
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(self.ptr, self.len))
+    }
+}
+
+/// A token whose text is a view rather than an owned copy.
+///
+/// `text` holds the string value of the token (for a `Dimension` that is its unit, for a `Delim` the delimiter itself)
+/// and is empty for tokens without one. Numeric tokens use the remaining fields, like `Number_t` does.
+#[derive_ReprC]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TokenView {
+    pub token_type: TokenType,
+    pub text: StrView,
+    /// The value of a `Number` or `Dimension`, or the unit value of a `Percentage` (`0.5` for `50%`).
+    pub value: f32,
+    /// Only meaningful when `has_int_value` is set.
+    pub int_value: i32,
+    pub has_int_value: bool,
+    pub has_sign: bool,
+    pub location: SourceLocation,
+}
+
+/// Storage for strings that did not appear verbatim in the input.
+#[derive_ReprC]
+#[repr(opaque)]
+#[derive(Default)]
+pub struct ViewArena(Vec<String>);
+
+/// The result of `parse_css_views`, free it with `free_token_views`.
+///
+/// Views that are not `owned` borrow the input, which must outlive this list.
+#[derive_ReprC]
+#[repr(C)]
+pub struct TokenViews {
+    pub tokens: repr_c::Vec<TokenView>,
+    pub arena: repr_c::Box<ViewArena>,
+}
+
+impl ViewArena {
+    /// Views `text` in place when it lies inside of `input`, otherwise copies it into the arena.
+    fn view(&mut self, input: &str, text: &str) -> StrView {
+        let range = input.as_bytes().as_ptr_range();
+        if range.contains(&text.as_ptr()) || (text.is_empty() && text.as_ptr() == range.end) {
+            return StrView { ptr: text.as_ptr(), len: text.len(), owned: false };
+        }
+        let owned = text.to_string();
+        let view = StrView { ptr: owned.as_ptr(), len: owned.len(), owned: true };
+        // moving the `String` does not move its buffer, so the view stays valid
+        self.0.push(owned);
+        view
+    }
+}
+
+impl TokenView {
+    /// Builds the view of `token`, `text_view` turns the token's text into a [`StrView`].
+    ///
+    /// `source` is the input `token` was parsed from, which a `Delim` is viewed in.
+    pub fn new(token: &cssparser::Token, location: SourceLocation, source: &str, text_view: &mut impl FnMut(&str) -> StrView) -> Self {
+        let mut view = Self {
+            token_type: token.into(),
+            text: StrView::empty(),
+            value: 0.0,
+            int_value: 0,
+            has_int_value: false,
+            has_sign: false,
+            location,
+        };
+        match token {
+            cssparser::Token::Ident(text)
+            | cssparser::Token::AtKeyword(text)
+            | cssparser::Token::Hash(text)
+            | cssparser::Token::IDHash(text)
+            | cssparser::Token::QuotedString(text)
+            | cssparser::Token::UnquotedUrl(text)
+            | cssparser::Token::Function(text)
+            | cssparser::Token::BadUrl(text)
+            | cssparser::Token::BadString(text) => view.text = text_view(text),
+            cssparser::Token::Comment(text) | cssparser::Token::WhiteSpace(text) => view.text = text_view(text),
+            cssparser::Token::Delim(_) => view.text = text_view(&source[location.start..location.end]),
+            cssparser::Token::Number { has_sign, value, int_value } => {
+                view.has_sign = *has_sign;
+                view.value = *value;
+                view.set_int_value(*int_value);
+            },
+            cssparser::Token::Percentage { has_sign, unit_value, int_value } => {
+                view.has_sign = *has_sign;
+                view.value = *unit_value;
+                view.set_int_value(*int_value);
+            },
+            cssparser::Token::Dimension { has_sign, value, int_value, unit } => {
+                view.has_sign = *has_sign;
+                view.value = *value;
+                view.set_int_value(*int_value);
+                view.text = text_view(unit);
+            },
+            _ => {},
+        }
+        view
+    }
+
+    fn set_int_value(&mut self, int_value: Option<i32>) {
+        self.has_int_value = int_value.is_some();
+        self.int_value = int_value.unwrap_or_default();
+    }
+}
+
+/// Tokenizes `input` like `parse_css` does, but without copying any text that appears verbatim in it.
+pub fn views(input: &str) -> Result<TokenViews, Error> {
+    let mut arena = ViewArena::default();
+    let mut tokens = Vec::new();
+
+    let mut parser_input = cssparser::ParserInput::new(input);
+    let mut parser = cssparser::Parser::new(&mut parser_input);
+    walk::<std::convert::Infallible>(&mut parser, &mut |token, location| {
+        tokens.push(TokenView::new(token, location, input, &mut |text| arena.view(input, text)));
+    }).map_err(|e| Error::from_parse_error(&e, parser.position()))?;
+
+    Ok(TokenViews { tokens: tokens.into(), arena: Box::new(arena).into() })
+}
+
+/// Parses the `len` bytes starting at `input` into token views written to `views`.
+///
+/// The input must be valid UTF-8 and stay alive (and unmodified) until `views` is freed with `free_token_views`.
+/// Out-params otherwise behave like they do for `parse_css`.
+#[ffi_export]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn parse_css_views(input: *const u8, len: usize, views: Out<'_, TokenViews>, error: Option<Out<'_, Error>>) -> Status {
+    let result = catch_panic(|| {
+        if len == 0 {
+            return self::views("");
+        }
+        if input.is_null() {
+            return Err(Error::new(ErrorKind::NullPointer, "input is null"));
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(input, len) };
+        self::views(std::str::from_utf8(bytes).map_err(Error::invalid_utf8)?)
+    });
+
+    finish(result, views, || TokenViews { tokens: Vec::new().into(), arena: Box::new(ViewArena::default()).into() }, error)
+}
+
+#[ffi_export]
+pub fn free_token_views(views: TokenViews) {
+    drop(views);
+}
@@ -53,11 +53,19 @@ int main(void) {
     //     }
     // )";
 
-    Vec_Token_t tokens = parse_css(css.c_str());
-    if(tokens.ptr == nullptr) {
-        std::cout << "Error: Failed to parse tokens array" << std::endl;
+    Vec_Token_t tokens;
+    Error_t error;
+    if(parse_css(css.c_str(), &tokens, &error) != STATUS_OK) {
+        std::cout
+        << "Error: Failed to parse tokens array: "
+        << std::string((const char*)error.message.ptr, error.message.len)
+        << " (line " << error.location.line << ", column " << error.location.column << ")"
+        << std::endl;
+        free_error(error);
+        free_tokens(tokens);
         return 1;
     }
+    free_error(error);
     std::cout << "Tokens array recieved successfully\n" << std::endl;
     std::cout << "Number of tokens: " << tokens.len << std::endl;
 
@@ -83,8 +91,9 @@ void showcase(void) {
     var-property: var(--custom-property);
 })";
     std::cout << "CSS:" << std::endl << css << std::endl << std::endl;
-    // get the tokens array
-    Vec_Token_t tokens = parse_css(css.c_str());
+    // get the tokens array, the error out-param is optional
+    Vec_Token_t tokens;
+    parse_css(css.c_str(), &tokens, nullptr);
 
     // get the first token
     Token_t token = tokens.ptr[1];
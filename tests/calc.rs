@@ -0,0 +1,168 @@
+//! Parsing and evaluating `calc()`, `min()`, `max()` and `clamp()`.
+mod common;
+
+use common::{call, call_str};
+use cssparser_fii::{calc::*, error::{Error, ErrorKind, Status}, free_tokens, parse_css, stylesheet::{declaration_list, free_declaration_list}};
+
+fn parse(css: &str) -> (Status, CalcExpression, Error) {
+    call_str(css, parse_calc)
+}
+
+fn evaluate(css: &str, context: &CalcContext) -> (CalcType, bool, f32) {
+    let (status, expression, error) = parse(css);
+    assert_eq!(status, Status::Ok, "{css}: {}", &*error.message);
+    let root = &expression.nodes[0];
+    let result = (root.value_type, root.has_percentage, calc_evaluate(&expression, context));
+    free_calc_expression(expression);
+    result
+}
+
+fn error(css: &str) -> (ErrorKind, String, usize) {
+    let (status, expression, error) = parse(css);
+    assert_ne!(status, Status::Ok, "{css}");
+    assert!(expression.nodes.is_empty());
+    free_calc_expression(expression);
+    (error.kind, error.message.to_string(), error.location.start)
+}
+
+#[test]
+fn structure() {
+    let (_, expression, _) = parse("calc(100% - 2 * 8px)");
+    let nodes: Vec<_> = expression.nodes.iter().map(|node| (node.node_type, node.value_type, node.has_percentage, node.first_child, node.child_count)).collect();
+    assert_eq!(nodes, [
+        (CalcNodeType::Sum, CalcType::Length, true, 1, 2),
+        (CalcNodeType::Value, CalcType::Percentage, true, 0, 0),
+        (CalcNodeType::Negate, CalcType::Length, false, 3, 1),
+        (CalcNodeType::Product, CalcType::Length, false, 4, 2),
+        (CalcNodeType::Value, CalcType::Number, false, 0, 0),
+        (CalcNodeType::Value, CalcType::Length, false, 0, 0),
+    ]);
+    assert_eq!((expression.nodes[1].value, expression.nodes[5].value, expression.nodes[5].unit.to_string()), (100.0, 8.0, "px".to_string()));
+    let locations: Vec<_> = expression.nodes.iter().map(|node| (node.location.start, node.location.end)).collect();
+    assert_eq!(locations, [(5, 19), (5, 9), (10, 19), (12, 19), (12, 13), (16, 19)]);
+    free_calc_expression(expression);
+
+    // `calc()` and parentheses only group
+    let (_, expression, _) = parse("clamp(1rem, calc((2.5vw)), MAX(10PX, 1em))");
+    let nodes: Vec<_> = expression.nodes.iter().map(|node| (node.node_type, node.unit.to_string(), node.location.start, node.location.end)).collect();
+    assert_eq!(nodes, [
+        (CalcNodeType::Clamp, String::new(), 0, 42),
+        (CalcNodeType::Value, "rem".to_string(), 6, 10),
+        (CalcNodeType::Value, "vw".to_string(), 18, 23),
+        (CalcNodeType::Max, String::new(), 27, 41),
+        (CalcNodeType::Value, "px".to_string(), 31, 35),
+        (CalcNodeType::Value, "em".to_string(), 37, 40),
+    ]);
+    free_calc_expression(expression);
+}
+
+#[test]
+fn evaluation() {
+    let context = CalcContext { percentage_basis: 500.0, ..calc_context_default() };
+    for (css, value_type, has_percentage, value) in [
+        ("calc(100% - 2 * 8px)", CalcType::Length, true, 484.0),
+        ("calc(1in - 2.54cm + 12pt)", CalcType::Length, false, 16.0),
+        ("calc((1em + 1rem) / 4)", CalcType::Length, false, 8.0),
+        ("calc(50vw - 10vh * 2 / 4)", CalcType::Length, false, 473.6),
+        ("min(100%, 40em, 1000px)", CalcType::Length, true, 500.0),
+        ("max(10px, 1ch)", CalcType::Length, false, 10.0),
+        ("clamp(1rem, 2.5vw, 2rem)", CalcType::Length, false, 25.6),
+        ("clamp(1rem, 10vw, 2rem)", CalcType::Length, false, 32.0),
+        ("clamp(20px, 1px, 10px)", CalcType::Length, false, 20.0),
+        ("calc(50% * 2 / 4)", CalcType::Percentage, true, 125.0),
+        ("calc(0.25turn - 10deg + 100grad / 2)", CalcType::Angle, false, 125.0),
+        ("calc(pi * 1rad)", CalcType::Angle, false, 180.0),
+        ("calc(1s + 250ms)", CalcType::Time, false, 1.25),
+        ("calc(3 / 2 * -2)", CalcType::Number, false, -3.0),
+        ("calc(1 + (2 - 3) * 4)", CalcType::Number, false, -3.0),
+    ] {
+        let (actual_type, actual_percentage, actual) = evaluate(css, &context);
+        assert_eq!((actual_type, actual_percentage), (value_type, has_percentage), "{css}");
+        assert!((actual - value).abs() < 1e-3, "{css} = {actual}, not {value}");
+    }
+
+    let small = CalcContext { viewport_width: 320.0, font_size: 20.0, ..context };
+    assert_eq!(evaluate("clamp(1rem, 2.5vw, 2rem)", &small).2, 16.0);
+    assert_eq!(evaluate("calc(1em + 1rem)", &small).2, 36.0);
+
+    // like in CSS, not like in Rust
+    assert_eq!(evaluate("calc(1px / 0)", &context).2, f32::INFINITY);
+    assert_eq!(evaluate("calc(-infinity * 1px)", &context).2, f32::NEG_INFINITY);
+    assert!(evaluate("min(1px, NaN * 1px)", &context).2.is_nan());
+}
+
+#[test]
+fn type_checking() {
+    for (css, message, start) in [
+        ("calc(1px + 2deg)", "cannot combine a length and an angle", 5),
+        ("calc(1 + 2px)", "cannot combine a number and a length", 5),
+        ("calc(10% + 1)", "cannot combine a percentage and a number", 5),
+        ("min(1s, 2px, 3deg)", "cannot combine a time, a length and an angle", 0),
+        ("calc(1px * 2px)", "cannot multiply a length and a length", 5),
+        ("calc(1px * 2% * 3)", "cannot multiply a length, a percentage and a number", 5),
+        ("calc(1px / 1px)", "cannot divide by a length", 9),
+        ("calc(2 * (1px + 1deg))", "cannot combine a length and an angle", 10),
+        ("calc(1fr + 1px)", "unsupported unit `fr`", 5),
+        ("clamp(1px, 2px)", "`clamp()` takes 3 arguments", 14),
+        ("calc(1px+ 2px)", "`+` must be surrounded by whitespace", 8),
+        ("calc(1px -(2px))", "`-` must be surrounded by whitespace", 9),
+    ] {
+        assert_eq!(error(css), (ErrorKind::Custom, message.to_string(), start), "{css}");
+    }
+}
+
+#[test]
+fn syntax_errors() {
+    for (css, kind, start) in [
+        ("calc(1px 2px)", ErrorKind::UnexpectedToken, 9),
+        ("calc(1px -2px)", ErrorKind::UnexpectedToken, 9),
+        ("calc(1px, 2px)", ErrorKind::UnexpectedToken, 8),
+        ("calc()", ErrorKind::EndOfInput, 5),
+        ("min(1px,)", ErrorKind::EndOfInput, 8),
+        ("calc(1px +", ErrorKind::EndOfInput, 10),
+        ("calc(auto)", ErrorKind::UnexpectedToken, 5),
+        ("calc(sin(1))", ErrorKind::UnexpectedToken, 5),
+        ("10px", ErrorKind::UnexpectedToken, 0),
+        ("calc(1px) 2px", ErrorKind::UnexpectedToken, 10),
+        ("", ErrorKind::EndOfInput, 0),
+    ] {
+        let (actual_kind, _, actual_start) = error(css);
+        assert_eq!((actual_kind, actual_start), (kind, start), "{css}");
+    }
+    // a function left open at the end of the input is closed by it
+    assert_eq!(evaluate("calc(1px + 2px", &calc_context_default()).2, 3.0);
+
+    let (status, expression, _) = call(|expression, error| parse_calc(std::ptr::null(), expression, error));
+    assert_eq!(status, Status::InvalidInput);
+    assert!(calc_evaluate(&expression, &calc_context_default()).is_nan());
+    free_calc_expression(expression);
+}
+
+#[test]
+fn from_tokens() {
+    let (status, tokens, _) = call_str("width: calc(100% - 2 * 8px); height: calc(1px -2px)", parse_css);
+    assert_eq!(status, Status::Ok);
+
+    // `parse_css` does not close functions, so a function ends with the tokens it is given
+    let (status, expression, _) = call(|expression, error| parse_calc_tokens((&tokens[2..8]).into(), expression, error));
+    assert_eq!(status, Status::Ok);
+    assert_eq!((expression.nodes[0].location.start, expression.nodes[0].location.end), (12, 26));
+    let context = CalcContext { percentage_basis: 200.0, ..calc_context_default() };
+    assert_eq!(calc_evaluate(&expression, &context), 184.0);
+    free_calc_expression(expression);
+
+    // the whitespace `parse_css` dropped is not needed, `-2px` is still a single token
+    let (status, expression, error) = call(|expression, error| parse_calc_tokens((&tokens[11..]).into(), expression, error));
+    assert_eq!(status, Status::ParseError);
+    assert_eq!((error.kind, error.location.start), (ErrorKind::UnexpectedToken, 46));
+    free_calc_expression(expression);
+    free_tokens(tokens);
+
+    // the value of a declaration keeps its whitespace
+    let list = declaration_list("margin: calc( 1px  + 2px ) ");
+    let (status, expression, _) = call(|expression, error| parse_calc_tokens((&list.declarations[0].value[..]).into(), expression, error));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(calc_evaluate(&expression, &calc_context_default()), 3.0);
+    free_calc_expression(expression);
+    free_declaration_list(list);
+}
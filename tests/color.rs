@@ -0,0 +1,140 @@
+//! Parsing every `<color>` syntax, and converting the results to sRGB.
+mod common;
+
+use common::{call, call_str};
+use cssparser_fii::{color::*, error::{ErrorKind, Status}, stylesheet::declaration_list};
+
+fn parse(css: &str) -> Result<Color, ErrorKind> {
+    let (status, color, error) = call_str(css, parse_color);
+    match status {
+        Status::Ok => Ok(color),
+        _ => Err(error.kind),
+    }
+}
+
+fn srgb(css: &str) -> [f32; 4] {
+    let color = color_to_srgb(parse(css).unwrap());
+    assert_eq!(color.space, ColorSpace::Srgb);
+    let [r, g, b] = color.components;
+    [r, g, b, color.alpha]
+}
+
+fn assert_close(css: &str, expected: [f32; 4]) {
+    let actual = srgb(css);
+    assert!(actual.iter().zip(expected).all(|(actual, expected)| (actual - expected).abs() < 0.005), "{css}: {actual:?}");
+}
+
+#[test]
+fn keywords_and_hex() {
+    assert_eq!(srgb("#f00"), [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(srgb("#0000FF80"), [0.0, 0.0, 1.0, 128.0 / 255.0]);
+    assert_eq!(srgb("RebeccaPurple"), [0.4, 0.2, 0.6, 1.0]);
+    assert_eq!(srgb("  transparent "), [0.0, 0.0, 0.0, 0.0]);
+    assert_eq!(parse("currentColor").unwrap().space, ColorSpace::CurrentColor);
+    assert_eq!(color_to_srgb(parse("currentcolor").unwrap()).space, ColorSpace::CurrentColor);
+
+    assert_eq!(parse("#ggg"), Err(ErrorKind::UnexpectedToken));
+    assert_eq!(parse("notacolor"), Err(ErrorKind::UnexpectedToken));
+    assert_eq!(parse("red blue"), Err(ErrorKind::UnexpectedToken));
+    assert_eq!(parse(""), Err(ErrorKind::EndOfInput));
+}
+
+#[test]
+fn rgb_and_hsl_syntaxes() {
+    for css in ["rgb(255, 0, 0)", "rgba(100%, 0%, 0%)", "rgb(255 0 0)", "RGB(100% 0 0%)", "rgb(300 -5 0)", "hsl(0, 100%, 50%)", "hsl(360 100 50)"] {
+        assert_eq!(srgb(css), [1.0, 0.0, 0.0, 1.0], "{css}");
+    }
+    assert_eq!(srgb("rgba(255, 0, 0, 0.5)")[3], 0.5);
+    assert_eq!(srgb("rgb(255 0 0 / 25%)")[3], 0.25);
+    assert_eq!(srgb("rgb(255 0 0 / 2)")[3], 1.0);
+    assert_close("hsl(120deg 100% 50%)", [0.0, 1.0, 0.0, 1.0]);
+    assert_close("hsla(0.5turn, 100%, 50%, 0.5)", [0.0, 1.0, 1.0, 0.5]);
+    assert_close("hsl(-120 100% 25%)", [0.0, 0.0, 0.5, 1.0]);
+    assert_close("hwb(0 0% 0%)", [1.0, 0.0, 0.0, 1.0]);
+    assert_close("hwb(90 60% 60%)", [0.5, 0.5, 0.5, 1.0]);
+
+    for css in [
+        // the legacy syntax takes one kind of value, and no `none`
+        "rgb(255, 0%, 0)",
+        "rgb(none, 0, 0)",
+        "rgb(255, 0, none)",
+        "hsl(0, 100, 50)",
+        // no mixing of commas and spaces
+        "rgb(255, 0 0)",
+        "rgb(255 0 0, 1)",
+        "rgb(255, 0, 0 / 1)",
+        "hwb(0, 0%, 0%)",
+        "rgb(0deg 0 0)",
+        "hsl(0 0deg 0)",
+        "rgb(1 2 3 4)",
+    ] {
+        assert_eq!(parse(css), Err(ErrorKind::UnexpectedToken), "{css}");
+    }
+    assert_eq!(parse("rgb(1 2"), Err(ErrorKind::EndOfInput));
+}
+
+#[test]
+fn modern_color_spaces() {
+    let lab = parse("lab(50% 100% -100%)").unwrap();
+    assert_eq!((lab.space, lab.components), (ColorSpace::Lab, [50.0, 125.0, -125.0]));
+    let oklch = parse("oklch(150% 100% 1rad)").unwrap();
+    assert_eq!(oklch.space, ColorSpace::Oklch);
+    assert_eq!(oklch.components[..2], [1.0, 0.4]);
+    assert!((oklch.components[2] - 57.29578).abs() < 1e-3);
+    assert_eq!(parse("lab(50 0 0deg)"), Err(ErrorKind::UnexpectedToken));
+
+    // sRGB red in each space
+    for css in [
+        "lab(54.29% 80.82 69.88)",
+        "lch(54.29 106.84 40.85)",
+        "oklab(62.8% 0.2249 0.1258)",
+        "oklch(0.628 0.2577 29.23deg)",
+        "color(srgb 1 0 0)",
+        "color(srgb-linear 100% 0% 0%)",
+        "color(xyz 0.4124 0.2126 0.0193)",
+    ] {
+        assert_close(css, [1.0, 0.0, 0.0, 1.0]);
+    }
+    // white
+    for css in [
+        "lab(100 0 0)",
+        "oklch(1 0 0)",
+        "color(display-p3 1 1 1)",
+        "color(a98-rgb 1 1 1)",
+        "color(prophoto-rgb 1 1 1)",
+        "color(rec2020 1 1 1)",
+        "color(xyz-d50 0.9642 1 0.8249)",
+    ] {
+        assert_close(css, [1.0, 1.0, 1.0, 1.0]);
+    }
+    assert_close("color(srgb-linear 0.2 0.2 0.2 / 0.5)", [0.4845, 0.4845, 0.4845, 0.5]);
+    // out of gamut colors are not clamped
+    assert!(srgb("color(display-p3 1 0 0)")[0] > 1.0);
+
+    assert_eq!(parse("color(unknown 1 1 1)"), Err(ErrorKind::UnexpectedToken));
+    assert_eq!(parse("color(1 1 1)"), Err(ErrorKind::UnexpectedToken));
+}
+
+#[test]
+fn missing_components() {
+    let color = parse("oklch(50% none 120 / none)").unwrap();
+    assert_eq!(color.components, [0.5, 0.0, 120.0]);
+    assert_eq!(color.alpha, 0.0);
+    assert_eq!(color.missing, 0b1010);
+    assert_eq!(color_to_srgb(color).missing, 0);
+    assert_eq!(parse("rgb(none 0 0)").unwrap().missing, 0b1);
+}
+
+#[test]
+fn tokens_of_a_declaration() {
+    let list = declaration_list("color: hsl(120 100% 50%); background: rgb(1 2 foo)");
+
+    let (status, color, _) = call(|color, error| parse_color_tokens((&list.declarations[0].value[..]).into(), color, error));
+    assert_eq!(status, Status::Ok);
+    assert_eq!((color.space, color.components), (ColorSpace::Hsl, [120.0, 100.0, 50.0]));
+
+    let (status, color, error) = call(|color, error| parse_color_tokens((&list.declarations[1].value[..]).into(), color, error));
+    assert_eq!(status, Status::ParseError);
+    assert_eq!((error.location.start, error.location.end), (46, 49));
+    assert_eq!(color.alpha, 0.0);
+}
@@ -0,0 +1,20 @@
+//! Helpers shared by the integration tests, not every test uses all of them.
+#![allow(dead_code)]
+use std::{ffi::CString, mem::MaybeUninit};
+
+use cssparser_fii::error::{Error, Status};
+use safer_ffi::prelude::{AsOut, Out};
+
+/// Calls an exported function with out-params for its result and its error, and returns what it wrote to them.
+pub fn call<T>(f: impl FnOnce(Out<'_, T>, Option<Out<'_, Error>>) -> Status) -> (Status, T, Error) {
+    let mut value = MaybeUninit::uninit();
+    let mut error = MaybeUninit::uninit();
+    let status = f(value.as_out(), Some(error.as_out()));
+    unsafe { (status, value.assume_init(), error.assume_init()) }
+}
+
+/// Like [`call`], for a function that takes `css` as a NUL-terminated string.
+pub fn call_str<T>(css: &str, f: extern "C" fn(*const safer_ffi::c_char, Out<'_, T>, Option<Out<'_, Error>>) -> Status) -> (Status, T, Error) {
+    let css = CString::new(css).unwrap();
+    call(|value, error| f(css.as_ptr() as *const _, value, error))
+}
@@ -0,0 +1,157 @@
+//! Computing custom properties and substituting `var()`.
+mod common;
+
+use common::{call, call_str};
+use cssparser_fii::{
+    custom_properties::*,
+    error::{ErrorKind, Status},
+    free_tokens, parse_css,
+    serialize::tokens_to_string,
+    stylesheet::{declaration_list, free_declaration_list},
+};
+
+fn element(parent: Option<&mut CustomProperties>, style: &str) -> CustomProperties {
+    let mut properties = CustomProperties::new(parent);
+    let list = declaration_list(style);
+    assert!(list.errors.is_empty(), "{style}");
+    properties.add(&list.declarations);
+    free_declaration_list(list);
+    properties
+}
+
+fn get(properties: &mut CustomProperties, name: &str) -> Option<String> {
+    properties.get(name).map(tokens_to_string)
+}
+
+/// The value of the declaration `style` with its `var()` replaced.
+fn substitute(properties: &mut CustomProperties, style: &str) -> Result<String, (ErrorKind, usize, usize)> {
+    let list = declaration_list(style);
+    let result = properties.substitute(&list.declarations[0].value);
+    free_declaration_list(list);
+    result.map(|tokens| tokens_to_string(&tokens)).map_err(|error| (error.kind, error.location.start, error.location.end))
+}
+
+#[test]
+fn references_and_fallbacks() {
+    let mut properties = element(None, "--color: red; --size: 2px; --border: var(--size) solid var( --color ); --empty:; color: blue");
+    assert_eq!(get(&mut properties, "--border").as_deref(), Some("2px solid red"));
+    assert_eq!(get(&mut properties, "--empty").as_deref(), Some(""));
+    assert_eq!(get(&mut properties, "--Color"), None);
+    assert_eq!(get(&mut properties, "color"), None);
+
+    for (style, substituted) in [
+        ("border: var(--border)", "2px solid red"),
+        ("margin: calc(var(--size) * 2) var(--missing, 1px)", "calc(2px * 2) 1px"),
+        ("margin: var(--missing, var(--size))", "2px"),
+        ("margin: var(--missing, 1px 2px, 3px)", "1px 2px, 3px"),
+        ("margin: var(--missing,)", ""),
+        ("margin: VAR(--size)", "2px"),
+        ("margin: var(--size", "2px"),
+        ("content: 'var(--size)'", "\"var(--size)\""),
+    ] {
+        assert_eq!(substitute(&mut properties, style).as_deref(), Ok(substituted), "{style}");
+    }
+}
+
+#[test]
+fn invalid_references() {
+    let mut properties = element(None, "--size: 2px; --broken: var(--missing); --fallback: var(--broken, 1px)");
+    assert_eq!(get(&mut properties, "--broken"), None);
+    assert_eq!(get(&mut properties, "--fallback").as_deref(), Some("1px"));
+
+    assert_eq!(substitute(&mut properties, "margin: 0 var(--missing)"), Err((ErrorKind::GuaranteedInvalid, 10, 24)));
+    assert_eq!(substitute(&mut properties, "margin: var(--broken)"), Err((ErrorKind::GuaranteedInvalid, 8, 21)));
+    assert_eq!(substitute(&mut properties, "margin: var(--missing, var(--broken))"), Err((ErrorKind::GuaranteedInvalid, 23, 36)));
+    for style in ["margin: var(size)", "margin: var()", "margin: var(--size 1px)", "margin: var(--size;"] {
+        assert_eq!(substitute(&mut properties, style).map_err(|error| error.0), Err(ErrorKind::GuaranteedInvalid), "{style}");
+    }
+}
+
+#[test]
+fn cycles() {
+    let mut properties = element(None, "--a: var(--b); --b: var(--c, 1px); --c: var(--a); --d: var(--a, 2px); --e: var(--e); --f: var(--d) var(--d)");
+    for name in ["--a", "--b", "--c", "--e"] {
+        assert_eq!(get(&mut properties, name), None, "{name}");
+    }
+    assert_eq!(get(&mut properties, "--d").as_deref(), Some("2px"));
+    assert_eq!(get(&mut properties, "--f").as_deref(), Some("2px 2px"));
+    // references in fallbacks count, even when the fallback is not used
+    let mut properties = element(None, "--a: var(--b, var(--a)); --b: 1px");
+    assert_eq!(get(&mut properties, "--a"), None);
+    assert_eq!(get(&mut properties, "--b").as_deref(), Some("1px"));
+
+    // the cycle x -> f -> r -> x goes through properties that also reference each other,
+    // all of them are invalid whatever order they are computed in
+    for _ in 0..20 {
+        let mut properties = element(None, "--r: var(--f, 0) var(--x); --f: var(--r, 1); --x: var(--f, 2); --y: var(--x, 3)");
+        for name in ["--r", "--f", "--x"] {
+            assert_eq!(get(&mut properties, name), None, "{name}");
+        }
+        assert_eq!(get(&mut properties, "--y").as_deref(), Some("3"));
+    }
+}
+
+#[test]
+fn inheritance_and_keywords() {
+    let mut root = element(None, "--a: 1; --b: 2; --c: 3; --d: 4; --e: 5");
+    let mut child = element(Some(&mut root), "--a: 10; --b: initial; --c: inherit; --d: unset; --f: var(--e) var(--a)");
+    let values: Vec<_> = ["--a", "--b", "--c", "--d", "--e", "--f"].into_iter().map(|name| get(&mut child, name)).collect();
+    assert_eq!(values, [Some("10".to_string()), None, Some("3".to_string()), Some("4".to_string()), Some("5".to_string()), Some("5 10".to_string())]);
+
+    // the guaranteed-invalid value is inherited too
+    let mut grandchild = element(Some(&mut child), "--g: var(--b, none)");
+    assert_eq!(get(&mut grandchild, "--b"), None);
+    assert_eq!(get(&mut grandchild, "--g").as_deref(), Some("none"));
+    assert_eq!(get(&mut root, "--a").as_deref(), Some("1"));
+}
+
+#[test]
+fn cascade_order() {
+    let mut properties = element(None, "--a: 1 !important; --b: 1; --a: 2; --b: 2 !important; --b: 3; --c: 1; --c: var(--a)");
+    assert_eq!(get(&mut properties, "--a").as_deref(), Some("1"));
+    assert_eq!(get(&mut properties, "--b").as_deref(), Some("2"));
+    assert_eq!(get(&mut properties, "--c").as_deref(), Some("1"));
+
+    // adding declarations recomputes the values
+    let list = declaration_list("--a: 3 !important");
+    properties.add(&list.declarations);
+    free_declaration_list(list);
+    assert_eq!(get(&mut properties, "--c").as_deref(), Some("3"));
+}
+
+#[test]
+fn through_the_c_api() {
+    let mut root = custom_properties_new(None);
+    let list = declaration_list("--gap: 4px; --pad: var(--gap) var(--gap)");
+    custom_properties_add(&mut root, (&list.declarations[..]).into());
+    free_declaration_list(list);
+    let mut child = custom_properties_new(Some(&mut root));
+    custom_properties_free(root);
+
+    let name = std::ffi::CString::new("--pad").unwrap();
+    let (status, value, _) = call(|value, error| custom_properties_get(&mut child, name.as_ptr() as *const _, value, error));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(tokens_to_string(&value), "4px 4px");
+    free_tokens(value);
+
+    let name = std::ffi::CString::new("--missing").unwrap();
+    let (status, value, error) = call(|value, error| custom_properties_get(&mut child, name.as_ptr() as *const _, value, error));
+    assert_eq!(status, Status::ParseError);
+    assert!(value.is_empty());
+    assert_eq!((error.kind, error.message.to_string()), (ErrorKind::GuaranteedInvalid, "`--missing` has no value".to_string()));
+    free_tokens(value);
+
+    // tokens from `parse_css` work as well, its functions are not closed so a `var()` runs to the end of them
+    let (status, tokens, _) = call_str("padding: 0 var(--missing, var(--pad)", parse_css);
+    assert_eq!(status, Status::Ok);
+    let (status, substituted, _) = call(|substituted, error| custom_properties_substitute(&mut child, (&tokens[2..]).into(), substituted, error));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(tokens_to_string(&substituted), "0 4px 4px");
+    free_tokens(substituted);
+    free_tokens(tokens);
+
+    let (status, value, _) = call(|value, error| custom_properties_get(&mut child, std::ptr::null(), value, error));
+    assert_eq!(status, Status::InvalidInput);
+    free_tokens(value);
+    custom_properties_free(child);
+}
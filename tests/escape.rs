@@ -0,0 +1,72 @@
+//! Escaping arbitrary text as identifiers, names and strings, which must tokenize back to that text.
+mod common;
+
+use common::{call, call_str};
+use cssparser_fii::{cstr, error::{Error, ErrorKind, Status}, free_tokens, parse_css_bytes, serialize::*, token_union::free_string};
+use safer_ffi::prelude::Out;
+
+type Serialize = extern "C" fn(*const safer_ffi::c_char, Out<'_, cstr>, Option<Out<'_, Error>>) -> Status;
+
+fn escape(serialize: Serialize, input: &str) -> String {
+    let (status, css, _) = call_str(input, serialize);
+    assert_eq!(status, Status::Ok);
+    let string = css.to_string();
+    free_string(css);
+    string
+}
+
+/// The kind and value of the only token `css` is made of.
+fn tokenize(css: &str) -> (&'static str, String) {
+    let (status, tokens, _) = call(|tokens, error| parse_css_bytes(css.as_ptr(), css.len(), tokens, error));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(tokens.len(), 1, "{css}");
+    let token = match cssparser::Token::from(&*tokens[0].value) {
+        cssparser::Token::Ident(value) => ("ident", value.to_string()),
+        cssparser::Token::Hash(value) | cssparser::Token::IDHash(value) => ("hash", value.to_string()),
+        cssparser::Token::QuotedString(value) => ("string", value.to_string()),
+        token => ("other", format!("{token:?}")),
+    };
+    free_tokens(tokens);
+    token
+}
+
+const INPUTS: &[&str] = &["foo", "2col", "-", "-2", "--x", "a b", "a.b#c", "日本語", "\x7f\x01", "\\", "\"'", "line\nbreak"];
+
+#[test]
+fn identifiers() {
+    assert_eq!(escape(serialize_identifier, "2col"), "\\32 col");
+    assert_eq!(escape(serialize_identifier, "-"), "\\-");
+    assert_eq!(escape(serialize_identifier, "--x"), "--x");
+    assert_eq!(escape(serialize_identifier, "a b"), "a\\ b");
+    for input in INPUTS {
+        let css = escape(serialize_identifier, input);
+        assert_eq!(tokenize(&css), ("ident", input.to_string()), "{css}");
+    }
+}
+
+#[test]
+fn names() {
+    assert_eq!(escape(serialize_name, "2col"), "2col");
+    assert_eq!(escape(serialize_name, "a b"), "a\\ b");
+    for input in INPUTS {
+        let css = format!("#{}", escape(serialize_name, input));
+        assert_eq!(tokenize(&css), ("hash", input.to_string()), "{css}");
+    }
+}
+
+#[test]
+fn strings() {
+    assert_eq!(escape(serialize_string, "Foo \"Bar\""), "\"Foo \\\"Bar\\\"\"");
+    assert_eq!(escape(serialize_string, ""), "\"\"");
+    for input in INPUTS.iter().chain(&[""]) {
+        let css = escape(serialize_string, input);
+        assert_eq!(tokenize(&css), ("string", input.to_string()), "{css}");
+    }
+}
+
+#[test]
+fn null_input() {
+    let (status, css, error) = call(|css, error| serialize_identifier(std::ptr::null(), css, error));
+    assert_eq!((status, css.len(), error.kind), (Status::InvalidInput, 0, ErrorKind::NullPointer));
+    free_string(css);
+}
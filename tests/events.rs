@@ -0,0 +1,213 @@
+//! Streaming a stylesheet through `parse_css_events`, recording every event as a line of text.
+use std::ffi::c_void;
+
+use cssparser_fii::{error::{Error, Status}, events::*, location::SourceLocation, stylesheet::{free_stylesheet, stylesheet, RuleType, StyleSheet}, view::StrView};
+
+struct Recorder {
+    events: Vec<String>,
+    /// Abort on the event that starts with this.
+    abort_on: Option<&'static str>,
+}
+
+fn text(view: StrView) -> String {
+    String::from_utf8(unsafe { std::slice::from_raw_parts(view.ptr, view.len) }.to_vec()).unwrap()
+}
+
+unsafe fn record(user: *mut c_void, event: String) -> EventFlow {
+    let recorder = &mut *(user as *mut Recorder);
+    let abort = recorder.abort_on.is_some_and(|prefix| event.starts_with(prefix));
+    recorder.events.push(event);
+    if abort { EventFlow::Abort } else { EventFlow::Continue }
+}
+
+unsafe extern "C" fn on_rule_start(rule_type: RuleType, location: SourceLocation, user: *mut c_void) -> EventFlow {
+    record(user, format!("start {rule_type:?} {}..{}", location.start, location.end))
+}
+
+unsafe extern "C" fn on_selector(selector: StrView, _: SourceLocation, user: *mut c_void) -> EventFlow {
+    record(user, format!("selector {}", text(selector)))
+}
+
+unsafe extern "C" fn on_declaration(name: StrView, value: StrView, important: bool, location: SourceLocation, user: *mut c_void) -> EventFlow {
+    let important = if important { " !important" } else { "" };
+    record(user, format!("declaration {}: {}{important} {}..{}", text(name), text(value), location.start, location.end))
+}
+
+unsafe extern "C" fn on_at_rule(name: StrView, prelude: StrView, has_block: bool, _: SourceLocation, user: *mut c_void) -> EventFlow {
+    let block = if has_block { " {" } else { ";" };
+    record(user, format!("at-rule @{} {}{block}", text(name), text(prelude)))
+}
+
+unsafe extern "C" fn on_rule_end(location: SourceLocation, user: *mut c_void) -> EventFlow {
+    record(user, format!("end {}..{}", location.start, location.end))
+}
+
+unsafe extern "C" fn on_comment(comment: StrView, _: SourceLocation, user: *mut c_void) -> EventFlow {
+    record(user, format!("comment {}", text(comment)))
+}
+
+unsafe extern "C" fn on_error(error: *const Error, user: *mut c_void) -> EventFlow {
+    let error = &*error;
+    record(user, format!("error {:?} {}..{}", error.kind, error.location.start, error.location.end))
+}
+
+const CALLBACKS: EventCallbacks = EventCallbacks {
+    on_rule_start: Some(on_rule_start),
+    on_selector: Some(on_selector),
+    on_declaration: Some(on_declaration),
+    on_at_rule: Some(on_at_rule),
+    on_rule_end: Some(on_rule_end),
+    on_comment: Some(on_comment),
+    on_error: Some(on_error),
+};
+
+fn parse(css: &str, abort_on: Option<&'static str>) -> (Status, Vec<String>) {
+    let css = std::ffi::CString::new(css).unwrap();
+    let mut recorder = Recorder { events: Vec::new(), abort_on };
+    let status = parse_css_events(css.as_ptr() as *const _, &CALLBACKS, &mut recorder as *mut Recorder as *mut c_void);
+    (status, recorder.events)
+}
+
+#[test]
+fn rules_and_declarations() {
+    let css = "a, b > c { color: red; margin: 0 !important }\n@media screen { .x { top: 1px } }";
+    let (status, events) = parse(css, None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(events, [
+        "start Qualified 0..10",
+        "selector a",
+        "selector b > c",
+        "declaration color: red 11..21",
+        "declaration margin: 0 !important 23..43",
+        "end 0..45",
+        "start At 46..61",
+        "at-rule @media screen {",
+        "start Qualified 62..66",
+        "selector .x",
+        "declaration top: 1px 67..75",
+        "end 62..77",
+        "end 46..79",
+    ]);
+}
+
+#[test]
+fn statements_and_nesting() {
+    let css = "@import url(a.css) screen;\n.a { color: red; &:hover { color: blue } @media print { color: black } }";
+    let (status, events) = parse(css, None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(events, [
+        "at-rule @import url(a.css) screen;",
+        "start Qualified 27..31",
+        "selector .a",
+        "declaration color: red 32..42",
+        "start Qualified 44..53",
+        "selector &:hover",
+        "declaration color: blue 54..65",
+        "end 44..67",
+        "start At 68..82",
+        "at-rule @media print {",
+        "declaration color: black 83..95",
+        "end 68..97",
+        "end 27..99",
+    ]);
+}
+
+#[test]
+fn comments() {
+    let css = "/* top */ a /* in */ { /* body */ color: red /* value */; }";
+    let (_, events) = parse(css, None);
+    assert_eq!(events, [
+        "comment  top ",
+        "start Qualified 10..22",
+        "selector a",
+        "comment  in ",
+        "comment  body ",
+        "declaration color: red 34..44",
+        "comment  value ",
+        "end 10..59",
+    ]);
+}
+
+#[test]
+fn errors_do_not_stop_the_parse() {
+    let css = "a { color: red; : nothing; top: 0 } b { left: 0 } c";
+    let (status, events) = parse(css, None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(events, [
+        "start Qualified 0..3",
+        "selector a",
+        "declaration color: red 4..14",
+        "error UnexpectedToken 16..26",
+        "declaration top: 0 27..33",
+        "end 0..35",
+        "start Qualified 36..39",
+        "selector b",
+        "declaration left: 0 40..47",
+        "end 36..49",
+        "error EndOfInput 50..51",
+    ]);
+}
+
+#[test]
+fn abort() {
+    let css = "a { color: red; top: 0 } b { left: 0 }";
+    let (status, events) = parse(css, Some("declaration color"));
+    assert_eq!(status, Status::Aborted);
+    assert_eq!(events, ["start Qualified 0..3", "selector a", "declaration color: red 4..14"]);
+
+    let (status, events) = parse(css, Some("end"));
+    assert_eq!((status, events.len()), (Status::Aborted, 5));
+}
+
+#[test]
+fn missing_callbacks_and_input() {
+    let none = EventCallbacks { on_rule_start: None, on_selector: None, on_declaration: None, on_at_rule: None, on_rule_end: None, on_comment: None, on_error: None };
+    let css = std::ffi::CString::new("a { color: red }").unwrap();
+    assert_eq!(parse_css_events(css.as_ptr() as *const _, &none, std::ptr::null_mut()), Status::Ok);
+
+    let mut recorder = Recorder { events: Vec::new(), abort_on: None };
+    let status = parse_css_events(std::ptr::null(), &CALLBACKS, &mut recorder as *mut Recorder as *mut c_void);
+    assert_eq!((status, recorder.events.as_slice()), (Status::InvalidInput, ["error NullPointer 0..0".to_string()].as_slice()));
+}
+
+/// The rules and declarations of `sheet` in the order `parse_css_events` reports them,
+/// provided the declarations of each block come before its nested rules.
+fn outline(sheet: &StyleSheet, index: usize, lines: &mut Vec<String>) {
+    let rule = &sheet.rules[index];
+    lines.push(match rule.rule_type {
+        RuleType::Qualified => "rule".to_string(),
+        RuleType::At => format!("@{}", &*rule.name),
+    });
+    lines.extend(rule.declarations.iter().map(|declaration| format!("declaration {}", &*declaration.name)));
+    for child in rule.first_child..rule.first_child + rule.child_count {
+        outline(sheet, child, lines);
+    }
+}
+
+#[test]
+fn blocks_hold_what_parse_stylesheet_accepts() {
+    let css = "@media print { @media screen { color: red } a { top: 0 } } b { left: 0; @media screen { color: blue } }";
+    let (status, events) = parse(css, None);
+    assert_eq!(status, Status::Ok);
+    let reported: Vec<_> = events.iter().filter_map(|event| {
+        let mut words = event.split(' ');
+        match (words.next()?, words.next()?) {
+            ("start", "Qualified") => Some("rule".to_string()),
+            ("at-rule", name) => Some(name.to_string()),
+            ("declaration", name) => Some(format!("declaration {}", name.trim_end_matches(':'))),
+            _ => None,
+        }
+    }).collect();
+    let errors = events.iter().filter(|event| event.starts_with("error")).count();
+
+    let sheet = stylesheet(css);
+    let mut expected = Vec::new();
+    for root in 0..sheet.roots {
+        outline(&sheet, root, &mut expected);
+    }
+    assert_eq!(reported, expected);
+    assert_eq!(errors, sheet.errors.len());
+    // at the top level `@media` only holds rules, inside of a style rule it holds declarations too
+    assert_eq!(expected, ["@media", "@media", "rule", "declaration top", "rule", "declaration left", "@media", "declaration color"]);
+    free_stylesheet(sheet);
+}
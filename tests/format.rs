@@ -0,0 +1,102 @@
+//! Formatting must only change whitespace (and quotes), and formatting twice must change nothing.
+mod common;
+
+use common::{call, call_str};
+use cssparser_fii::{error::Status, format::*, free_tokens, parse_css, token_union::free_string, Token};
+
+const CSS: &str = "/* top */\n@import 'a.css';\na:hover ,b>c{color:red;/* c */margin : 0 .5em ;background:url( 'it\\'s.png' ) , f( 1,2 )}\
+@media screen and (min-width:0){x{y:calc(0px + 1px)}z{}}\n/* doc */\np{q:r !important;--x:  a  b}";
+
+fn all_options() -> Vec<FormatOptions> {
+    let mut options = Vec::new();
+    for (use_tabs, one_declaration_per_line, blank_line_between_rules, quote_style) in [
+        (false, true, true, QuoteStyle::Double),
+        (true, false, false, QuoteStyle::Single),
+        (false, false, true, QuoteStyle::Single),
+        (true, true, false, QuoteStyle::Double),
+    ] {
+        options.push(FormatOptions { indent_width: 4, use_tabs, one_declaration_per_line, blank_line_between_rules, quote_style });
+    }
+    options
+}
+
+fn format(css: &str, options: Option<&FormatOptions>) -> String {
+    let css = std::ffi::CString::new(css).unwrap();
+    let (status, formatted, _) = call(|formatted, error| format_css(css.as_ptr() as *const _, options, formatted, error));
+    assert_eq!(status, Status::Ok);
+    let string = formatted.to_string();
+    free_string(formatted);
+    string
+}
+
+fn parse(css: &str) -> safer_ffi::Vec<Token> {
+    let (status, tokens, _) = call_str(css, parse_css);
+    assert_eq!(status, Status::Ok);
+    tokens
+}
+
+/// The tokens of `css`, a semicolon right before the end of a block is optional so it is left out.
+fn assert_same_tokens(css: &str, formatted: &str) {
+    let (original, reformatted) = (parse(css), parse(formatted));
+    {
+        let significant = |tokens: &[Token]| -> Vec<String> {
+            let tokens: Vec<cssparser::Token> = tokens.iter().map(|token| (&*token.value).into()).collect();
+            tokens.iter().enumerate()
+                .filter(|&(i, token)| !(*token == cssparser::Token::Semicolon && tokens.get(i + 1) == Some(&cssparser::Token::CloseCurlyBracket)))
+                .map(|(_, token)| format!("{token:?}"))
+                .collect()
+        };
+        assert_eq!(significant(&original), significant(&reformatted), "{formatted}");
+    }
+    free_tokens(original);
+    free_tokens(reformatted);
+}
+
+#[test]
+fn default_layout() {
+    assert_eq!(format("a,b{c:d;e:f}@media x{g{}}", None), "a, b {\n  c: d;\n  e: f;\n}\n\n@media x {\n  g {}\n}\n");
+    assert_eq!(format("", None), "");
+}
+
+#[test]
+fn compact_layout() {
+    let options = FormatOptions { one_declaration_per_line: false, blank_line_between_rules: false, ..format_options_default() };
+    assert_eq!(format("a { c : d ; e : f }\nb{g:h}", Some(&options)), "a { c: d; e: f; }\nb { g: h; }\n");
+}
+
+#[test]
+fn quote_style() {
+    let single = FormatOptions { quote_style: QuoteStyle::Single, ..format_options_default() };
+    assert_eq!(format(r#"@import "it's.css";"#, Some(&single)), "@import 'it\\'s.css';\n");
+    assert_eq!(format("@import 'a\"b.css';", None), "@import \"a\\\"b.css\";\n");
+}
+
+#[test]
+fn comments_are_preserved() {
+    for options in all_options() {
+        let formatted = format(CSS, Some(&options));
+        for comment in ["/* top */", "/* c */", "/* doc */"] {
+            assert!(formatted.contains(comment), "{comment} is missing from {formatted}");
+        }
+    }
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    let test_css = include_str!("../test/test.css");
+    for options in all_options() {
+        for css in [CSS, test_css] {
+            let formatted = format(css, Some(&options));
+            assert_eq!(format(&formatted, Some(&options)), formatted);
+            assert_same_tokens(css, &formatted);
+        }
+    }
+}
+
+#[test]
+fn indentation() {
+    let tabs = FormatOptions { use_tabs: true, ..format_options_default() };
+    assert_eq!(format("@media x{a{b:c}}", Some(&tabs)), "@media x {\n\ta {\n\t\tb: c;\n\t}\n}\n");
+    let four = FormatOptions { indent_width: 4, ..format_options_default() };
+    assert_eq!(format("a{b:c}", Some(&four)), "a {\n    b: c;\n}\n");
+}
@@ -0,0 +1,173 @@
+//! Inlining `@import` rules through a loader callback.
+mod common;
+
+use std::{collections::HashMap, ffi::c_void};
+
+use common::call;
+use cssparser_fii::{error::{Error, ErrorKind, Status}, import::*, serialize::tokens_to_string, stylesheet::RuleType, view::StrView};
+
+struct Files {
+    files: HashMap<&'static str, &'static str>,
+    requested: Vec<String>,
+}
+
+unsafe extern "C" fn load_file(url: StrView, user: *mut c_void) -> StrView {
+    let files = &mut *(user as *mut Files);
+    let url = url.as_str();
+    files.requested.push(url.to_string());
+    match files.files.get(url) {
+        Some(css) => StrView::borrowed(css),
+        None => StrView { ptr: std::ptr::null(), len: 0, owned: false },
+    }
+}
+
+fn load(entry: &str, files: &[(&'static str, &'static str)]) -> (Status, LoadedStyleSheet, Error, Vec<String>) {
+    let mut files = Files { files: files.iter().copied().collect(), requested: Vec::new() };
+    let entry = std::ffi::CString::new(entry).unwrap();
+    let user = &mut files as *mut Files as *mut c_void;
+    let (status, sheet, error) = call(|sheet, error| load_stylesheet(entry.as_ptr() as *const _, Some(load_file), user, sheet, error));
+    (status, sheet, error, files.requested)
+}
+
+/// The rule at `index` and its nested rules, with the file each one comes from.
+fn describe(sheet: &LoadedStyleSheet, index: usize) -> String {
+    let rule = &sheet.sheet.rules[index];
+    let file = &sheet.files[sheet.rule_files[index]];
+    let head = match rule.rule_type {
+        RuleType::Qualified => tokens_to_string(&rule.prelude),
+        RuleType::At => format!("@{} {}", rule.name, tokens_to_string(&rule.prelude)),
+    };
+    let children: Vec<_> = (rule.first_child..rule.first_child + rule.child_count).map(|child| describe(sheet, child)).collect();
+    match rule.has_block {
+        true => format!("{} ({file}) {{ {} }}", head.trim(), children.join(" ")),
+        false => format!("{} ({file});", head.trim()),
+    }
+}
+
+fn roots(sheet: &LoadedStyleSheet) -> Vec<String> {
+    (0..sheet.sheet.roots).map(|index| describe(sheet, index)).collect()
+}
+
+fn errors(sheet: &LoadedStyleSheet) -> Vec<(ErrorKind, String)> {
+    sheet.sheet.errors.iter().zip(sheet.error_files.iter()).map(|(error, &file)| (error.kind, sheet.files[file].to_string())).collect()
+}
+
+#[test]
+fn imports_are_inlined() {
+    let (status, sheet, _, requested) = load("css/main.css", &[
+        ("css/main.css", "@charset \"utf-8\";\n@layer base, theme;\n@import \"reset.css\";\n@import url(parts/b.css) screen and (min-width: 40em);\nmain { color: red }"),
+        ("css/reset.css", "* { margin: 0 }"),
+        ("css/parts/b.css", "@import url(\"../theme/c.css\") layer(theme) supports(display: grid);\nb { color: blue }"),
+        ("css/theme/c.css", "c { color: green } @media print { c { color: black } }"),
+    ]);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(requested, ["css/main.css", "css/reset.css", "css/parts/b.css", "css/theme/c.css"]);
+    assert_eq!(roots(&sheet), [
+        "@layer base, theme (css/main.css);",
+        "* (css/reset.css) {  }",
+        "@media screen and (min-width: 40em) (css/main.css) { \
+            @supports (display: grid) (css/parts/b.css) { \
+                @layer theme (css/parts/b.css) { c (css/theme/c.css) {  } @media print (css/theme/c.css) { c (css/theme/c.css) {  } } } \
+            } \
+            b (css/parts/b.css) {  } \
+        }",
+        "main (css/main.css) {  }",
+    ]);
+    assert!(sheet.sheet.errors.is_empty());
+
+    // the wrapping rules are located at the `@import` they replace
+    let media = &sheet.sheet.rules[2];
+    assert_eq!(media.location.start, 59);
+    free_loaded_stylesheet(sheet);
+}
+
+#[test]
+fn anonymous_layers_and_repeated_imports() {
+    let (status, sheet, ..) = load("main.css", &[
+        ("main.css", "@import 'a.css' layer; @import 'a.css';"),
+        ("a.css", "a { } }"),
+    ]);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(roots(&sheet), ["@layer (main.css) { a (a.css) {  } }", "a (a.css) {  }"]);
+    assert_eq!(sheet.files.iter().map(|file| file.to_string()).collect::<Vec<_>>(), ["main.css", "a.css"]);
+    // the errors of a file are only listed once
+    assert_eq!(errors(&sheet), [(ErrorKind::EndOfInput, "a.css".to_string())]);
+    free_loaded_stylesheet(sheet);
+}
+
+#[test]
+fn cycles_and_missing_files() {
+    let (status, sheet, ..) = load("https://example.com/css/a.css", &[
+        ("https://example.com/css/a.css", "@import 'b.css'; @import '/missing.css'; a { }"),
+        ("https://example.com/css/b.css", "@import './a.css'; @import url(b.css); b { }"),
+    ]);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(roots(&sheet), ["b (https://example.com/css/b.css) {  }", "a (https://example.com/css/a.css) {  }"]);
+    assert_eq!(errors(&sheet), [
+        (ErrorKind::ImportCycle, "https://example.com/css/b.css".to_string()),
+        (ErrorKind::ImportCycle, "https://example.com/css/b.css".to_string()),
+        (ErrorKind::LoadFailed, "https://example.com/css/a.css".to_string()),
+    ]);
+    assert_eq!(sheet.sheet.errors[2].message.to_string(), "could not load `https://example.com/missing.css`");
+    assert_eq!((sheet.sheet.errors[2].location.start, sheet.sheet.errors[2].location.end), (17, 40));
+    free_loaded_stylesheet(sheet);
+}
+
+#[test]
+fn invalid_imports() {
+    let (_, sheet, ..) = load("main.css", &[
+        ("main.css", "@import 12px; a { } @import 'b.css';"),
+        ("b.css", "b { }"),
+    ]);
+    assert_eq!(roots(&sheet), ["a (main.css) {  }"]);
+    let kinds: Vec<_> = sheet.sheet.errors.iter().map(|error| error.kind).collect();
+    assert_eq!(kinds, [ErrorKind::AtRuleInvalid, ErrorKind::AtRuleInvalid]);
+    free_loaded_stylesheet(sheet);
+
+    // `layer()` is not an anonymous layer
+    let (_, sheet, _, requested) = load("main.css", &[
+        ("main.css", "@import 'b.css' layer(); @import 'b.css' layer( );"),
+        ("b.css", "b { }"),
+    ]);
+    assert!(roots(&sheet).is_empty() && requested == ["main.css"]);
+    let errors: Vec<_> = sheet.sheet.errors.iter().map(|error| (error.kind, error.message.to_string(), error.location.start)).collect();
+    assert_eq!(errors, [
+        (ErrorKind::AtRuleInvalid, "`layer()` must contain a layer name".to_string(), 0),
+        (ErrorKind::AtRuleInvalid, "`layer()` must contain a layer name".to_string(), 25),
+    ]);
+    free_loaded_stylesheet(sheet);
+}
+
+#[test]
+fn entry_cannot_be_loaded() {
+    let (status, sheet, error, _) = load("main.css", &[]);
+    assert_eq!((status, error.kind), (Status::InvalidInput, ErrorKind::LoadFailed));
+    assert!(sheet.sheet.rules.is_empty() && sheet.files.is_empty());
+    free_loaded_stylesheet(sheet);
+
+    let entry = std::ffi::CString::new("main.css").unwrap();
+    let (status, sheet, error) = call(|sheet, error| load_stylesheet(entry.as_ptr() as *const _, None, std::ptr::null_mut(), sheet, error));
+    assert_eq!((status, error.kind), (Status::InvalidInput, ErrorKind::NullPointer));
+    free_loaded_stylesheet(sheet);
+}
+
+#[test]
+fn urls() {
+    for (url, base, resolved) in [
+        ("b.css", "css/a.css", "css/b.css"),
+        ("../b.css", "css/a.css", "b.css"),
+        ("../../b.css", "css/a.css", "../b.css"),
+        ("./x/./y/../b.css", "a.css", "x/b.css"),
+        ("/b.css", "css/a.css", "/b.css"),
+        ("/../b.css", "a.css", "/b.css"),
+        ("b.css", "https://example.com/css/a.css", "https://example.com/css/b.css"),
+        ("../../b.css", "https://example.com/css/a.css", "https://example.com/b.css"),
+        ("https://cdn.example.com/x/../b.css", "a.css", "https://cdn.example.com/b.css"),
+        ("/b.css", "https://example.com/css/a.css", "https://example.com/b.css"),
+        ("//cdn.example.com/b.css", "https://example.com/a.css", "https://cdn.example.com/b.css"),
+        ("b.css", "https://example.com", "https://example.com/b.css"),
+        ("data:text/css,a{}", "css/a.css", "data:text/css,a{}"),
+    ] {
+        assert_eq!(resolve_url(url, base), resolved, "{url} from {base}");
+    }
+}
@@ -0,0 +1,167 @@
+//! Checks that tokens give back everything they allocate, using an allocator that counts live bytes per thread.
+mod common;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use common::{call, call_str};
+use cssparser::{CowRcStr, Token as CssToken};
+use cssparser_fii::{token_types::TokenType, token_union::*, Token};
+
+struct CountingAllocator;
+
+thread_local! {
+    static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE_BYTES.with(|live| live.set(live.get() + layout.size() as isize));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.with(|live| live.set(live.get() - layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn live_bytes() -> isize {
+    LIVE_BYTES.with(Cell::get)
+}
+
+/// Runs `f` and returns how many bytes it left allocated.
+fn leaked(f: impl FnOnce()) -> isize {
+    let before = live_bytes();
+    f();
+    live_bytes() - before
+}
+
+/// One token of every type, with owned strings so the conversion has to copy them.
+fn every_token() -> Vec<CssToken<'static>> {
+    let s = |s: &str| CowRcStr::from(s.to_string());
+    vec![
+        CssToken::Ident(s("ident")),
+        CssToken::AtKeyword(s("media")),
+        CssToken::Hash(s("f00")),
+        CssToken::IDHash(s("id")),
+        CssToken::QuotedString(s("string")),
+        CssToken::UnquotedUrl(s("image.png")),
+        CssToken::Delim('+'),
+        CssToken::Number { has_sign: false, value: 1.0, int_value: Some(1) },
+        CssToken::Percentage { has_sign: true, unit_value: 0.5, int_value: Some(50) },
+        CssToken::Dimension { has_sign: false, value: 2.0, int_value: Some(2), unit: s("px") },
+        CssToken::WhiteSpace(" "),
+        CssToken::Comment(" comment "),
+        CssToken::Colon,
+        CssToken::Semicolon,
+        CssToken::Comma,
+        CssToken::IncludeMatch,
+        CssToken::DashMatch,
+        CssToken::PrefixMatch,
+        CssToken::SuffixMatch,
+        CssToken::SubstringMatch,
+        CssToken::CDO,
+        CssToken::CDC,
+        CssToken::Function(s("var")),
+        CssToken::ParenthesisBlock,
+        CssToken::SquareBracketBlock,
+        CssToken::CurlyBracketBlock,
+        CssToken::BadUrl(s("bad url")),
+        CssToken::BadString(s("bad string")),
+        CssToken::CloseParenthesis,
+        CssToken::CloseSquareBracket,
+        CssToken::CloseCurlyBracket,
+    ]
+}
+
+#[test]
+fn every_token_type_is_covered() {
+    let mut types: Vec<u8> = every_token().iter().map(|token| TokenType::from(token) as u8).collect();
+    types.sort();
+    types.dedup();
+    assert_eq!(types.len(), 31);
+}
+
+#[test]
+fn tokens_free_their_values() {
+    for token in every_token() {
+        let token_type = TokenType::from(&token);
+        assert_eq!(leaked(|| drop(Token::from(&token))), 0, "{:?} leaked", token_type);
+    }
+}
+
+#[test]
+fn getters_return_values_that_can_be_freed() {
+    for token in every_token() {
+        let token = Token::from(&token);
+        let token_type = token.token_type;
+        let value = &*token.value;
+        let leak = leaked(|| match token_type {
+            TokenType::Ident => free_string(ident(value)),
+            TokenType::AtKeyword => free_string(at_keyword(value)),
+            TokenType::Hash => free_string(hash(value)),
+            TokenType::IDHash => free_string(id_hash(value)),
+            TokenType::QuotedString => free_string(quoted_string(value)),
+            TokenType::UnquotedUrl => free_string(unquoted_url(value)),
+            TokenType::Comment => free_string(comment(value)),
+            TokenType::Function => free_string(function(value)),
+            TokenType::WhiteSpace => free_string(whitespace(value)),
+            TokenType::BadString => free_string(bad_string(value)),
+            TokenType::BadUrl => free_string(bad_url(value)),
+            TokenType::Number => free_number(number(value)),
+            TokenType::Percentage => free_percentage(percentage(value)),
+            TokenType::Dimension => free_dimension(dimension(value)),
+            TokenType::Delim => free_string(delim_utf8(value)),
+            _ => {},
+        });
+        assert_eq!(leak, 0, "getter for {:?} leaked", token_type);
+    }
+}
+
+#[test]
+fn getters_of_another_type_do_not_read_the_union() {
+    let token = Token::from(CssToken::Number { has_sign: false, value: 1.0, int_value: Some(1) });
+    assert_eq!(ident(&token.value).len(), 0);
+    assert_eq!(delim(&token.value), 0);
+    // the fields are private, but the debug output is enough to tell the unit is empty
+    assert!(format!("{:?}", dimension(&token.value)).contains("unit: \"\""));
+}
+
+#[test]
+fn free_tokens_releases_a_whole_stylesheet() {
+    let css = include_str!("../test/test.css");
+    let leak = leaked(|| {
+        let (_, tokens, error) = call_str(css, cssparser_fii::parse_css);
+        cssparser_fii::free_tokens(tokens);
+        cssparser_fii::error::free_error(error);
+    });
+    assert_eq!(leak, 0);
+}
+
+#[test]
+fn token_list_free_releases_the_list_and_its_text() {
+    let css = include_str!("../test/test.css");
+    let leak = leaked(|| {
+        let (_, list, _) = call(|list, error| cssparser_fii::token_list::parse_css_list(css.as_ptr(), css.len(), list, error));
+        let list = list.unwrap();
+        assert!(cssparser_fii::token_list::token_list_len(&list) > 0);
+        cssparser_fii::token_list::token_list_free(list);
+    });
+    assert_eq!(leak, 0);
+}
+
+#[test]
+fn free_token_views_releases_the_arena() {
+    // every escape is copied into the arena
+    let css = ".\\61 \\62 { content: '\\63' } ".repeat(50);
+    let leak = leaked(|| {
+        let (_, views, _) = call(|views, error| cssparser_fii::view::parse_css_views(css.as_ptr(), css.len(), views, error));
+        assert_eq!(views.tokens.iter().filter(|token| token.text.owned).count(), 100);
+        cssparser_fii::view::free_token_views(views);
+    });
+    assert_eq!(leak, 0);
+}
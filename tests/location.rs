@@ -0,0 +1,45 @@
+//! The locations `parse_css` gives its tokens.
+mod common;
+
+use common::call_str;
+use cssparser_fii::{error::Status, free_tokens, parse_css};
+
+/// The `(start, end, line, column)` of every token of `css`.
+fn locations(css: &str) -> Vec<(usize, usize, u32, u32)> {
+    let (status, tokens, _) = call_str(css, parse_css);
+    assert_eq!(status, Status::Ok);
+    let locations = tokens.iter().map(|token| (token.location.start, token.location.end, token.location.line, token.location.column)).collect();
+    free_tokens(tokens);
+    locations
+}
+
+#[test]
+fn lines_and_columns() {
+    assert_eq!(locations("a {\n  b: c;\n}"), [
+        (0, 1, 1, 1),
+        (2, 3, 1, 3),
+        (6, 7, 2, 3),
+        (7, 8, 2, 4),
+        (9, 10, 2, 6),
+        (10, 11, 2, 7),
+        (12, 13, 3, 1),
+    ]);
+    // offsets count bytes, columns count UTF-16 code units
+    assert_eq!(locations("é 😀 x"), [(0, 2, 1, 1), (3, 7, 1, 3), (8, 9, 1, 6)]);
+    // `\r\n` is a single line break
+    assert_eq!(locations("a\r\n\r\nb"), [(0, 1, 1, 1), (5, 6, 3, 1)]);
+}
+
+#[test]
+fn synthesized_closing_tokens() {
+    // the closing tokens of blocks left open are empty and sit at the end of the input, functions have none
+    assert_eq!(locations("a {\r\n  f(1\n"), [
+        (0, 1, 1, 1),
+        (2, 3, 1, 3),
+        (7, 9, 2, 3),
+        (9, 10, 2, 5),
+        (11, 11, 3, 1),
+    ]);
+    // closing tokens found in the input cover them
+    assert_eq!(locations("[(\n)]"), [(0, 1, 1, 1), (1, 2, 1, 2), (3, 4, 2, 1), (4, 5, 2, 2)]);
+}
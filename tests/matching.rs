@@ -0,0 +1,306 @@
+//! Matching selectors against a small tree that is only reachable through the callbacks.
+mod common;
+
+use std::ffi::c_void;
+
+use common::{call, call_str};
+use cssparser_fii::{error::{ErrorKind, Status}, matching::*, selector::*, view::StrView};
+
+struct Node {
+    name: &'static str,
+    id: Option<&'static str>,
+    classes: Vec<&'static str>,
+    attributes: Vec<(&'static str, &'static str)>,
+    parent: Option<usize>,
+}
+
+struct Dom {
+    nodes: Vec<Node>,
+    hovered: usize,
+}
+
+impl Dom {
+    fn add(&mut self, parent: Option<usize>, name: &'static str, id: Option<&'static str>, classes: &[&'static str], attributes: &[(&'static str, &'static str)]) -> usize {
+        self.nodes.push(Node { name, id, classes: classes.to_vec(), attributes: attributes.to_vec(), parent });
+        self.nodes.len() - 1
+    }
+
+    fn element(&self, index: usize) -> Element {
+        &self.nodes[index] as *const Node as Element
+    }
+
+    fn index(&self, element: Element) -> usize {
+        (element as usize - self.nodes.as_ptr() as usize) / std::mem::size_of::<Node>()
+    }
+
+    fn parent_of(&self, index: usize) -> Option<usize> {
+        self.nodes[index].parent
+    }
+
+    fn children(&self, parent: Option<usize>) -> Vec<usize> {
+        (0..self.nodes.len()).filter(|&i| self.parent_of(i) == parent).collect()
+    }
+
+    /// The sibling `offset` positions away.
+    fn sibling(&self, index: usize, offset: isize) -> Element {
+        let siblings = self.children(self.parent_of(index));
+        let position = siblings.iter().position(|&i| i == index).unwrap() as isize + offset;
+        match usize::try_from(position).ok().and_then(|position| siblings.get(position)) {
+            Some(&sibling) => self.element(sibling),
+            None => std::ptr::null(),
+        }
+    }
+}
+
+fn view(s: &str) -> StrView {
+    StrView { ptr: s.as_ptr(), len: s.len(), owned: false }
+}
+
+fn absent() -> StrView {
+    StrView { ptr: std::ptr::null(), len: 0, owned: false }
+}
+
+unsafe fn context<'a>(element: Element, user: *mut c_void) -> (&'a Dom, usize) {
+    let dom = &*(user as *const Dom);
+    (dom, dom.index(element))
+}
+
+unsafe fn as_str<'a>(name: StrView) -> &'a str {
+    std::str::from_utf8(std::slice::from_raw_parts(name.ptr, name.len)).unwrap()
+}
+
+unsafe extern "C" fn local_name(element: Element, user: *mut c_void) -> StrView {
+    let (dom, index) = context(element, user);
+    view(dom.nodes[index].name)
+}
+
+unsafe extern "C" fn id(element: Element, user: *mut c_void) -> StrView {
+    let (dom, index) = context(element, user);
+    dom.nodes[index].id.map_or(absent(), view)
+}
+
+unsafe extern "C" fn has_class(element: Element, name: StrView, user: *mut c_void) -> bool {
+    let (dom, index) = context(element, user);
+    dom.nodes[index].classes.contains(&as_str(name))
+}
+
+unsafe extern "C" fn attribute(element: Element, name: StrView, user: *mut c_void) -> StrView {
+    let (dom, index) = context(element, user);
+    let name = as_str(name);
+    dom.nodes[index].attributes.iter().find(|(attribute, _)| *attribute == name).map_or(absent(), |(_, value)| view(value))
+}
+
+unsafe extern "C" fn parent(element: Element, user: *mut c_void) -> Element {
+    let (dom, index) = context(element, user);
+    dom.parent_of(index).map_or(std::ptr::null(), |parent| dom.element(parent))
+}
+
+unsafe extern "C" fn previous_sibling(element: Element, user: *mut c_void) -> Element {
+    let (dom, index) = context(element, user);
+    dom.sibling(index, -1)
+}
+
+unsafe extern "C" fn next_sibling(element: Element, user: *mut c_void) -> Element {
+    let (dom, index) = context(element, user);
+    dom.sibling(index, 1)
+}
+
+unsafe extern "C" fn first_child(element: Element, user: *mut c_void) -> Element {
+    let (dom, index) = context(element, user);
+    dom.children(Some(index)).first().map_or(std::ptr::null(), |&child| dom.element(child))
+}
+
+unsafe extern "C" fn is_root(element: Element, user: *mut c_void) -> bool {
+    let (dom, index) = context(element, user);
+    dom.parent_of(index).is_none()
+}
+
+unsafe extern "C" fn match_pseudo_class(element: Element, name: StrView, user: *mut c_void) -> bool {
+    let (dom, index) = context(element, user);
+    as_str(name) == "hover" && index == dom.hovered
+}
+
+const CALLBACKS: ElementCallbacks = ElementCallbacks {
+    local_name: Some(local_name),
+    id: Some(id),
+    has_class: Some(has_class),
+    attribute: Some(attribute),
+    parent: Some(parent),
+    previous_sibling: Some(previous_sibling),
+    next_sibling: Some(next_sibling),
+    first_child: Some(first_child),
+    is_root: Some(is_root),
+    match_pseudo_class: Some(match_pseudo_class),
+};
+
+/// ```text
+/// html
+///   body
+///     div#main.content.wide[lang=en-US][data-x="Foo Bar"]
+///       p.first  p  span  p.last
+///     ul
+///       li  li  li  li  li
+/// ```
+fn dom() -> Dom {
+    let mut dom = Dom { nodes: Vec::new(), hovered: 4 };
+    let html = dom.add(None, "html", None, &[], &[]);
+    let body = dom.add(Some(html), "body", None, &[], &[]);
+    let div = dom.add(Some(body), "div", Some("main"), &["content", "wide"], &[("lang", "en-US"), ("data-x", "Foo Bar")]);
+    dom.add(Some(div), "p", None, &["first"], &[]);
+    dom.add(Some(div), "p", None, &[], &[]);
+    dom.add(Some(div), "span", None, &[], &[]);
+    dom.add(Some(div), "p", None, &["last"], &[]);
+    let ul = dom.add(Some(body), "ul", None, &[], &[]);
+    for _ in 0..5 {
+        dom.add(Some(ul), "li", None, &[], &[]);
+    }
+    dom
+}
+
+fn parse(css: &str) -> SelectorList {
+    let (status, list, _) = call_str(css, parse_selector_list);
+    assert_eq!(status, Status::Ok);
+    list
+}
+
+/// Whether the selector at `index` matches `element`, the call has to succeed.
+fn matches(list: &SelectorList, index: usize, element: Element, pseudo_element: *const safer_ffi::c_char, callbacks: &ElementCallbacks, user: *mut c_void) -> bool {
+    let (status, matches, _) = call(|matches, error| selector_matches(list, index, element, pseudo_element, callbacks, user, matches, error));
+    assert_eq!(status, Status::Ok);
+    matches
+}
+
+fn list_matches(list: &SelectorList, element: Element, user: *mut c_void) -> bool {
+    let (status, matches, _) = call(|matches, error| selector_list_matches(list, element, &CALLBACKS, user, matches, error));
+    assert_eq!(status, Status::Ok);
+    matches
+}
+
+/// The indices of the elements `selector` matches.
+fn matching(dom: &mut Dom, selector: &str) -> Vec<usize> {
+    matching_with(dom, selector, &CALLBACKS, None)
+}
+
+fn matching_with(dom: &mut Dom, selector: &str, callbacks: &ElementCallbacks, pseudo_element: Option<&str>) -> Vec<usize> {
+    let list = parse(selector);
+    let pseudo_element = pseudo_element.map(|pseudo_element| std::ffi::CString::new(pseudo_element).unwrap());
+    let pseudo_element = pseudo_element.as_ref().map_or(std::ptr::null(), |pseudo_element| pseudo_element.as_ptr() as *const _);
+    let user = dom as *mut Dom as *mut c_void;
+    let found = (0..dom.nodes.len())
+        .filter(|&i| {
+            let element = unsafe { &*(user as *const Dom) }.element(i);
+            (0..list.roots).any(|root| matches(&list, root, element, pseudo_element, callbacks, user))
+        })
+        .collect();
+    free_selector_list(list);
+    found
+}
+
+#[test]
+fn simple_selectors() {
+    let dom = &mut dom();
+    assert_eq!(matching(dom, "p"), [3, 4, 6]);
+    assert_eq!(matching(dom, "P"), [3, 4, 6]);
+    assert_eq!(matching(dom, "#main.content"), [2]);
+    assert_eq!(matching(dom, ".wide.missing"), []);
+    assert_eq!(matching(dom, ":root"), [0]);
+    assert_eq!(matching(dom, "*").len(), dom.nodes.len());
+}
+
+#[test]
+fn combinators() {
+    let dom = &mut dom();
+    assert_eq!(matching(dom, "body p"), [3, 4, 6]);
+    assert_eq!(matching(dom, "body > p"), []);
+    assert_eq!(matching(dom, "div > .first + p"), [4]);
+    assert_eq!(matching(dom, "p + span"), [5]);
+    assert_eq!(matching(dom, ".first ~ p"), [4, 6]);
+    assert_eq!(matching(dom, "html div ~ ul > li:first-child"), [8]);
+}
+
+#[test]
+fn attributes() {
+    let dom = &mut dom();
+    for (selector, matches) in [
+        ("[lang]", true),
+        ("[missing]", false),
+        ("[lang|=en]", true),
+        ("[lang=en-us]", false),
+        ("[lang=en-us i]", true),
+        ("[data-x~=Bar]", true),
+        ("[data-x~=Ba]", false),
+        ("[data-x^=Foo]", true),
+        ("[data-x$='ar']", true),
+        ("[data-x*='o B']", true),
+        ("[data-x*='']", false),
+    ] {
+        assert_eq!(matching(dom, selector) == [2], matches, "{selector}");
+    }
+}
+
+#[test]
+fn tree_structural_pseudo_classes() {
+    let dom = &mut dom();
+    assert_eq!(matching(dom, "li:nth-child(odd)"), [8, 10, 12]);
+    assert_eq!(matching(dom, "li:nth-child(-n+2)"), [8, 9]);
+    assert_eq!(matching(dom, "li:nth-last-child(2)"), [11]);
+    assert_eq!(matching(dom, "p:nth-of-type(2)"), [4]);
+    assert_eq!(matching(dom, "div > :nth-child(2 of p)"), [4]);
+    assert_eq!(matching(dom, "div > :last-of-type"), [5, 6]);
+    assert_eq!(matching(dom, "span:only-of-type"), [5]);
+    assert_eq!(matching(dom, "body > :only-child"), []);
+    assert_eq!(matching(dom, "div > :first-child, ul > :last-child"), [3, 12]);
+}
+
+#[test]
+fn logical_pseudo_classes() {
+    let dom = &mut dom();
+    assert_eq!(matching(dom, "p:not(.first, .last)"), [4]);
+    assert_eq!(matching(dom, "div :is(span, .last)"), [5, 6]);
+    assert_eq!(matching(dom, ":where(ul) li:nth-child(5)"), [12]);
+    assert_eq!(matching(dom, ":has(> span)"), [2]);
+    assert_eq!(matching(dom, ":has(+ ul)"), [2]);
+    assert_eq!(matching(dom, "ul:has(p)"), []);
+    assert_eq!(matching(dom, ":has(li)"), [0, 1, 7]);
+}
+
+#[test]
+fn host_pseudo_classes_and_pseudo_elements() {
+    let dom = &mut dom();
+    assert_eq!(matching(dom, "p:hover"), [4]);
+    let without_pseudo_classes = ElementCallbacks { match_pseudo_class: None, ..CALLBACKS };
+    assert_eq!(matching_with(dom, "p:hover", &without_pseudo_classes, None), []);
+
+    assert_eq!(matching(dom, "p::before"), []);
+    assert_eq!(matching_with(dom, "p::before", &CALLBACKS, Some("before")), [3, 4, 6]);
+    assert_eq!(matching_with(dom, ".first:after", &CALLBACKS, Some("after")), [3]);
+    assert_eq!(matching_with(dom, "p", &CALLBACKS, Some("before")), []);
+}
+
+#[test]
+fn selector_lists() {
+    let dom = dom();
+    let list = parse("span, li:first-child");
+    let user = &dom as *const Dom as *mut c_void;
+    assert!(list_matches(&list, dom.element(5), user));
+    assert!(list_matches(&list, dom.element(8), user));
+    assert!(!list_matches(&list, dom.element(9), user));
+    // out of bounds
+    assert!(!matches(&list, 99, dom.element(5), std::ptr::null(), &CALLBACKS, user));
+    free_selector_list(list);
+}
+
+#[test]
+fn missing_callbacks() {
+    let dom = dom();
+    let list = parse("span");
+    let user = &dom as *const Dom as *mut c_void;
+    let without_parent = ElementCallbacks { parent: None, ..CALLBACKS };
+    let (status, matches, error) = call(|matches, error| selector_matches(&list, 0, dom.element(5), std::ptr::null(), &without_parent, user, matches, error));
+    assert_eq!((status, matches, error.kind), (Status::InvalidInput, false, ErrorKind::NullPointer));
+    assert_eq!(error.message.to_string(), "the `parent` callback is null");
+
+    let (status, matches, _) = call(|matches, error| selector_list_matches(&list, dom.element(5), &without_parent, user, matches, error));
+    assert_eq!((status, matches), (Status::InvalidInput, false));
+    free_selector_list(list);
+}
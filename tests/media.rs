@@ -0,0 +1,195 @@
+//! Parsing media query lists and evaluating them against an environment.
+mod common;
+
+use common::{call, call_str};
+use cssparser_fii::{error::{ErrorKind, Status}, free_tokens, media::*, parse_css};
+
+fn parse(css: &str) -> MediaQueryList {
+    let (status, list, _) = call_str(css, parse_media_query_list);
+    assert_eq!(status, Status::Ok);
+    list
+}
+
+fn matches(css: &str, environment: &MediaEnvironment) -> bool {
+    let list = parse(css);
+    let matches = media_query_list_matches(&list, environment);
+    free_media_query_list(list);
+    matches
+}
+
+fn viewport(width: f32, height: f32) -> MediaEnvironment {
+    MediaEnvironment { width, height, ..media_environment_default() }
+}
+
+#[test]
+fn structure() {
+    let list = parse("only screen and (min-width: 40em), not print, (400px <= width < 700px) or (hover)");
+    assert!(list.errors.is_empty());
+    let queries: Vec<_> = list.queries.iter().map(|query| (query.qualifier, query.media_type.to_string(), query.has_condition)).collect();
+    assert_eq!(queries, [
+        (MediaQualifier::Only, "screen".to_string(), true),
+        (MediaQualifier::Not, "print".to_string(), false),
+        (MediaQualifier::None, "all".to_string(), true),
+    ]);
+
+    let min_width = &list.conditions[list.queries[0].condition];
+    assert_eq!((min_width.condition_type, min_width.name.to_string(), min_width.comparison), (MediaConditionType::Feature, "width".to_string(), MediaComparison::GreaterOrEqual));
+    assert_eq!((min_width.value.value_type, min_width.value.number, min_width.value.unit.to_string()), (MediaValueType::Length, 40.0, "em".to_string()));
+    assert_eq!((min_width.location.start, min_width.location.end), (16, 33));
+
+    let or = &list.conditions[list.queries[2].condition];
+    assert_eq!((or.condition_type, or.child_count), (MediaConditionType::Or, 2));
+    let range = &list.conditions[or.first_child];
+    assert_eq!((range.name.to_string(), range.comparison, range.value.number), ("width".to_string(), MediaComparison::GreaterOrEqual, 400.0));
+    assert_eq!((range.second_comparison, range.second_value.number), (MediaComparison::Less, 700.0));
+    let hover = &list.conditions[or.first_child + 1];
+    assert_eq!((hover.name.to_string(), hover.comparison), ("hover".to_string(), MediaComparison::None));
+    free_media_query_list(list);
+
+    // the conditions of the queries come first, then every level of operands after the one before it
+    let list = parse("(a) and ((b) or (c)), not (d)");
+    assert_eq!((list.queries[0].condition, list.queries[1].condition), (0, 1));
+    let layout: Vec<_> = list.conditions.iter().map(|condition| (condition.condition_type, condition.first_child, condition.child_count)).collect();
+    assert_eq!(layout, [
+        (MediaConditionType::And, 2, 2),
+        (MediaConditionType::Not, 4, 1),
+        (MediaConditionType::Feature, 0, 0),
+        (MediaConditionType::Or, 5, 2),
+        (MediaConditionType::Feature, 0, 0),
+        (MediaConditionType::Feature, 0, 0),
+        (MediaConditionType::Feature, 0, 0),
+    ]);
+    free_media_query_list(list);
+}
+
+#[test]
+fn invalid_queries_become_not_all() {
+    let list = parse("screen, print and, print and (color) or (hover), 12px, not (color)");
+    assert_eq!(list.queries.len(), 5);
+    let errors: Vec<_> = list.errors.iter().map(|error| (error.location.start, error.location.end)).collect();
+    assert_eq!(errors, [(8, 17), (19, 47), (49, 53)]);
+    for index in [1, 2, 3] {
+        let query = &list.queries[index];
+        assert_eq!((query.qualifier, query.media_type.to_string()), (MediaQualifier::Not, "all".to_string()));
+        assert!(!media_query_matches(&list, index, &media_environment_default()));
+    }
+    assert!(media_query_matches(&list, 0, &media_environment_default()));
+    free_media_query_list(list);
+
+    let list = parse("  ");
+    assert!(list.queries.is_empty() && list.errors.is_empty());
+    assert!(media_query_list_matches(&list, &media_environment_default()));
+    free_media_query_list(list);
+}
+
+#[test]
+fn ranges() {
+    let small = viewport(500.0, 800.0);
+    let large = viewport(1200.0, 800.0);
+    for (css, on_small, on_large) in [
+        ("(min-width: 600px)", false, true),
+        ("(max-width: 600px)", true, false),
+        ("(400px <= width <= 700px)", true, false),
+        ("(width > 37.5em)", false, true),
+        ("(1000px < width)", false, true),
+        ("(width = 500px)", true, false),
+        ("(orientation: portrait)", true, false),
+        ("(min-aspect-ratio: 16/9)", false, false),
+        ("(aspect-ratio > 1)", false, true),
+        ("(width >= 50vh)", true, true),
+        // the same lengths as in `calc()`
+        ("(width > 70ch)", false, true),
+        ("(min-width: 30lh)", false, true),
+        ("(width)", true, true),
+    ] {
+        assert_eq!((matches(css, &small), matches(css, &large)), (on_small, on_large), "{css}");
+    }
+}
+
+#[test]
+fn types_and_logic() {
+    let screen = media_environment_default();
+    let print = MediaEnvironment { media_type: MediaType::Print, ..screen };
+    for (css, on_screen, on_print) in [
+        ("all", true, true),
+        ("screen", true, false),
+        ("not screen", false, true),
+        ("only print", false, true),
+        ("tv", false, false),
+        ("print, screen and (color)", true, true),
+        ("not print and (color)", true, false),
+        ("not (color)", false, false),
+        ("(color) and (not (grid))", true, true),
+        ("(monochrome) or (hover)", true, true),
+    ] {
+        assert_eq!((matches(css, &screen), matches(css, &print)), (on_screen, on_print), "{css}");
+    }
+}
+
+#[test]
+fn preferences_and_devices() {
+    let phone = MediaEnvironment {
+        resolution: 3.0,
+        prefers_color_scheme: ColorScheme::Dark,
+        prefers_reduced_motion: true,
+        pointer: Pointer::Coarse,
+        hover: false,
+        any_pointer: Pointer::Coarse,
+        any_hover: false,
+        ..viewport(390.0, 844.0)
+    };
+    let desktop = media_environment_default();
+    for (css, on_phone, on_desktop) in [
+        ("(prefers-color-scheme: dark)", true, false),
+        ("(prefers-reduced-motion)", true, false),
+        ("(prefers-reduced-motion: no-preference)", false, true),
+        ("(min-resolution: 2dppx)", true, false),
+        ("(resolution >= 192dpi)", true, false),
+        ("(resolution: 1x)", false, true),
+        ("(pointer: coarse)", true, false),
+        ("(hover: hover) and (any-pointer: fine)", false, true),
+    ] {
+        assert_eq!((matches(css, &phone), matches(css, &desktop)), (on_phone, on_desktop), "{css}");
+    }
+}
+
+#[test]
+fn unknown_conditions() {
+    let environment = media_environment_default();
+    // unknown features, units and syntax never match, even negated
+    for css in ["(frobnicate)", "(width: )", "(min-width)", "(100px < width > 10px)", "(width > 10furlongs)", "not (frobnicate)", "(foo bar)", "not (foo bar)", "(color) and func()", "(prefers-color-scheme > light)"] {
+        assert!(!matches(css, &environment), "{css}");
+    }
+    assert!(matches("(color) or (foo bar)", &environment));
+
+    let list = parse("(foo bar)");
+    let unknown = &list.conditions[list.queries[0].condition];
+    assert_eq!((unknown.condition_type, unknown.name.to_string()), (MediaConditionType::Unknown, "(foo bar)".to_string()));
+    free_media_query_list(list);
+}
+
+#[test]
+fn from_tokens() {
+    let (status, tokens, _) = call_str("@media screen and (min-width: 40em), print and {}", parse_css);
+    assert_eq!(status, Status::Ok);
+    // the prelude is everything between the at-keyword and the block
+    let prelude = &tokens[1..tokens.len() - 2];
+
+    let (status, list, _) = call(|list, error| parse_media_query_list_tokens(prelude.into(), list, error));
+    assert_eq!(status, Status::Ok);
+    assert_eq!((list.queries[0].location.start, list.queries[0].location.end), (7, 35));
+    let feature = &list.conditions[list.queries[0].condition];
+    assert_eq!((feature.location.start, feature.location.end), (18, 35));
+    assert_eq!((list.errors[0].kind, list.errors[0].location.start, list.errors[0].location.end), (ErrorKind::EndOfInput, 37, 46));
+    assert!(media_query_matches(&list, 0, &media_environment_default()));
+    free_media_query_list(list);
+    free_tokens(tokens);
+}
+
+#[test]
+fn null_input() {
+    let (status, list, _) = call(|list, error| parse_media_query_list(std::ptr::null(), list, error));
+    assert_eq!(status, Status::InvalidInput);
+    assert!(list.queries.is_empty());
+    free_media_query_list(list);
+}
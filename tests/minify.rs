@@ -0,0 +1,83 @@
+//! The minified stylesheet must tokenize to the same values as the original one.
+mod common;
+
+use common::call_str;
+use cssparser_fii::{error::Status, free_tokens, minify::minify_css, parse_css, token_union::free_string, Token};
+
+fn parse(css: &str) -> safer_ffi::Vec<Token> {
+    let (status, tokens, _) = call_str(css, parse_css);
+    assert_eq!(status, Status::Ok);
+    tokens
+}
+
+fn minify(css: &str) -> String {
+    let (status, minified, _) = call_str(css, minify_css);
+    assert_eq!(status, Status::Ok);
+    let string = minified.to_string();
+    free_string(minified);
+    string
+}
+
+/// Numbers may be written differently (`0.50` and `.5`, `0px` and `0`) as long as they mean the same thing.
+fn same_value(original: &cssparser::Token, minified: &cssparser::Token) -> bool {
+    use cssparser::Token::*;
+    match (original, minified) {
+        (Number { value: a, .. }, Number { value: b, .. }) | (Percentage { unit_value: a, .. }, Percentage { unit_value: b, .. }) => a == b,
+        (Dimension { value: a, unit: u, .. }, Dimension { value: b, unit: v, .. }) => a == b && u == v,
+        (Dimension { value: 0.0, .. }, Number { value: 0.0, .. }) => true,
+        _ => original == minified,
+    }
+}
+
+/// The tokens that matter, a semicolon right before the end of a block does not.
+fn significant<'a>(tokens: &'a [Token]) -> Vec<cssparser::Token<'a>> {
+    let tokens: Vec<cssparser::Token> = tokens.iter().map(|token| (&*token.value).into()).collect();
+    let ends_block = |i: usize| matches!(tokens.get(i + 1), Some(cssparser::Token::CloseCurlyBracket));
+    tokens.iter().enumerate().filter(|&(i, token)| !(*token == cssparser::Token::Semicolon && ends_block(i))).map(|(_, token)| token.clone()).collect()
+}
+
+fn assert_equivalent(css: &str) -> String {
+    let minified = minify(css);
+    let (original, reparsed) = (parse(css), parse(&minified));
+    {
+        let (a, b) = (significant(&original), significant(&reparsed));
+        assert_eq!(a.len(), b.len(), "{minified}");
+        for (a, b) in a.iter().zip(&b) {
+            assert!(same_value(a, b), "{a:?} became {b:?} in {minified}");
+        }
+    }
+    free_tokens(original);
+    free_tokens(reparsed);
+    minified
+}
+
+#[test]
+fn test_css_is_equivalent_and_smaller() {
+    let css = include_str!("../test/test.css");
+    let minified = assert_equivalent(css);
+    assert!(minified.len() < css.len());
+    assert!(!minified.contains("/*"));
+    assert_eq!(minify(&minified), minified);
+}
+
+#[test]
+fn numbers_are_shortened() {
+    assert_eq!(assert_equivalent("a { opacity: 0.50; margin: 0px -0.5em 0% }"), "a{opacity:.5;margin:0 -.5em 0%}");
+    // `calc(0 + 1px)` would be invalid
+    assert_eq!(assert_equivalent("a { width: calc(0px + 1px) }"), "a{width:calc(0px + 1px)}");
+    // nor would `calc(var(--x) + 1px)`, so custom properties keep their units up to the end of their value
+    assert_eq!(assert_equivalent("a { --x: 0px 0.5em; margin: 0px; b { --y : 0px } top: 0px }"), "a{--x:0px .5em;margin:0;b{--y :0px}top:0}");
+}
+
+#[test]
+fn significant_whitespace_is_kept() {
+    assert_eq!(assert_equivalent("a .b, c  d { }"), "a .b,c d{}");
+    assert_eq!(assert_equivalent("a :hover { }"), "a :hover{}");
+    assert_eq!(assert_equivalent("@media screen and (min-width: 10px) { }"), "@media screen and (min-width:10px){}");
+}
+
+#[test]
+fn last_semicolons_are_removed() {
+    assert_eq!(assert_equivalent("a { b: c; d: e; }"), "a{b:c;d:e}");
+    assert_eq!(assert_equivalent("@import 'x.css';"), "@import \"x.css\";");
+}
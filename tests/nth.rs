@@ -0,0 +1,70 @@
+//! The `An+B` microsyntax, checked against the examples of CSS Syntax Level 3.
+mod common;
+
+use common::{call, call_str};
+use cssparser_fii::{error::{ErrorKind, Status}, free_tokens, nth::*, parse_css_bytes};
+
+fn parse(css: &str) -> Result<(i32, i32), ErrorKind> {
+    let (status, Nth { a, b }, error) = call_str(css, parse_nth);
+    match status {
+        Status::Ok => Ok((a, b)),
+        _ => Err(error.kind),
+    }
+}
+
+#[test]
+fn valid() {
+    for (css, expected) in [
+        ("odd", (2, 1)),
+        ("EVEN", (2, 0)),
+        ("3", (0, 3)),
+        ("+5", (0, 5)),
+        ("n", (1, 0)),
+        ("+n", (1, 0)),
+        ("-n+3", (-1, 3)),
+        ("-N-1", (-1, -1)),
+        ("2n+1", (2, 1)),
+        ("2n+ 1", (2, 1)),
+        ("2n +1", (2, 1)),
+        (" 2n - 1 ", (2, -1)),
+        ("n- 1", (1, -1)),
+        ("10n-1", (10, -1)),
+        ("-2n", (-2, 0)),
+    ] {
+        assert_eq!(parse(css), Ok(expected), "{css}");
+    }
+}
+
+#[test]
+fn invalid() {
+    for css in ["+ 5", "+ n", "2n + -1", "2n +- 1", "1.5n", "2n+1.5", "3 n", "odd 1", "foo"] {
+        assert!(parse(css).is_err(), "{css}");
+    }
+    assert_eq!(parse(""), Err(ErrorKind::EndOfInput));
+}
+
+#[test]
+fn tokens() {
+    let css = "2n + foo";
+    let (status, tokens, _) = call(|tokens, error| parse_css_bytes(css.as_ptr(), css.len(), tokens, error));
+    assert_eq!(status, Status::Ok);
+
+    let (status, nth, _) = call(|nth, error| parse_nth_tokens((&tokens[..1]).into(), nth, error));
+    assert_eq!((status, nth), (Status::Ok, Nth { a: 2, b: 0 }));
+
+    let (status, _, error) = call(|nth, error| parse_nth_tokens((&tokens[..]).into(), nth, error));
+    assert_eq!(status, Status::ParseError);
+    assert_eq!((error.location.start, error.location.end), (5, 8));
+    free_tokens(tokens);
+}
+
+#[test]
+fn matching() {
+    let selected = |nth: Nth| (1..=10).filter(|&index| nth_matches(nth, index)).collect::<Vec<_>>();
+    assert_eq!(selected(Nth { a: 2, b: 1 }), [1, 3, 5, 7, 9]);
+    assert_eq!(selected(Nth { a: 3, b: -1 }), [2, 5, 8]);
+    assert_eq!(selected(Nth { a: -1, b: 3 }), [1, 2, 3]);
+    assert_eq!(selected(Nth { a: 0, b: 4 }), [4]);
+    assert_eq!(selected(Nth { a: 0, b: 0 }), []);
+    assert!(!nth_matches(Nth { a: -1, b: i32::MIN }, i32::MAX));
+}
@@ -0,0 +1,79 @@
+//! The token stream returned by `parse_css`.
+mod common;
+
+use common::{call, call_str};
+use cssparser_fii::{error::{ErrorKind, Status}, free_tokens, parse_css, parse_css_bytes, Token};
+
+fn parse(css: &str) -> safer_ffi::Vec<Token> {
+    let (status, tokens, _) = call_str(css, parse_css);
+    assert_eq!(status, Status::Ok);
+    tokens
+}
+
+fn parse_bytes(css: &[u8]) -> safer_ffi::Vec<Token> {
+    let (status, tokens, _) = call(|tokens, error| parse_css_bytes(css.as_ptr(), css.len(), tokens, error));
+    assert_eq!(status, Status::Ok);
+    tokens
+}
+
+fn values(tokens: &safer_ffi::Vec<Token>) -> Vec<cssparser::Token<'_>> {
+    tokens.iter().map(|token| (&*token.value).into()).collect()
+}
+
+#[test]
+fn functions_are_not_closed() {
+    let tokens = parse("a { color: var(--x, rgb(0 0 0)) }");
+    assert_eq!(values(&tokens), [
+        cssparser::Token::Ident("a".into()),
+        cssparser::Token::CurlyBracketBlock,
+        cssparser::Token::Ident("color".into()),
+        cssparser::Token::Colon,
+        cssparser::Token::Function("var".into()),
+        cssparser::Token::Ident("--x".into()),
+        cssparser::Token::Comma,
+        cssparser::Token::Function("rgb".into()),
+        cssparser::Token::Number { has_sign: false, value: 0.0, int_value: Some(0) },
+        cssparser::Token::Number { has_sign: false, value: 0.0, int_value: Some(0) },
+        cssparser::Token::Number { has_sign: false, value: 0.0, int_value: Some(0) },
+        // only blocks get a closing token, as they always have
+        cssparser::Token::CloseCurlyBracket,
+    ]);
+    assert_eq!((tokens[11].location.start, tokens[11].location.end), (32, 33));
+    free_tokens(tokens);
+
+    // a block left open is closed at the end of the input
+    let tokens = parse("calc((1px");
+    assert_eq!(values(&tokens)[3], cssparser::Token::CloseParenthesis);
+    assert_eq!((tokens[3].location.start, tokens[3].location.end), (9, 9));
+    free_tokens(tokens);
+}
+
+#[test]
+fn bytes() {
+    // invalid UTF-8 is replaced, locations count the bytes of the replacement
+    let tokens = parse_bytes(b"a\xffb c");
+    assert_eq!(values(&tokens), [cssparser::Token::Ident("a\u{fffd}b".into()), cssparser::Token::Ident("c".into())]);
+    assert_eq!((tokens[1].location.start, tokens[1].location.end), (6, 7));
+    free_tokens(tokens);
+
+    // the input does not end at a NUL, which is replaced like in any CSS
+    let tokens = parse_bytes(b"a\0b c");
+    assert_eq!(values(&tokens), [cssparser::Token::Ident("a\u{fffd}b".into()), cssparser::Token::Ident("c".into())]);
+    assert_eq!((tokens[1].location.start, tokens[1].location.end), (4, 5));
+    free_tokens(tokens);
+
+    // only the first `len` bytes are read
+    let tokens = parse_bytes(&b"a b"[..1]);
+    assert_eq!(values(&tokens), [cssparser::Token::Ident("a".into())]);
+    free_tokens(tokens);
+
+    let (status, tokens, _) = call(|tokens, error| parse_css_bytes(std::ptr::null(), 0, tokens, error));
+    assert_eq!(status, Status::Ok);
+    assert!(tokens.is_empty());
+    free_tokens(tokens);
+
+    let (status, tokens, error) = call(|tokens, error| parse_css_bytes(std::ptr::null(), 4, tokens, error));
+    assert_eq!((status, error.kind), (Status::InvalidInput, ErrorKind::NullPointer));
+    assert!(tokens.is_empty());
+    free_tokens(tokens);
+}
@@ -0,0 +1,60 @@
+//! Serializing tokens with `tokens_to_css` and parsing the result must give back the same tokens.
+mod common;
+
+use common::call;
+use cssparser_fii::{error::Status, free_tokens, parse_css_bytes, serialize::tokens_to_css, token_union::free_string, Token};
+
+fn parse(css: &str) -> safer_ffi::Vec<Token> {
+    let (status, tokens, _) = call(|tokens, error| parse_css_bytes(css.as_ptr(), css.len(), tokens, error));
+    assert_eq!(status, Status::Ok);
+    tokens
+}
+
+fn to_css(tokens: &safer_ffi::Vec<Token>) -> String {
+    let (status, css, _) = call(|css, error| tokens_to_css((&tokens[..]).into(), css, error));
+    assert_eq!(status, Status::Ok);
+    let string = css.to_string();
+    free_string(css);
+    string
+}
+
+fn assert_round_trips(css: &str) -> String {
+    let tokens = parse(css);
+    let serialized = to_css(&tokens);
+    let reparsed = parse(&serialized);
+
+    let original: Vec<cssparser::Token> = tokens.iter().map(|token| (&*token.value).into()).collect();
+    let round_tripped: Vec<cssparser::Token> = reparsed.iter().map(|token| (&*token.value).into()).collect();
+    assert_eq!(original, round_tripped, "{serialized}");
+    drop((original, round_tripped));
+
+    free_tokens(reparsed);
+    free_tokens(tokens);
+    serialized
+}
+
+#[test]
+fn test_css_round_trips() {
+    assert_round_trips(include_str!("../test/test.css"));
+}
+
+#[test]
+fn values_are_escaped_and_marked() {
+    assert_eq!(assert_round_trips(r#"@media{a#b{c:"d\"e"}}"#), r#"@media{a#b{c:"d\"e"}}"#);
+    assert_eq!(assert_round_trips("a{b:+1 -2.5px 50% .5}"), "a{b:+1 -2.5px 50%0.5}");
+    assert_eq!(assert_round_trips(r"\31 a{b:url(x.png) f(g)}"), r"\31 a{b:url(x.png)f(g)}");
+}
+
+#[test]
+fn functions_are_closed_with_their_block() {
+    // `parse_css` does not say where a function ends, so it runs until the block around it does
+    assert_eq!(assert_round_trips("a{b:f(1) 2}[g(x)]"), "a{b:f(1 2)}[g(x)]");
+    assert_eq!(assert_round_trips("f(g(1"), "f(g(1))");
+}
+
+#[test]
+fn adjacent_tokens_are_separated() {
+    assert_eq!(assert_round_trips("a b"), "a b");
+    assert_eq!(assert_round_trips("1 2 x-y"), "1 2 x-y");
+    assert_eq!(assert_round_trips("a/* comment */b"), "a b");
+}
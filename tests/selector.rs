@@ -0,0 +1,114 @@
+//! Selector structure and specificity, checked against the examples of Selectors Level 4.
+mod common;
+
+use common::call_str;
+use cssparser_fii::{error::{ErrorKind, Status}, selector::*, serialize::tokens_to_string};
+
+fn parse(css: &str) -> Result<SelectorList, ErrorKind> {
+    let (status, list, error) = call_str(css, parse_selector_list);
+    if status == Status::Ok {
+        Ok(list)
+    } else {
+        free_selector_list(list);
+        Err(error.kind)
+    }
+}
+
+fn specificity(css: &str) -> (u32, u32, u32) {
+    let list = parse(css).unwrap();
+    let Specificity { a, b, c } = list.selectors[0].specificity;
+    free_selector_list(list);
+    (a, b, c)
+}
+
+#[test]
+fn specificities() {
+    for (selector, expected) in [
+        ("*", (0, 0, 0)),
+        ("li", (0, 0, 1)),
+        ("ul li", (0, 0, 2)),
+        ("ul ol+li", (0, 0, 3)),
+        ("h1 + *[rel=up]", (0, 1, 1)),
+        ("ul ol li.red", (0, 1, 3)),
+        ("li.red.level", (0, 2, 1)),
+        ("#x34y", (1, 0, 0)),
+        ("#s12:not(FOO)", (1, 0, 1)),
+        (".foo :is(.bar, #baz)", (1, 1, 0)),
+        (":where(#a, .b) p", (0, 0, 1)),
+        ("li:nth-child(2n+1 of .x)", (0, 2, 1)),
+        ("a::before:hover", (0, 1, 2)),
+        ("a:first-line", (0, 0, 2)),
+        ("div:has(> #x)", (1, 0, 1)),
+    ] {
+        assert_eq!(specificity(selector), expected, "{selector}");
+    }
+}
+
+#[test]
+fn specificities_compare_like_tuples() {
+    let low = Specificity { a: 0, b: 5, c: 9 };
+    let high = Specificity { a: 1, b: 0, c: 0 };
+    assert!(specificity_compare(low, high) < 0);
+    assert!(specificity_compare(high, low) > 0);
+    assert_eq!(specificity_compare(low, low), 0);
+}
+
+#[test]
+fn compound_and_complex_selectors() {
+    let css = "a.b > c[href|='en' i]:nth-child(-n+3), #d ~ e::part(label)";
+    let list = parse(css).unwrap();
+    assert_eq!(list.roots, 2);
+
+    let first = &list.selectors[0];
+    assert_eq!(&css[first.location.start..first.location.end], "a.b > c[href|='en' i]:nth-child(-n+3)");
+    let types: Vec<ComponentType> = first.components.iter().map(|c| c.component_type).collect();
+    assert_eq!(types, [ComponentType::Type, ComponentType::Class, ComponentType::Combinator, ComponentType::Type, ComponentType::Attribute, ComponentType::PseudoClass]);
+    assert_eq!(first.components[2].combinator, Combinator::Child);
+    let attribute = &first.components[4];
+    assert_eq!((attribute.name.to_string(), attribute.attribute_operator, attribute.value.to_string(), attribute.case_insensitive), ("href".to_string(), AttributeOperator::DashMatch, "en".to_string(), true));
+    let nth = &first.components[5];
+    assert_eq!((nth.has_nth, nth.nth_a, nth.nth_b), (true, -1, 3));
+
+    let second = &list.selectors[1];
+    assert_eq!(second.components[1].combinator, Combinator::SubsequentSibling);
+    let part = &second.components[3];
+    assert_eq!((part.component_type, part.is_function), (ComponentType::PseudoElement, true));
+    assert_eq!(tokens_to_string(&part.arguments), "label");
+    free_selector_list(list);
+}
+
+#[test]
+fn nested_selector_lists() {
+    let list = parse("a:not(.b, #c):has(+ d)").unwrap();
+    let not = &list.selectors[0].components[1];
+    let arguments: Vec<(u32, u32, u32)> = list.selectors[not.first_selector..not.first_selector + not.selector_count]
+        .iter()
+        .map(|selector| (selector.specificity.a, selector.specificity.b, selector.specificity.c))
+        .collect();
+    assert_eq!(arguments, [(0, 1, 0), (1, 0, 0)]);
+
+    let has = &list.selectors[0].components[2];
+    let relative = &list.selectors[has.first_selector];
+    assert_eq!(relative.components[0].combinator, Combinator::NextSibling);
+    assert_eq!((not.first_selector, has.first_selector, has.selector_count), (1, 3, 1));
+    free_selector_list(list);
+
+    // the selectors of every root come before any of theirs
+    let list = parse("a:is(b:not(c)), d:where(e)").unwrap();
+    let layout: Vec<_> = list.selectors.iter()
+        .map(|selector| selector.components.iter().filter(|component| component.selector_count > 0).map(|component| (component.first_selector, component.selector_count)).collect::<Vec<_>>())
+        .collect();
+    assert_eq!(layout, [vec![(2, 1)], vec![(3, 1)], vec![(4, 1)], vec![], vec![]]);
+    assert_eq!(list.roots, 2);
+    free_selector_list(list);
+}
+
+#[test]
+fn invalid_selectors() {
+    assert_eq!(parse("a >").unwrap_err(), ErrorKind::EndOfInput);
+    assert_eq!(parse("a,").unwrap_err(), ErrorKind::EndOfInput);
+    assert_eq!(parse("a..b").unwrap_err(), ErrorKind::UnexpectedToken);
+    assert_eq!(parse("#1a").unwrap_err(), ErrorKind::UnexpectedToken);
+    assert_eq!(parse("[a~b]").unwrap_err(), ErrorKind::UnexpectedToken);
+    assert_eq!(parse("> a").unwrap_err(), ErrorKind::UnexpectedToken);
+}
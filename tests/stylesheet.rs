@@ -0,0 +1,113 @@
+//! The stylesheet AST and where its rules and declarations come from.
+mod common;
+
+use common::call_str;
+use cssparser_fii::{error::{ErrorKind, Status}, serialize::tokens_to_string, stylesheet::*};
+
+fn parse(css: &str) -> StyleSheet {
+    let (status, sheet, _) = call_str(css, parse_stylesheet);
+    assert_eq!(status, Status::Ok);
+    sheet
+}
+
+fn children(sheet: &StyleSheet, rule: &Rule) -> Vec<String> {
+    sheet.rules[rule.first_child..rule.first_child + rule.child_count].iter().map(|rule| tokens_to_string(&rule.prelude)).collect()
+}
+
+#[test]
+fn rules_and_declarations() {
+    let css = "@import \"a.css\";\na b { color: red !important; margin: 0 auto }";
+    let sheet = parse(css);
+    assert_eq!(sheet.roots, 2);
+
+    let import = &sheet.rules[0];
+    assert_eq!((import.rule_type, import.name.to_string(), import.has_block), (RuleType::At, "import".to_string(), false));
+    assert_eq!(tokens_to_string(&import.prelude), "\"a.css\"");
+    assert_eq!(&css[import.location.start..import.location.end], "@import \"a.css\";");
+
+    let rule = &sheet.rules[1];
+    assert_eq!((rule.rule_type, rule.has_block, rule.child_count), (RuleType::Qualified, true, 0));
+    // whitespace is kept in preludes, `a b` is not `ab`
+    assert_eq!(tokens_to_string(&rule.prelude), "a b");
+    assert_eq!(rule.location.line, 2);
+
+    let [color, margin] = &rule.declarations[..] else { panic!("expected two declarations") };
+    assert_eq!((color.name.to_string(), tokens_to_string(&color.value), color.important), ("color".to_string(), "red".to_string(), true));
+    assert_eq!(&css[color.location.start..color.location.end], "color: red !important");
+    assert_eq!((margin.name.to_string(), tokens_to_string(&margin.value), margin.important), ("margin".to_string(), "0 auto".to_string(), false));
+    free_stylesheet(sheet);
+}
+
+#[test]
+fn nested_rules() {
+    let sheet = parse("@media screen { a { b: c } d { e: f } } @font-face { src: url(f.woff) } g { h: i; & j { k: l } }");
+    assert_eq!(sheet.roots, 3);
+    let [media, font_face, style] = &sheet.rules[..3] else { unreachable!() };
+
+    assert_eq!(children(&sheet, media), ["a", "d"]);
+    assert!(media.declarations.is_empty());
+    assert_eq!(font_face.child_count, 0);
+    assert_eq!(font_face.declarations[0].name.to_string(), "src");
+    assert_eq!(children(&sheet, style), ["& j"]);
+    assert_eq!(style.declarations.len(), 1);
+    free_stylesheet(sheet);
+}
+
+#[test]
+fn invalid_declarations_are_reported() {
+    let css = "a { b: c; oops; d: e }";
+    let sheet = parse(css);
+    assert_eq!(sheet.rules[0].declarations.len(), 2);
+    let [error] = &sheet.errors[..] else { panic!("expected one error") };
+    assert_eq!(error.kind, ErrorKind::EndOfInput);
+    assert_eq!(&css[error.location.start..error.location.end], "oops;");
+    free_stylesheet(sheet);
+}
+
+#[test]
+fn test_css() {
+    let sheet = parse(include_str!("../test/test.css"));
+    let names: Vec<String> = sheet.rules[..sheet.roots].iter().map(|rule| rule.name.to_string()).collect();
+    assert_eq!(names.iter().filter(|name| *name == "keyframes").count(), 2);
+    assert!(sheet.errors.is_empty());
+    assert_eq!(sheet.rules[0].declarations.len(), 6);
+    free_stylesheet(sheet);
+}
+
+fn parse_list(css: &str) -> DeclarationList {
+    let (status, list, _) = call_str(css, parse_declaration_list);
+    assert_eq!(status, Status::Ok);
+    list
+}
+
+#[test]
+fn style_attribute() {
+    let css = "color: red; margin: 0 ! IMPORTANT;; --empty:;";
+    let list = parse_list(css);
+    let found: Vec<(String, String, bool, &str)> = list.declarations.iter()
+        .map(|d| (d.name.to_string(), tokens_to_string(&d.value), d.important, &css[d.location.start..d.location.end]))
+        .collect();
+    assert_eq!(found, [
+        ("color".to_string(), "red".to_string(), false, "color: red"),
+        ("margin".to_string(), "0".to_string(), true, "margin: 0 ! IMPORTANT"),
+        ("--empty".to_string(), "".to_string(), false, "--empty:"),
+    ]);
+    assert!(list.errors.is_empty());
+    free_declaration_list(list);
+}
+
+#[test]
+fn invalid_style_attribute_declarations_are_reported() {
+    let css = "color red; 12px: x; @media x { a: b } ok: yes";
+    let list = parse_list(css);
+    assert_eq!(list.declarations.len(), 1);
+    assert_eq!(list.declarations[0].name.to_string(), "ok");
+
+    let errors: Vec<(ErrorKind, &str)> = list.errors.iter().map(|e| (e.kind, &css[e.location.start..e.location.end])).collect();
+    assert_eq!(errors, [
+        (ErrorKind::UnexpectedToken, "color red;"),
+        (ErrorKind::UnexpectedToken, "12px: x;"),
+        (ErrorKind::AtRuleInvalid, "@media x { a: b }"),
+    ]);
+    free_declaration_list(list);
+}
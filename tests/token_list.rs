@@ -0,0 +1,85 @@
+//! Token lists, which hold all the tokens of a parse and their text.
+mod common;
+
+use common::call;
+use cssparser_fii::{
+    error::{ErrorKind, Status},
+    token_list::*,
+    token_types::TokenType,
+    view::TokenView,
+};
+use safer_ffi::prelude::repr_c;
+
+fn parse(css: &[u8]) -> repr_c::Box<TokenList> {
+    let (status, list, _) = call(|list, error| parse_css_list(css.as_ptr(), css.len(), list, error));
+    assert_eq!(status, Status::Ok);
+    list.unwrap()
+}
+
+/// The type and text of `token`, with its `(start, end, line, column)`.
+fn describe(token: &TokenView) -> (TokenType, String, (usize, usize, u32, u32)) {
+    assert!(token.text.owned || token.text.len == 0);
+    let location = token.location;
+    (token.token_type, unsafe { token.text.as_str() }.to_string(), (location.start, location.end, location.line, location.column))
+}
+
+#[test]
+fn values_and_locations() {
+    // the input is not needed once the list is built
+    let css = b"a {\n  margin: -2px 50% 'x\\79';\n}".to_vec();
+    let list = parse(&css);
+    drop(css);
+
+    let tokens: Vec<_> = (0..token_list_len(&list)).map(|index| describe(token_list_get(&list, index).unwrap())).collect();
+    assert_eq!(tokens, [
+        (TokenType::Ident, "a".to_string(), (0, 1, 1, 1)),
+        (TokenType::CurlyBracketBlock, String::new(), (2, 3, 1, 3)),
+        (TokenType::Ident, "margin".to_string(), (6, 12, 2, 3)),
+        (TokenType::Colon, String::new(), (12, 13, 2, 9)),
+        (TokenType::Dimension, "px".to_string(), (14, 18, 2, 11)),
+        (TokenType::Percentage, String::new(), (19, 22, 2, 16)),
+        (TokenType::QuotedString, "xy".to_string(), (23, 29, 2, 20)),
+        (TokenType::Semicolon, String::new(), (29, 30, 2, 26)),
+        (TokenType::CloseCurlyBracket, String::new(), (31, 32, 3, 1)),
+    ]);
+
+    let dimension = token_list_get(&list, 4).unwrap();
+    assert_eq!((dimension.value, dimension.int_value, dimension.has_int_value, dimension.has_sign), (-2.0, -2, true, true));
+    let percentage = token_list_get(&list, 5).unwrap();
+    assert_eq!((percentage.value, percentage.int_value, percentage.has_int_value, percentage.has_sign), (0.5, 50, true, false));
+
+    assert!(std::ptr::eq(token_list_tokens(&list), token_list_get(&list, 0).unwrap()));
+    assert!(token_list_get(&list, 9).is_none());
+    token_list_free(list);
+}
+
+#[test]
+fn blocks_left_open_are_closed() {
+    // like in `parse_css`, functions are not closed
+    let list = parse(b"f([a");
+    let tokens: Vec<_> = (0..token_list_len(&list)).map(|index| describe(token_list_get(&list, index).unwrap())).collect();
+    assert_eq!(tokens, [
+        (TokenType::Function, "f".to_string(), (0, 2, 1, 1)),
+        (TokenType::SquareBracketBlock, String::new(), (2, 3, 1, 3)),
+        (TokenType::Ident, "a".to_string(), (3, 4, 1, 4)),
+        (TokenType::CloseSquareBracket, String::new(), (4, 4, 1, 5)),
+    ]);
+    token_list_free(list);
+}
+
+#[test]
+fn empty_and_invalid_input() {
+    let (status, list, _) = call(|list, error| parse_css_list(std::ptr::null(), 0, list, error));
+    assert_eq!(status, Status::Ok);
+    let list = list.unwrap();
+    assert_eq!(token_list_len(&list), 0);
+    token_list_free(list);
+
+    let (status, list, error) = call(|list, error| parse_css_list(std::ptr::null(), 1, list, error));
+    assert_eq!((status, error.kind), (Status::InvalidInput, ErrorKind::NullPointer));
+    assert!(list.is_none());
+
+    let list = parse(b"\xffa");
+    assert_eq!(describe(token_list_get(&list, 0).unwrap()), (TokenType::Ident, "\u{fffd}a".to_string(), (0, 4, 1, 1)));
+    token_list_free(list);
+}
@@ -0,0 +1,131 @@
+//! The incremental tokenizer.
+mod common;
+
+use std::mem::MaybeUninit;
+
+use common::call;
+use cssparser_fii::{
+    error::{Error, ErrorKind, Status},
+    free_token,
+    tokenizer::*,
+    Token,
+};
+use cssparser::ToCss;
+use safer_ffi::prelude::{repr_c, AsOut, Out};
+
+fn new_tokenizer(css: &[u8]) -> repr_c::Box<Tokenizer> {
+    let (status, tokenizer, _) = call(|tokenizer, error| tokenizer_new(css.as_ptr(), css.len(), tokenizer, error));
+    assert_eq!(status, Status::Ok);
+    tokenizer.unwrap()
+}
+
+/// The token `function` writes, which it does not at the end of the input.
+fn next(tokenizer: &mut Tokenizer, function: extern "C" fn(&mut Tokenizer, Out<'_, Token>, Out<'_, bool>, Option<Out<'_, Error>>) -> Status) -> (Status, Option<Token>, Error) {
+    let mut token = MaybeUninit::uninit();
+    let (status, has_token, error) = call(|has_token, error| function(tokenizer, token.as_out(), has_token, error));
+    (status, has_token.then(|| unsafe { token.assume_init() }), error)
+}
+
+/// `(start, end, line, column)`
+type Location = (usize, usize, u32, u32);
+
+/// Every token left as CSS, with its location.
+fn rest(tokenizer: &mut Tokenizer) -> Vec<(String, Location)> {
+    let mut tokens = Vec::new();
+    loop {
+        let (status, token, error) = next(tokenizer, tokenizer_next);
+        assert_eq!((status, error.kind), (Status::Ok, ErrorKind::None));
+        let Some(token) = token else { return tokens };
+        let location = token.location;
+        let css = cssparser::Token::from(&*token.value).to_css_string();
+        tokens.push((css, (location.start, location.end, location.line, location.column)));
+        free_token(token);
+    }
+}
+
+fn owned(tokens: &[(&str, Location)]) -> Vec<(String, Location)> {
+    tokens.iter().map(|(css, location)| (css.to_string(), *location)).collect()
+}
+
+#[test]
+fn every_token_with_its_location() {
+    let mut tokenizer = new_tokenizer(b"a {\n  color: red;\n}\n/* c */ )");
+    assert_eq!(rest(&mut tokenizer), owned(&[
+        ("a", (0, 1, 1, 1)),
+        (" ", (1, 2, 1, 2)),
+        ("{", (2, 3, 1, 3)),
+        ("\n  ", (3, 6, 1, 4)),
+        ("color", (6, 11, 2, 3)),
+        (":", (11, 12, 2, 8)),
+        (" ", (12, 13, 2, 9)),
+        ("red", (13, 16, 2, 10)),
+        (";", (16, 17, 2, 13)),
+        ("\n", (17, 18, 2, 14)),
+        ("}", (18, 19, 3, 1)),
+        ("\n", (19, 20, 3, 2)),
+        ("/* c */", (20, 27, 4, 1)),
+        (" ", (27, 28, 4, 8)),
+        // closing tokens are the ones found in the input, none are synthesized
+        (")", (28, 29, 4, 9)),
+    ]));
+    let position = tokenizer_position(&tokenizer);
+    assert_eq!((position.start, position.end, position.line, position.column), (29, 29, 4, 10));
+
+    // the end of the input is not an error and is reported again and again, without a token
+    let (status, token, _) = next(&mut tokenizer, tokenizer_next);
+    assert_eq!(status, Status::Ok);
+    assert!(token.is_none());
+    assert_eq!(tokenizer_position(&tokenizer).start, 29);
+    tokenizer_free(tokenizer);
+
+    let mut tokenizer = new_tokenizer(b"f(");
+    assert_eq!(rest(&mut tokenizer), owned(&[("f(", (0, 2, 1, 1))]));
+    tokenizer_free(tokenizer);
+}
+
+#[test]
+fn peek_does_not_advance() {
+    let mut tokenizer = new_tokenizer(b"[ x ]");
+    free_token(next(&mut tokenizer, tokenizer_next).1.unwrap());
+    for _ in 0..2 {
+        let (status, token, _) = next(&mut tokenizer, tokenizer_peek);
+        let token = token.unwrap();
+        assert_eq!((status, token.location.start, token.location.end), (Status::Ok, 1, 2));
+        assert_eq!(tokenizer_position(&tokenizer).start, 1);
+        free_token(token);
+    }
+    // the content of a block peeked into is not skipped
+    assert_eq!(rest(&mut tokenizer).into_iter().map(|(token, _)| token).collect::<Vec<_>>(), [
+        " ",
+        "x",
+        " ",
+        "]",
+    ]);
+
+    let (status, token, _) = next(&mut tokenizer, tokenizer_peek);
+    assert_eq!(status, Status::Ok);
+    assert!(token.is_none());
+    tokenizer_free(tokenizer);
+}
+
+#[test]
+fn empty_and_invalid_input() {
+    let (status, empty, _) = call(|empty, error| tokenizer_new(std::ptr::null(), 0, empty, error));
+    assert_eq!(status, Status::Ok);
+    let mut empty = empty.unwrap();
+    assert!(rest(&mut empty).is_empty());
+    tokenizer_free(empty);
+
+    let (status, missing, error) = call(|missing, error| tokenizer_new(std::ptr::null(), 3, missing, error));
+    assert_eq!((status, error.kind), (Status::InvalidInput, ErrorKind::NullPointer));
+    assert!(missing.is_none());
+
+    // invalid UTF-8 is replaced, the locations count the bytes of the replacement
+    let mut tokenizer = new_tokenizer(b"a\xff b");
+    assert_eq!(rest(&mut tokenizer), owned(&[
+        ("a\u{fffd}", (0, 4, 1, 1)),
+        (" ", (4, 5, 1, 3)),
+        ("b", (5, 6, 1, 4)),
+    ]));
+    tokenizer_free(tokenizer);
+}
@@ -0,0 +1,67 @@
+//! Trees of component values.
+mod common;
+
+use common::{call, call_str};
+use cssparser::ToCss;
+use cssparser_fii::{error::{ErrorKind, Status}, tree::*};
+
+fn parse(css: &str) -> TokenTree {
+    let (status, tree, _) = call_str(css, parse_css_tree);
+    assert_eq!(status, Status::Ok);
+    tree
+}
+
+/// Every node as CSS, with its first child, its child count and where it is closed.
+fn nodes(tree: &TokenTree) -> Vec<(String, usize, usize, (usize, usize))> {
+    tree.nodes.iter()
+        .map(|node| {
+            let css = cssparser::Token::from(&*node.token.value).to_css_string();
+            (css, node.first_child, node.child_count, (node.closing.start, node.closing.end))
+        })
+        .collect()
+}
+
+#[test]
+fn nested_blocks() {
+    let tree = parse("a { b(c [d]) e } f(");
+    assert_eq!(tree.roots, 3);
+    assert_eq!(nodes(&tree), [
+        ("a".to_string(), 0, 0, (0, 0)),
+        ("{".to_string(), 3, 2, (15, 16)),
+        // left open, so it is closed where the input ends
+        ("f(".to_string(), 0, 0, (19, 19)),
+        ("b(".to_string(), 5, 2, (11, 12)),
+        ("e".to_string(), 0, 0, (0, 0)),
+        ("c".to_string(), 0, 0, (0, 0)),
+        ("[".to_string(), 7, 1, (10, 11)),
+        ("d".to_string(), 0, 0, (0, 0)),
+    ]);
+    let locations: Vec<_> = tree.nodes.iter().map(|node| (node.token.location.start, node.token.location.end)).collect();
+    assert_eq!(locations, [(0, 1), (2, 3), (17, 19), (4, 6), (13, 14), (6, 7), (8, 9), (9, 10)]);
+    free_tree(tree);
+
+    // every node of a level comes before the nodes of the next one
+    let tree = parse("(a (b)) [c]");
+    let nodes: Vec<_> = nodes(&tree).into_iter().map(|(css, first_child, child_count, _)| (css, first_child, child_count)).collect();
+    assert_eq!(nodes, [
+        ("(".to_string(), 2, 2),
+        ("[".to_string(), 4, 1),
+        ("a".to_string(), 0, 0),
+        ("(".to_string(), 5, 1),
+        ("c".to_string(), 0, 0),
+        ("b".to_string(), 0, 0),
+    ]);
+    free_tree(tree);
+}
+
+#[test]
+fn empty_and_invalid_input() {
+    let tree = parse(" /* nothing */ ");
+    assert!(tree.nodes.is_empty() && tree.roots == 0);
+    free_tree(tree);
+
+    let (status, tree, error) = call(|tree, error| parse_css_tree(std::ptr::null(), tree, error));
+    assert_eq!((status, error.kind), (Status::InvalidInput, ErrorKind::NullPointer));
+    assert!(tree.nodes.is_empty());
+    free_tree(tree);
+}
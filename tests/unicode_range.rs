@@ -0,0 +1,50 @@
+//! `unicode-range` values, including the ones that tokenize in surprising ways.
+mod common;
+
+use common::call_str;
+use cssparser_fii::{error::{Error, Status}, unicode_range::*};
+
+fn parse(css: &str) -> Result<Vec<(u32, u32)>, Error> {
+    let (status, ranges, error) = call_str(css, parse_unicode_range);
+    let pairs = ranges.iter().map(|range| (range.start, range.end)).collect();
+    free_unicode_ranges(ranges);
+    match status {
+        Status::Ok => Ok(pairs),
+        _ => Err(error),
+    }
+}
+
+#[test]
+fn valid() {
+    assert_eq!(parse("U+0025-00FF, u+4??").unwrap(), [(0x25, 0xFF), (0x400, 0x4FF)]);
+    for (css, expected) in [
+        ("U+26", (0x26, 0x26)),
+        ("u+0-7F", (0, 0x7F)),
+        ("U+1e3", (0x1E3, 0x1E3)),
+        ("U+1e3-1e5", (0x1E3, 0x1E5)),
+        ("U+?????", (0, 0xFFFFF)),
+        ("U+10????", (0x100000, 0x10FFFF)),
+        ("U+10FFFF", (0x10FFFF, 0x10FFFF)),
+    ] {
+        assert_eq!(parse(css).unwrap(), [expected], "{css}");
+    }
+}
+
+#[test]
+fn invalid() {
+    for css in ["", "U+", "U+110000", "U+FF-00", "U+1???-2", "U+0025 - 00FF", "V+12", "U+12,"] {
+        assert!(parse(css).is_err(), "{css}");
+    }
+
+    let error = parse("U+0-7F,  U+FF-00").unwrap_err();
+    assert_eq!((error.location.start, error.location.line, error.location.column), (9, 1, 10));
+}
+
+#[test]
+fn contains() {
+    let ranges = [UnicodeRange { start: 0x25, end: 0xFF }, UnicodeRange { start: 0x400, end: 0x4FF }];
+    let contains = |codepoint| unicode_range_contains((&ranges[..]).into(), codepoint);
+    assert!(contains(0x25) && contains(0xFF) && contains(0x42F));
+    assert!(!contains(0x24) && !contains(0x100) && !contains(0x10FFFF));
+    assert!(!unicode_range_contains((&[][..]).into(), 0));
+}
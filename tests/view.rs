@@ -0,0 +1,68 @@
+//! Token views, whose text points into the input unless it had to be unescaped.
+mod common;
+
+use common::call;
+use cssparser_fii::{
+    error::{ErrorKind, Status},
+    token_types::TokenType,
+    view::*,
+};
+
+fn parse(css: &[u8]) -> (Status, TokenViews, ErrorKind) {
+    let (status, views, error) = call(|views, error| parse_css_views(css.as_ptr(), css.len(), views, error));
+    (status, views, error.kind)
+}
+
+#[test]
+fn text_points_into_the_input() {
+    let css = "#i\\64  .b > c:hover { content: 'x\\79'; width: 2px * 3 }";
+    let (status, views, _) = parse(css.as_bytes());
+    assert_eq!(status, Status::Ok);
+
+    let input = css.as_bytes().as_ptr_range();
+    let tokens: Vec<_> = views.tokens.iter()
+        .filter(|token| token.text.len > 0)
+        .map(|token| {
+            // the text of a view that is not owned is the input itself
+            let inside = input.contains(&token.text.ptr);
+            assert_eq!(inside, !token.text.owned, "{:?}", token.token_type);
+            (token.token_type, unsafe { token.text.as_str() }, token.text.owned)
+        })
+        .collect();
+    assert_eq!(tokens, [
+        // escapes are unescaped into the arena
+        (TokenType::IDHash, "id", true),
+        (TokenType::Delim, ".", false),
+        (TokenType::Ident, "b", false),
+        (TokenType::Delim, ">", false),
+        (TokenType::Ident, "c", false),
+        (TokenType::Ident, "hover", false),
+        (TokenType::Ident, "content", false),
+        (TokenType::QuotedString, "xy", true),
+        (TokenType::Ident, "width", false),
+        (TokenType::Dimension, "px", false),
+        (TokenType::Delim, "*", false),
+    ]);
+    let three = views.tokens.iter().find(|token| token.token_type == TokenType::Number).unwrap();
+    assert_eq!((three.value, three.int_value, three.has_int_value), (3.0, 3, true));
+    assert_eq!((three.location.start, three.location.end), (52, 53));
+    free_token_views(views);
+}
+
+#[test]
+fn empty_and_invalid_input() {
+    let (status, views, _) = parse(b"");
+    assert_eq!(status, Status::Ok);
+    assert!(views.tokens.is_empty());
+    free_token_views(views);
+
+    let (status, views, error) = call(|views, error| parse_css_views(std::ptr::null(), 2, views, error));
+    assert_eq!((status, error.kind), (Status::InvalidInput, ErrorKind::NullPointer));
+    free_token_views(views);
+
+    // views cannot point into a replacement character, so the input has to be UTF-8
+    let (status, views, kind) = parse(b"a\xff");
+    assert_eq!((status, kind), (Status::InvalidInput, ErrorKind::InvalidUtf8));
+    assert!(views.tokens.is_empty());
+    free_token_views(views);
+}